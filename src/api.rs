@@ -18,22 +18,178 @@ pub struct Message {
     pub tool_call_id: Option<String>,
 }
 
+/// Server-sent event conventions of the chat completions stream.
+/// Defaults match OpenAI-compatible APIs; some providers differ slightly.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct StreamFormat {
+    /// Prefix of each event line carrying a JSON chunk
+    pub data_prefix: String,
+    /// Payload that marks the end of the stream
+    pub done_sentinel: String,
+    /// How long to keep reading after the sentinel, for providers that send the
+    /// final usage chunk after it (0 stops at the sentinel)
+    pub done_grace_ms: u64,
+}
+
+impl Default for StreamFormat {
+    fn default() -> Self {
+        StreamFormat {
+            data_prefix: "data: ".to_string(),
+            done_sentinel: "[DONE]".to_string(),
+            done_grace_ms: 500,
+        }
+    }
+}
+
+/// One line of the stream, classified
+#[derive(Debug, PartialEq)]
+pub enum StreamLine<'a> {
+    Data(&'a str),
+    Done,
+    Skip,
+}
+
+impl StreamFormat {
+    /// Classify a (trimmed) stream line
+    pub fn parse_line<'a>(&self, line: &'a str) -> StreamLine<'a> {
+        // Tolerate a missing space after the prefix (e.g. "data:{...}")
+        let prefix = self.data_prefix.trim_end();
+        let payload = match line.strip_prefix(prefix) {
+            Some(rest) => rest.trim_start(),
+            None => return StreamLine::Skip,
+        };
+        if payload == self.done_sentinel {
+            StreamLine::Done
+        } else if payload.is_empty() {
+            StreamLine::Skip
+        } else {
+            StreamLine::Data(payload)
+        }
+    }
+}
+
+/// Splits the response body into event payloads as chunks arrive. Lines after
+/// the end sentinel are still returned (a late usage chunk); the caller stops
+/// reading once `grace_deadline` passes.
+pub struct SseReader {
+    format: StreamFormat,
+    buffer: String,
+    done_at: Option<tokio::time::Instant>,
+}
+
+impl SseReader {
+    pub fn new(format: StreamFormat) -> Self {
+        SseReader { format, buffer: String::new(), done_at: None }
+    }
+
+    /// JSON payloads of the complete lines received so far; a partial last
+    /// line is kept until the rest of it arrives
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.push_str(&String::from_utf8_lossy(chunk));
+        let mut payloads = Vec::new();
+        while let Some(newline_pos) = self.buffer.find('\n') {
+            let line: String = self.buffer.drain(..=newline_pos).collect();
+            match self.format.parse_line(line.trim()) {
+                StreamLine::Data(json) => payloads.push(json.to_string()),
+                StreamLine::Done => {
+                    self.done_at.get_or_insert_with(tokio::time::Instant::now);
+                }
+                StreamLine::Skip => {}
+            }
+        }
+        payloads
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done_at.is_some()
+    }
+
+    /// When to stop waiting for more chunks, once the sentinel has been seen
+    pub fn grace_deadline(&self) -> Option<tokio::time::Instant> {
+        self.done_at.map(|at| at + std::time::Duration::from_millis(self.format.done_grace_ms))
+    }
+}
+
+/// Sampling parameters sent with every request; unset ones are left to the API
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Sampling {
+    pub temperature: Option<f64>,
+    pub seed: Option<u64>,
+    pub max_tokens: Option<u64>,
+}
+
+impl Sampling {
+    /// Add the parameters that are set to a request body
+    pub fn apply(&self, body: &mut Value) {
+        if let Some(temperature) = self.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(seed) = self.seed {
+            body["seed"] = json!(seed);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+    }
+}
+
+/// Environment variable holding the API key
+pub const API_KEY_ENV: &str = "XAI_API_KEY";
+
+/// Pick the API key: the environment wins, then the config file's `api_key`.
+/// Blank values count as unset.
+pub fn resolve_api_key(env_key: Option<String>, config_key: Option<&str>) -> Option<String> {
+    env_key
+        .filter(|key| !key.trim().is_empty())
+        .or_else(|| config_key.filter(|key| !key.trim().is_empty()).map(str::to_string))
+}
+
 pub struct GrokClient {
     client: Client,
     api_key: String,
     model: String,
+    api_url: String,
+    stream_format: StreamFormat,
+    sampling: Sampling,
 }
 
 impl GrokClient {
+    #[allow(dead_code)]
     pub fn new(model: String) -> Result<Self> {
-        let api_key = env::var("XAI_API_KEY").expect("XAI_API_KEY must be set");
+        Self::with_config_key(model, None)
+    }
+
+    /// Create a client, using `config_key` if XAI_API_KEY isn't set
+    pub fn with_config_key(model: String, config_key: Option<&str>) -> Result<Self> {
+        let api_key = resolve_api_key(env::var(API_KEY_ENV).ok(), config_key)
+            .ok_or_else(|| anyhow::anyhow!("{} is not set and config has no api_key", API_KEY_ENV))?;
         Ok(Self {
             client: Client::new(),
             api_key,
             model,
+            api_url: API_URL.to_string(),
+            stream_format: StreamFormat::default(),
+            sampling: Sampling::default(),
         })
     }
 
+    pub fn with_stream_format(mut self, stream_format: StreamFormat) -> Self {
+        self.stream_format = stream_format;
+        self
+    }
+
+    pub fn stream_format(&self) -> &StreamFormat {
+        &self.stream_format
+    }
+
+    /// Point the client at a different chat completions endpoint (used by tests)
+    #[allow(dead_code)]
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = api_url.to_string();
+        self
+    }
+
     pub fn update_model(&mut self, model: String) {
         self.model = model;
     }
@@ -42,20 +198,29 @@ impl GrokClient {
         &self.model
     }
 
+    pub fn sampling(&self) -> Sampling {
+        self.sampling
+    }
+
+    pub fn set_sampling(&mut self, sampling: Sampling) {
+        self.sampling = sampling;
+    }
+
     #[allow(dead_code)]
     pub async fn chat_completion(
         &self,
         messages: Vec<Message>,
         tools: Vec<Value>,
     ) -> Result<Value> {
-        let body = json!({
+        let mut body = json!({
             "model": self.model,
             "messages": messages,
             "tools": tools,
             "stream": false
         });
+        self.sampling.apply(&mut body);
 
-        let res = self.client.post(API_URL)
+        let res = self.client.post(&self.api_url)
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
             .json(&body)
@@ -86,7 +251,7 @@ impl GrokClient {
         tools: Vec<Value>,
         model: &str,
     ) -> Result<reqwest::Response> {
-        let body = json!({
+        let mut body = json!({
             "model": model,
             "messages": messages,
             "tools": tools,
@@ -95,8 +260,9 @@ impl GrokClient {
                 "include_usage": true
             }
         });
+        self.sampling.apply(&mut body);
 
-        let res = self.client.post(API_URL)
+        let res = self.client.post(&self.api_url)
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
             .json(&body)
@@ -111,3 +277,54 @@ impl GrokClient {
         Ok(res)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stream_with_custom_sentinel() {
+        let format = StreamFormat {
+            data_prefix: "data:".to_string(),
+            done_sentinel: "[END]".to_string(),
+            ..StreamFormat::default()
+        };
+
+        let stream = "data:{\"a\":1}\n: keep-alive\ndata: {\"b\":2}\ndata: [DONE]\ndata:[END]\ndata:{\"c\":3}";
+        let mut chunks = Vec::new();
+        for line in stream.lines() {
+            match format.parse_line(line.trim()) {
+                StreamLine::Data(json) => chunks.push(json),
+                StreamLine::Done => break,
+                StreamLine::Skip => {}
+            }
+        }
+
+        // The standard sentinel is just data here; only "[END]" stops the stream
+        assert_eq!(chunks, vec!["{\"a\":1}", "{\"b\":2}", "[DONE]"]);
+        assert_eq!(StreamFormat::default().parse_line("data: [DONE]"), StreamLine::Done);
+    }
+
+    #[test]
+    fn test_api_key_precedence() {
+        let env = || Some("env-key".to_string());
+
+        assert_eq!(resolve_api_key(env(), None).as_deref(), Some("env-key"));
+        assert_eq!(resolve_api_key(None, Some("config-key")).as_deref(), Some("config-key"));
+        // Environment wins when both are set
+        assert_eq!(resolve_api_key(env(), Some("config-key")).as_deref(), Some("env-key"));
+        // Blank values are treated as missing
+        assert_eq!(resolve_api_key(Some(" ".to_string()), Some("config-key")).as_deref(), Some("config-key"));
+        assert_eq!(resolve_api_key(None, Some("")), None);
+    }
+
+    #[test]
+    fn test_sampling_only_sends_set_parameters() {
+        let mut body = json!({"model": "grok-3"});
+        Sampling::default().apply(&mut body);
+        assert_eq!(body, json!({"model": "grok-3"}));
+
+        Sampling { temperature: Some(0.2), seed: None, max_tokens: Some(512) }.apply(&mut body);
+        assert_eq!(body, json!({"model": "grok-3", "temperature": 0.2, "max_tokens": 512}));
+    }
+}
@@ -0,0 +1,163 @@
+//! `--check`: validate setup without starting the UI or calling the API
+//!
+//! Prints one `PASS`/`FAIL` line per check so CI can grep it, and the process
+//! exits non-zero if anything failed.
+
+use std::fs;
+use std::path::Path;
+
+use crate::api::{resolve_api_key, API_KEY_ENV};
+use crate::config::Config;
+use crate::tool_plugins::{parse_tool_file, plugin_files};
+
+/// Outcome of a single check
+pub struct CheckResult {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+#[derive(Default)]
+pub struct CheckReport {
+    pub results: Vec<CheckResult>,
+}
+
+impl CheckReport {
+    fn record(&mut self, name: impl Into<String>, outcome: Result<String, String>) {
+        let (passed, detail) = match outcome {
+            Ok(detail) => (true, detail),
+            Err(detail) => (false, detail),
+        };
+        self.results.push(CheckResult { name: name.into(), passed, detail });
+    }
+
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|r| r.passed)
+    }
+
+    pub fn render(&self) -> String {
+        let mut out: Vec<String> = self
+            .results
+            .iter()
+            .map(|r| format!("{} {}: {}", if r.passed { "PASS" } else { "FAIL" }, r.name, r.detail))
+            .collect();
+        let failed = self.results.iter().filter(|r| !r.passed).count();
+        out.push(if failed == 0 {
+            format!("OK: {} checks passed", self.results.len())
+        } else {
+            format!("FAILED: {} of {} checks failed", failed, self.results.len())
+        });
+        out.join("\n")
+    }
+}
+
+/// Check the config and plugins in `config_dir` (config.json plus tools/),
+/// with `env_key` standing in for the XAI_API_KEY environment variable
+pub fn run(config_dir: &Path, env_key: Option<String>) -> CheckReport {
+    let mut report = CheckReport::default();
+
+    // Unlike load_config, a config that doesn't parse is an error here
+    let config_path = config_dir.join("config.json");
+    let config = if config_path.exists() {
+        match fs::read_to_string(&config_path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str::<Config>(&content).map_err(|e| e.to_string()))
+        {
+            Ok(config) => {
+                report.record("config", Ok(format!("{} is valid", config_path.display())));
+                config
+            }
+            Err(e) => {
+                report.record("config", Err(format!("{}: {}", config_path.display(), e)));
+                Config::default()
+            }
+        }
+    } else {
+        report.record("config", Ok(format!("{} not found, using defaults", config_path.display())));
+        Config::default()
+    };
+
+    let (trigger, target) = (config.compaction_trigger_pct, config.compaction_target_pct);
+    report.record(
+        "compaction",
+        if config.compaction_thresholds() == (trigger, target) {
+            Ok(format!("trigger {}%, target {}%", trigger, target))
+        } else {
+            Err(format!("target {}% must be above 0 and below trigger {}% (at most 100)", target, trigger))
+        },
+    );
+
+    // Known models are those with rate limits (the built-ins plus any the user added)
+    let known = |model: &str| config.rate_limits.contains_key(model);
+    report.record(
+        "model",
+        if known(&config.model) {
+            Ok(config.model.clone())
+        } else {
+            Err(format!("unknown model '{}' (add it to rate_limits if it's valid)", config.model))
+        },
+    );
+
+    // Never print the key itself
+    report.record(
+        "api key",
+        match (env_key.as_deref().filter(|k| !k.trim().is_empty()), resolve_api_key(env_key.clone(), config.api_key.as_deref())) {
+            (Some(_), _) => Ok(format!("from {}", API_KEY_ENV)),
+            (None, Some(_)) => Ok("from config api_key".to_string()),
+            (None, None) => Err(format!("not found; set {} or add \"api_key\" to config.json", API_KEY_ENV)),
+        },
+    );
+
+    let tools_dir = config_dir.join("tools");
+    let files = plugin_files(&tools_dir);
+    if files.is_empty() {
+        report.record("plugins", Ok(format!("none in {}", tools_dir.display())));
+    }
+    let mut seen: Vec<String> = Vec::new();
+    for file in files {
+        let label = format!("plugin {}", file.file_name().and_then(|n| n.to_str()).unwrap_or("?"));
+        let outcome = parse_tool_file(&file).and_then(|tool| {
+            if seen.contains(&tool.name) {
+                Err(format!("duplicate tool name '{}'", tool.name))
+            } else {
+                seen.push(tool.name.clone());
+                Ok(tool.name)
+            }
+        });
+        report.record(label, outcome);
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_fails_on_broken_plugin() {
+        let dir = tempfile::tempdir().unwrap();
+        let tools = dir.path().join("tools");
+        fs::create_dir_all(&tools).unwrap();
+        fs::write(dir.path().join("config.json"), r#"{"model": "grok-3"}"#).unwrap();
+        fs::write(tools.join("ok.yaml"), "name: hello\ndescription: Say hello\ncommand: echo hello\n").unwrap();
+        fs::write(tools.join("broken.yml"), "name: broken\ndescription: [unclosed\n").unwrap();
+
+        let report = run(dir.path(), Some("test-key".to_string()));
+        let rendered = report.render();
+
+        assert!(!report.passed());
+        assert!(rendered.contains("PASS plugin ok.yaml: hello"));
+        assert!(rendered.contains("FAIL plugin broken.yml: Failed to parse YAML"));
+        assert!(rendered.contains("PASS api key: from XAI_API_KEY"));
+        assert!(!rendered.contains("test-key"));
+        assert!(rendered.lines().last().unwrap().starts_with("FAILED: 1 of"));
+
+        // Without the broken plugin everything passes
+        fs::remove_file(tools.join("broken.yml")).unwrap();
+        assert!(run(dir.path(), Some("test-key".to_string())).passed());
+
+        // No key anywhere is a failure
+        assert!(!run(dir.path(), None).passed());
+    }
+}
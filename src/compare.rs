@@ -0,0 +1,215 @@
+//! `@@all:` / `/compare`: ask every configured role the same question
+//!
+//! A lighter-weight megamind: each role answers independently and concurrently,
+//! with no tools, no shared context and no synthesis. Answers are shown as one
+//! labeled section per role, in role-name order.
+
+use crate::api::{GrokClient, Message, SseReader};
+use crate::app::AppEvent;
+use crate::config::ModelRole;
+use serde_json::Value;
+use std::future::Future;
+use std::sync::{mpsc, Arc};
+
+/// One role's answer (or the error it hit)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoleAnswer {
+    pub role: String,
+    pub model: String,
+    pub answer: Result<String, String>,
+}
+
+impl RoleAnswer {
+    /// Labeled section shown in the chat
+    pub fn render(&self) -> String {
+        match &self.answer {
+            Ok(text) => format!("[@{} · {}]\n{}", self.role, self.model, text),
+            Err(e) => format!("[@{} · {}]\nError: {}", self.role, self.model, e),
+        }
+    }
+}
+
+/// The question after an `@@all:` prefix, if the message has one
+pub fn parse_broadcast(content: &str) -> Option<&str> {
+    let rest = content.trim().strip_prefix("@@")?;
+    let (target, question) = rest.split_once(':')?;
+    target.trim().eq_ignore_ascii_case("all").then(|| question.trim())
+}
+
+/// Ask every role `question` at once via `ask(role_name, role, question)`,
+/// returning the answers sorted by role name
+pub async fn broadcast<F, Fut>(
+    roles: &std::collections::HashMap<String, ModelRole>,
+    question: &str,
+    ask: F,
+) -> Vec<RoleAnswer>
+where
+    F: Fn(String, ModelRole, String) -> Fut,
+    Fut: Future<Output = Result<String, String>> + Send + 'static,
+{
+    let mut names: Vec<&String> = roles.keys().collect();
+    names.sort();
+
+    let handles: Vec<_> = names
+        .into_iter()
+        .map(|name| {
+            let role = roles[name].clone();
+            let model = role.model.clone();
+            (name.clone(), model, tokio::spawn(ask(name.clone(), role, question.to_string())))
+        })
+        .collect();
+
+    let mut answers = Vec::with_capacity(handles.len());
+    for (role, model, handle) in handles {
+        let answer = handle.await.unwrap_or_else(|e| Err(format!("task failed: {}", e)));
+        answers.push(RoleAnswer { role, model, answer });
+    }
+    answers
+}
+
+/// Ask one role with its model and prompt, collecting the streamed reply
+async fn ask_role(client: Arc<GrokClient>, role: ModelRole, question: String) -> Result<String, String> {
+    let mut messages = Vec::new();
+    if let Some(prompt) = role.prompt {
+        messages.push(Message { role: "system".to_string(), content: Some(prompt), tool_calls: None, tool_call_id: None });
+    }
+    messages.push(Message { role: "user".to_string(), content: Some(question), tool_calls: None, tool_call_id: None });
+
+    let mut response = client
+        .chat_completion_stream_with_model(messages, vec![], &role.model)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut sse = SseReader::new(client.stream_format().clone());
+    let mut answer = String::new();
+    // No usage is tracked here, so there is no need to wait past the end sentinel
+    while !sse.is_done() {
+        // A dropped connection is an error, not the end of the answer
+        let chunk = match response.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => return Err(format!("stream interrupted: {}", e)),
+        };
+        for json_str in sse.push(&chunk) {
+            if let Ok(val) = serde_json::from_str::<Value>(&json_str) {
+                if let Some(content) = val["choices"][0]["delta"]["content"].as_str() {
+                    answer.push_str(content);
+                }
+            }
+        }
+    }
+    Ok(answer.trim().to_string())
+}
+
+/// Run the comparison and report it to the UI
+pub async fn run_compare(
+    client: Arc<GrokClient>,
+    roles: std::collections::HashMap<String, ModelRole>,
+    question: String,
+    tx: mpsc::Sender<AppEvent>,
+) {
+    let _ = tx.send(AppEvent::StatusUpdate(format!("Asking {} roles...", roles.len())));
+    let answers = broadcast(&roles, &question, |_, role, question| ask_role(client.clone(), role, question)).await;
+    let _ = tx.send(AppEvent::CompareComplete(answers));
+    let _ = tx.send(AppEvent::Finished);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    fn role(model: &str) -> ModelRole {
+        ModelRole { model: model.to_string(), prompt: None, rate_limit: None }
+    }
+
+    #[test]
+    fn test_parse_broadcast() {
+        assert_eq!(parse_broadcast("@@all: which db?"), Some("which db?"));
+        assert_eq!(parse_broadcast("  @@ALL:which db?"), Some("which db?"));
+        assert_eq!(parse_broadcast("@planner: which db?"), None);
+        assert_eq!(parse_broadcast("@@coder: which db?"), None);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_asks_each_role_once() {
+        let roles: HashMap<String, ModelRole> = [
+            ("planner".to_string(), role("grok-4-1-fast-reasoning")),
+            ("coder".to_string(), role("grok-code-fast-1")),
+            ("reviewer".to_string(), role("grok-3-mini")),
+        ]
+        .into_iter()
+        .collect();
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let answers = broadcast(&roles, "Tabs or spaces?", |name, role, question| {
+            calls.lock().unwrap().push((name.clone(), question.clone()));
+            async move {
+                if name == "reviewer" {
+                    Err("rate limited".to_string())
+                } else {
+                    Ok(format!("{} says {}", role.model, question.len()))
+                }
+            }
+        })
+        .await;
+
+        let mut calls = calls.lock().unwrap().clone();
+        calls.sort();
+        assert_eq!(
+            calls,
+            vec![
+                ("coder".to_string(), "Tabs or spaces?".to_string()),
+                ("planner".to_string(), "Tabs or spaces?".to_string()),
+                ("reviewer".to_string(), "Tabs or spaces?".to_string()),
+            ]
+        );
+        let rendered: Vec<String> = answers.iter().map(RoleAnswer::render).collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "[@coder · grok-code-fast-1]\ngrok-code-fast-1 says 15",
+                "[@planner · grok-4-1-fast-reasoning]\ngrok-4-1-fast-reasoning says 15",
+                "[@reviewer · grok-3-mini]\nError: rate limited",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ask_role_collects_streamed_reply() {
+        let (url, bodies) = crate::app::tests::spawn_mock_model(vec![serde_json::json!({"content": " Spaces, always. "})]).await;
+        let client = Arc::new(crate::app::tests::test_client("grok-3").with_api_url(&url));
+        let reviewer = ModelRole { prompt: Some("You review code.".to_string()), ..role("grok-3-mini") };
+
+        let answer = ask_role(client, reviewer, "Tabs or spaces?".to_string()).await;
+        assert_eq!(answer, Ok("Spaces, always.".to_string()));
+        let request: Value = serde_json::from_str(&bodies.lock().unwrap()[0]).unwrap();
+        assert_eq!(request["model"], "grok-3-mini");
+        assert_eq!(request["messages"][0]["content"], "You review code.");
+    }
+
+    #[tokio::test]
+    async fn test_ask_role_reports_dropped_stream() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Promise a longer body than is sent, then hang up mid-answer
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1/chat/completions", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 8192];
+            socket.read(&mut buf).await.ok();
+            let partial = "data: {\"choices\": [{\"delta\": {\"content\": \"Spaces\"}}]}\n\n";
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: 1000\r\n\r\n{}",
+                partial
+            );
+            socket.write_all(response.as_bytes()).await.ok();
+        });
+        let client = Arc::new(crate::app::tests::test_client("grok-3").with_api_url(&url));
+
+        let answer = ask_role(client, role("grok-3-mini"), "Tabs or spaces?".to_string()).await;
+        assert!(answer.as_ref().is_err_and(|e| e.starts_with("stream interrupted")), "{:?}", answer);
+    }
+}
@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use serde_json::Value;
 
 use crate::settings::{Settings, RateLimitConfig, default_rate_limits};
 
@@ -16,6 +17,9 @@ pub struct ModelRole {
     /// Optional custom system prompt for this role
     #[serde(default)]
     pub prompt: Option<String>,
+    /// Optional rate limits for this role (e.g. a different API tier); overrides the model's
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -28,12 +32,131 @@ pub struct Config {
     /// Model roles for multi-model orchestration (e.g., @planner, @coder)
     #[serde(default)]
     pub roles: HashMap<String, ModelRole>,
+    /// API key used when XAI_API_KEY isn't set in the environment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
     /// User-toggleable settings
     #[serde(default)]
     pub settings: Settings,
+    /// Context window per model, for models the CLI doesn't know (e.g. behind a proxy)
+    /// or to correct a known one
+    #[serde(default)]
+    pub model_context: HashMap<String, usize>,
     /// Rate limits per model (can be customized by user)
     #[serde(default = "default_rate_limits")]
     pub rate_limits: HashMap<String, RateLimitConfig>,
+    /// Use a terse system prompt to save context on small-context models
+    #[serde(default)]
+    pub compact_prompt: bool,
+    /// Stop a turn after this many rounds of tool calls (0 = no limit)
+    #[serde(default = "default_max_tool_iterations")]
+    pub max_tool_iterations: usize,
+    /// How many times to nudge the model after an empty response before giving up
+    #[serde(default = "default_empty_response_retries")]
+    pub empty_response_retries: u8,
+    /// Compact the conversation once it fills this percentage of the context window
+    #[serde(default = "default_compaction_trigger_pct")]
+    pub compaction_trigger_pct: u8,
+    /// Percentage of the context window the kept recent messages may fill after compaction
+    #[serde(default = "default_compaction_target_pct")]
+    pub compaction_target_pct: u8,
+    /// Tokens kept free for the model's reply when deciding whether to compact
+    #[serde(default = "default_output_reserve_tokens")]
+    pub output_reserve_tokens: usize,
+    /// SSE conventions of the API stream (for non-standard OpenAI-compatible providers)
+    #[serde(default)]
+    pub stream_format: crate::api::StreamFormat,
+    /// Backend used by the WebSearch tool
+    #[serde(default)]
+    pub web_search: crate::web_search::WebSearchConfig,
+    /// Check command run by the Diagnostics tool
+    #[serde(default)]
+    pub diagnostics: crate::diagnostics::DiagnosticsConfig,
+    /// Size cap and rotation for grok-debug.log, grok-cli-errors.log and the crash log
+    #[serde(default)]
+    pub logging: crate::logging::LogConfig,
+    /// Whether prose sent alongside tool calls ("Let me check...") stays in the chat view
+    #[serde(default)]
+    pub interim_prose: InterimProse,
+    /// Color theme: dark, light or high-contrast
+    #[serde(default)]
+    pub theme: crate::theme::ThemeName,
+    /// Show assistant replies as raw markdown instead of rendered (toggle with /raw)
+    #[serde(default)]
+    pub raw_markdown: bool,
+    /// Models for /megamind's brainstorming agents and its synthesis step
+    #[serde(default)]
+    pub megamind: crate::megamind::MegamindConfig,
+    /// If non-empty, the only tools the model is offered (whatever the role)
+    #[serde(default)]
+    pub enabled_tools: Vec<String>,
+    /// Tools the model is never offered and may not run
+    #[serde(default)]
+    pub disabled_tools: Vec<String>,
+    /// Let tools reach the network (WebSearch); false refuses them without asking
+    #[serde(default = "default_true")]
+    pub allow_network: bool,
+    /// Let the model search saved sessions with SearchHistory (off by default)
+    #[serde(default)]
+    pub allow_history_search: bool,
+    /// Bash output returned to the model is cut in the middle beyond this many bytes (0 = no cap)
+    #[serde(default = "default_bash_output_limit")]
+    pub bash_output_limit: usize,
+    /// Bash commands are killed after this many seconds unless the call sets timeout_secs
+    #[serde(default = "default_bash_timeout_secs")]
+    pub bash_timeout_secs: u64,
+    /// While carrying out a confirmed plan, show each file change or command for apply/skip first
+    #[serde(default)]
+    pub preview_plan_steps: bool,
+    /// Save the conversation and unsent input every this many seconds when changed (0 = off)
+    #[serde(default = "default_autosave_secs")]
+    pub autosave_secs: u64,
+}
+
+/// Display of assistant text that arrives in the same message as tool calls.
+/// The model always sees it; this only affects the chat view.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum InterimProse {
+    #[default]
+    Show,
+    Hide,
+}
+
+fn default_max_tool_iterations() -> usize {
+    25
+}
+
+fn default_empty_response_retries() -> u8 {
+    2
+}
+
+fn default_compaction_trigger_pct() -> u8 {
+    60
+}
+
+fn default_compaction_target_pct() -> u8 {
+    30
+}
+
+fn default_output_reserve_tokens() -> usize {
+    8192
+}
+
+fn default_bash_output_limit() -> usize {
+    crate::tools::DEFAULT_BASH_OUTPUT_LIMIT
+}
+
+fn default_bash_timeout_secs() -> u64 {
+    crate::tools::DEFAULT_BASH_TIMEOUT_SECS
+}
+
+fn default_autosave_secs() -> u64 {
+    30
+}
+
+fn default_true() -> bool {
+    true
 }
 
 impl Default for Config {
@@ -44,18 +167,21 @@ impl Default for Config {
         roles.insert("planner".to_string(), ModelRole {
             model: "grok-4.1-fast-reasoning".to_string(),
             prompt: Some("You are a planning assistant. Analyze requests carefully, break them into steps, and create detailed implementation plans. Focus on the 'what' and 'why', not the 'how'. When your plan is complete, hand off to @coder for implementation.".to_string()),
+            rate_limit: None,
         });
 
         // Default coder role - optimized for code
         roles.insert("coder".to_string(), ModelRole {
             model: "grok-code-fast-1".to_string(),
             prompt: Some("You are a code execution assistant. Implement the plan given to you efficiently. Use tools to read, edit, and test code. Be concise and focus on execution.".to_string()),
+            rate_limit: None,
         });
 
         // Default reviewer role - quick verification
         roles.insert("reviewer".to_string(), ModelRole {
             model: "grok-3-mini".to_string(),
             prompt: Some("You are a code reviewer. Check the implementation for bugs, edge cases, and improvements. Be concise.".to_string()),
+            rate_limit: None,
         });
 
         Config {
@@ -63,13 +189,58 @@ impl Default for Config {
             sandbox_enabled: false,
             allowed_commands: HashMap::new(),
             roles,
+            api_key: None,
             settings: Settings::default(),
+            model_context: HashMap::new(),
             rate_limits: default_rate_limits(),
+            compact_prompt: false,
+            max_tool_iterations: default_max_tool_iterations(),
+            empty_response_retries: default_empty_response_retries(),
+            compaction_trigger_pct: default_compaction_trigger_pct(),
+            compaction_target_pct: default_compaction_target_pct(),
+            output_reserve_tokens: default_output_reserve_tokens(),
+            stream_format: crate::api::StreamFormat::default(),
+            web_search: crate::web_search::WebSearchConfig::default(),
+            diagnostics: crate::diagnostics::DiagnosticsConfig::default(),
+            logging: crate::logging::LogConfig::default(),
+            interim_prose: InterimProse::default(),
+            theme: crate::theme::ThemeName::default(),
+            raw_markdown: false,
+            megamind: crate::megamind::MegamindConfig::default(),
+            enabled_tools: Vec::new(),
+            disabled_tools: Vec::new(),
+            allow_network: true,
+            allow_history_search: false,
+            autosave_secs: default_autosave_secs(),
+            preview_plan_steps: false,
+            bash_output_limit: default_bash_output_limit(),
+            bash_timeout_secs: default_bash_timeout_secs(),
         }
     }
 }
 
 impl Config {
+    /// Settings the builtin tools run with
+    pub fn tool_settings(&self) -> crate::tools::ToolSettings {
+        crate::tools::ToolSettings {
+            filter: self.tool_filter(),
+            bash_output_limit: self.bash_output_limit,
+            bash_timeout_secs: self.bash_timeout_secs,
+            diagnostics: self.diagnostics.clone(),
+            web_search: self.web_search.clone(),
+        }
+    }
+
+    /// Tool allow/deny lists, whatever the active role
+    pub fn tool_filter(&self) -> crate::tools::ToolFilter {
+        crate::tools::ToolFilter {
+            enabled: self.enabled_tools.clone(),
+            disabled: self.disabled_tools.clone(),
+            offline: !self.allow_network,
+            history_search: self.allow_history_search,
+        }
+    }
+
     /// Check if a command is allowed for the given directory
     #[allow(dead_code)]
     pub fn is_command_allowed(&self, command: &str, cwd: &str) -> bool {
@@ -97,18 +268,42 @@ impl Config {
     pub fn get_rate_limit(&self, model: &str) -> Option<&RateLimitConfig> {
         self.rate_limits.get(model)
     }
+
+    /// Get rate limit config for a role: its own limits, else those of the role's model
+    pub fn get_role_rate_limit(&self, name: &str) -> Option<&RateLimitConfig> {
+        let role = self.get_role(name)?;
+        role.rate_limit.as_ref().or_else(|| self.get_rate_limit(&role.model))
+    }
+
+    /// Compaction (trigger, target) percentages, falling back to the defaults
+    /// unless 0 < target < trigger <= 100
+    pub fn compaction_thresholds(&self) -> (u8, u8) {
+        let (trigger, target) = (self.compaction_trigger_pct, self.compaction_target_pct);
+        if target > 0 && target < trigger && trigger <= 100 {
+            (trigger, target)
+        } else {
+            (default_compaction_trigger_pct(), default_compaction_target_pct())
+        }
+    }
+
+    /// Thresholds for compacting inside a running turn: the trigger is 10
+    /// points higher than between turns, so a turn is only interrupted when
+    /// the between-turn compaction couldn't keep the history small enough
+    pub fn mid_turn_compaction_thresholds(&self) -> (u8, u8) {
+        let (trigger, target) = self.compaction_thresholds();
+        (trigger.saturating_add(10).min(100), target)
+    }
 }
 
-fn get_config_path() -> PathBuf {
+pub fn get_config_path() -> PathBuf {
     let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
     PathBuf::from(home).join(CONFIG_DIR).join(CONFIG_FILE)
 }
 
-pub fn load_config() -> Config {
-    let path = get_config_path();
-
+/// Load the config at `path`, falling back to the defaults
+pub fn load_config_from(path: &Path) -> Config {
     let mut config = if path.exists() {
-        match fs::read_to_string(&path) {
+        match fs::read_to_string(path) {
             Ok(content) => {
                 serde_json::from_str(&content).unwrap_or_default()
             }
@@ -127,20 +322,84 @@ pub fn load_config() -> Config {
     config
 }
 
-pub fn save_config(config: &Config) -> Result<(), std::io::Error> {
-    let path = get_config_path();
+pub fn load_config() -> Config {
+    load_config_from(&get_config_path())
+}
 
+pub fn save_config_to(config: &Config, path: &Path) -> Result<(), std::io::Error> {
     // Create config directory if it doesn't exist
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
 
     let content = serde_json::to_string_pretty(config)?;
-    fs::write(&path, content)?;
+    fs::write(path, content)?;
 
     Ok(())
 }
 
+pub fn save_config(config: &Config) -> Result<(), std::io::Error> {
+    save_config_to(config, &get_config_path())
+}
+
+/// The config in effect as pretty JSON, with secrets replaced by a marker
+pub fn redacted_config_json(config: &Config) -> String {
+    let mut value = serde_json::to_value(config).unwrap_or_default();
+    for pointer in ["/api_key", "/web_search/api_key"] {
+        if let Some(secret) = value.pointer_mut(pointer) {
+            *secret = Value::String("<redacted>".to_string());
+        }
+    }
+    serde_json::to_string_pretty(&value).unwrap_or_default()
+}
+
+/// Allowed commands per directory, sorted and without duplicates
+pub fn allowlist(config: &Config) -> std::collections::BTreeMap<String, Vec<String>> {
+    config
+        .allowed_commands
+        .iter()
+        .filter(|(_, commands)| !commands.is_empty())
+        .map(|(dir, commands)| {
+            let mut commands = commands.clone();
+            commands.sort();
+            commands.dedup();
+            (dir.clone(), commands)
+        })
+        .collect()
+}
+
+/// Where `/allowlist export` writes by default, next to the global config
+pub fn allowlist_export_path() -> PathBuf {
+    get_config_path().with_file_name("allowlist.json")
+}
+
+/// Write the allowlist to `path` as `{"allowed_commands": ...}`, for backup or
+/// review. An existing file is only replaced if it is such an export itself, so
+/// pointing this at config.json can't wipe the config.
+/// Returns how many commands were written.
+pub fn export_allowlist(config: &Config, path: &Path) -> Result<usize, std::io::Error> {
+    if path.exists() {
+        let existing: Option<Value> = fs::read_to_string(path).ok().and_then(|content| serde_json::from_str(&content).ok());
+        let is_export = existing
+            .as_ref()
+            .and_then(Value::as_object)
+            .is_some_and(|o| o.keys().all(|k| k == "allowed_commands"));
+        if !is_export {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not an allowlist export", path.display()),
+            ));
+        }
+    }
+    let allowlist = allowlist(config);
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let value = serde_json::json!({ "allowed_commands": allowlist });
+    fs::write(path, serde_json::to_string_pretty(&value)? + "\n")?;
+    Ok(allowlist.values().map(Vec::len).sum())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -161,4 +420,74 @@ mod tests {
         assert!(!config.is_command_allowed("pwd", "/home"));
         assert!(!config.is_command_allowed("ls", "/tmp"));
     }
+
+    #[test]
+    fn test_export_allowlist() {
+        let mut config = Config::default();
+        config.allow_command("cargo test".to_string(), "/work/app".to_string());
+        config.allow_command("cargo build".to_string(), "/work/app".to_string());
+        config.allow_command("cargo test".to_string(), "/work/app".to_string());
+        config.allow_command("make".to_string(), "/work/lib".to_string());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("allowlist.json");
+
+        assert_eq!(export_allowlist(&config, &path).unwrap(), 3);
+        let written: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            written,
+            serde_json::json!({
+                "allowed_commands": {
+                    "/work/app": ["cargo build", "cargo test"],
+                    "/work/lib": ["make"]
+                }
+            })
+        );
+
+        // An earlier export is replaced, any other file is left alone
+        config.allow_command("make".to_string(), "/work/app".to_string());
+        assert_eq!(export_allowlist(&config, &path).unwrap(), 4);
+        let other = dir.path().join("config.json");
+        fs::write(&other, r#"{"model": "grok-3"}"#).unwrap();
+        let err = export_allowlist(&config, &other).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&other).unwrap(), r#"{"model": "grok-3"}"#);
+    }
+
+    #[test]
+    fn test_role_rate_limit_overrides_model() {
+        let mut config = Config::default();
+        let coder_model = config.roles["coder"].model.clone();
+        config.rate_limits.insert(coder_model.clone(), RateLimitConfig::new(100_000, 1_000, 10));
+
+        // No role override - falls back to the role's model limits
+        assert_eq!(config.get_role_rate_limit("coder").unwrap().tpm, 1_000);
+
+        config.roles.get_mut("coder").unwrap().rate_limit = Some(RateLimitConfig::new(100_000, 50, 2));
+        let limit = config.get_role_rate_limit("coder").unwrap();
+        assert_eq!((limit.tpm, limit.rpm), (50, 2));
+        assert_eq!(config.get_rate_limit(&coder_model).unwrap().tpm, 1_000);
+
+        assert!(config.get_role_rate_limit("unknown").is_none());
+    }
+
+    #[test]
+    fn test_compaction_thresholds_validated() {
+        let mut config = Config::default();
+        assert_eq!(config.compaction_thresholds(), (60, 30));
+
+        config.compaction_trigger_pct = 80;
+        config.compaction_target_pct = 40;
+        assert_eq!(config.compaction_thresholds(), (80, 40));
+
+        // Target at or above trigger makes no sense - use the defaults
+        config.compaction_target_pct = 80;
+        assert_eq!(config.compaction_thresholds(), (60, 30));
+        config.compaction_trigger_pct = 150;
+        config.compaction_target_pct = 40;
+        assert_eq!(config.compaction_thresholds(), (60, 30));
+        assert_eq!(config.mid_turn_compaction_thresholds(), (70, 30));
+        config.compaction_trigger_pct = 95;
+        assert_eq!(config.mid_turn_compaction_thresholds(), (100, 40));
+    }
 }
@@ -0,0 +1,256 @@
+//! The Diagnostics tool: run the project's check command and report errors
+//!
+//! Output is parsed into one line per diagnostic (`file:line:col: severity: message`)
+//! and deduplicated, so the model gets clean signal instead of raw compiler output.
+//! `cargo --message-format=json` is parsed structurally; anything else (tsc, ruff,
+//! gcc, eslint's unix format...) is matched line by line.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+/// Which command Diagnostics runs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct DiagnosticsConfig {
+    /// Shell command to run. When unset it is picked from the project type
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Maximum number of diagnostics returned to the model
+    pub max_results: usize,
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        DiagnosticsConfig { command: None, max_results: 50 }
+    }
+}
+
+/// One compiler or linter finding
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: Option<usize>,
+    pub severity: String,
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn render(&self) -> String {
+        let location = match self.column {
+            Some(col) => format!("{}:{}:{}", self.file, self.line, col),
+            None => format!("{}:{}", self.file, self.line),
+        };
+        match &self.code {
+            Some(code) => format!("{}: {}[{}]: {}", location, self.severity, code, self.message),
+            None => format!("{}: {}: {}", location, self.severity, self.message),
+        }
+    }
+}
+
+/// Check command for the project in `dir`, guessed from its manifest files
+pub fn detect_command(dir: &Path) -> Option<String> {
+    crate::project::detect(dir).find_map(|kind| kind.check).map(str::to_string)
+}
+
+/// The command Diagnostics will run in `sandbox_cwd`, for approval prompts and the allowlist
+pub fn command(sandbox_cwd: Option<&str>, config: &DiagnosticsConfig) -> Option<String> {
+    let dir = Path::new(sandbox_cwd.unwrap_or("."));
+    config.command.clone().filter(|c| !c.trim().is_empty()).or_else(|| detect_command(dir))
+}
+
+/// Parse `cargo check --message-format=json` output. Only the primary span of
+/// each error or warning is kept; notes and the build summary are dropped
+pub fn parse_cargo_json(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for line in output.lines() {
+        let Ok(value) = serde_json::from_str::<Value>(line) else { continue };
+        if value["reason"] != "compiler-message" {
+            continue;
+        }
+        let message = &value["message"];
+        let severity = message["level"].as_str().unwrap_or("error");
+        if !matches!(severity, "error" | "warning") {
+            continue;
+        }
+        let Some(span) = message["spans"]
+            .as_array()
+            .and_then(|spans| spans.iter().find(|s| s["is_primary"].as_bool().unwrap_or(false)))
+        else {
+            continue;
+        };
+        diagnostics.push(Diagnostic {
+            file: span["file_name"].as_str().unwrap_or("?").to_string(),
+            line: span["line_start"].as_u64().unwrap_or(0) as usize,
+            column: span["column_start"].as_u64().map(|c| c as usize),
+            severity: severity.to_string(),
+            code: message["code"]["code"].as_str().map(str::to_string),
+            message: message["message"].as_str().unwrap_or("").to_string(),
+        });
+    }
+    diagnostics
+}
+
+lazy_static::lazy_static! {
+    /// `file:line:col: rest` (gcc, ruff, go vet)
+    static ref COLON_LOCATION: regex::Regex =
+        regex::Regex::new(r"^(?:\./)?([^\s:()][^:()]*):(\d+)(?::(\d+))?:\s*(.+)$").unwrap();
+    /// tsc's `file(line,col): rest`
+    static ref PAREN_LOCATION: regex::Regex = regex::Regex::new(r"^([^\s(][^(]*)\((\d+),(\d+)\):\s*(.+)$").unwrap();
+    /// `error[E0308]: message` or `warning TS2322: message`
+    static ref SEVERITY: regex::Regex =
+        regex::Regex::new(r"^(?i)(error|warning)(?:\[(\w+)\]|\s+(\w+))?:\s*(.+)$").unwrap();
+    /// ruff's `F401 message`
+    static ref CODE_FIRST: regex::Regex = regex::Regex::new(r"^([A-Z]+\d+)\s+(.+)$").unwrap();
+}
+
+/// Parse plain-text output with one diagnostic per line, in either the
+/// `file:line:col: message` form (gcc, ruff, go vet) or tsc's `file(line,col): message`
+pub fn parse_text(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for line in output.lines() {
+        let line = line.trim_end();
+        let Some(caps) = PAREN_LOCATION.captures(line).or_else(|| COLON_LOCATION.captures(line)) else { continue };
+        let rest = &caps[4];
+        let (severity, code, message) = if let Some(s) = SEVERITY.captures(rest) {
+            let code = s.get(2).or_else(|| s.get(3)).map(|c| c.as_str().to_string());
+            (s[1].to_lowercase(), code, s[4].to_string())
+        } else if let Some(c) = CODE_FIRST.captures(rest) {
+            ("error".to_string(), Some(c[1].to_string()), c[2].to_string())
+        } else {
+            ("error".to_string(), None, rest.to_string())
+        };
+        diagnostics.push(Diagnostic {
+            file: caps[1].to_string(),
+            line: caps[2].parse().unwrap_or(0),
+            column: caps.get(3).and_then(|c| c.as_str().parse().ok()),
+            severity,
+            code,
+            message,
+        });
+    }
+    diagnostics
+}
+
+/// Drop repeats (the same error reported once per target, say), keeping first-seen order
+pub fn dedup(diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    let mut seen = std::collections::HashSet::new();
+    diagnostics
+        .into_iter()
+        .filter(|d| seen.insert((d.file.clone(), d.line, d.column, d.message.clone())))
+        .collect()
+}
+
+/// Parse a check command's output, cargo JSON if there is any
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    let cargo = parse_cargo_json(output);
+    let parsed = if cargo.is_empty() { parse_text(output) } else { cargo };
+    dedup(parsed)
+}
+
+/// Format diagnostics for the model: errors first, then warnings, capped at `max`
+pub fn render(diagnostics: &[Diagnostic], max: usize) -> String {
+    let errors = diagnostics.iter().filter(|d| d.severity == "error").count();
+    let warnings = diagnostics.len() - errors;
+    let mut sorted: Vec<&Diagnostic> = diagnostics.iter().collect();
+    sorted.sort_by_key(|d| d.severity != "error");
+
+    let mut out = format!("{} error(s), {} warning(s)", errors, warnings);
+    for d in sorted.iter().take(max) {
+        out.push('\n');
+        out.push_str(&d.render());
+    }
+    if diagnostics.len() > max {
+        out.push_str(&format!("\n... {} more not shown", diagnostics.len() - max));
+    }
+    out
+}
+
+/// Run the check command and return its parsed diagnostics. Like Bash, it is
+/// killed along with anything it started after `timeout`.
+pub fn run(sandbox_cwd: Option<&str>, config: &DiagnosticsConfig, timeout: std::time::Duration) -> String {
+    let Some(command) = command(sandbox_cwd, config) else {
+        return "Error: no check command configured and none detected for this project. Set diagnostics.command in ~/.config/grok-cli/config.json".to_string();
+    };
+    let (out, timed_out) = match crate::tools::status_output_with_timeout(crate::tools::shell_command(&command, sandbox_cwd), timeout) {
+        Ok(out) => out,
+        Err(e) => return format!("Error running '{}': {}", command, e),
+    };
+
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let raw = format!("{}{}", stdout, stderr);
+    let lines: Vec<&str> = raw.lines().collect();
+    let tail = lines[lines.len().saturating_sub(30)..].join("\n");
+    if timed_out {
+        return format!("Error: `{}` timed out after {}s and was killed. Last output:\n{}", command, timeout.as_secs(), tail);
+    }
+    let diagnostics = parse(&format!("{}\n{}", stdout, stderr));
+    if diagnostics.is_empty() {
+        if out.status.success() {
+            return format!("`{}` passed with no diagnostics", command);
+        }
+        // Failed without anything we could parse; show the tail of the raw output
+        return format!("`{}` failed ({}) but no diagnostics could be parsed:\n{}", command, out.status, tail);
+    }
+    format!("`{}`: {}", command, render(&diagnostics, config.max_results.max(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cargo_check_json() {
+        let output = [
+            r#"{"reason":"compiler-artifact","package_id":"dep 0.1.0","target":{"name":"dep"}}"#,
+            r#"{"reason":"compiler-message","message":{"message":"mismatched types","code":{"code":"E0308","explanation":"..."},"level":"error","spans":[{"file_name":"src/main.rs","line_start":12,"line_end":12,"column_start":18,"column_end":25,"is_primary":true},{"file_name":"src/main.rs","line_start":10,"line_end":10,"column_start":9,"column_end":12,"is_primary":false}],"children":[],"rendered":"error[E0308]: mismatched types"}}"#,
+            r#"{"reason":"compiler-message","message":{"message":"unused variable: `x`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[{"file_name":"src/lib.rs","line_start":3,"line_end":3,"column_start":9,"column_end":10,"is_primary":true}],"children":[],"rendered":"warning: unused variable"}}"#,
+            // The same error again, as cargo reports it once per target
+            r#"{"reason":"compiler-message","message":{"message":"mismatched types","code":{"code":"E0308","explanation":"..."},"level":"error","spans":[{"file_name":"src/main.rs","line_start":12,"line_end":12,"column_start":18,"column_end":25,"is_primary":true}],"children":[],"rendered":"error[E0308]: mismatched types"}}"#,
+            r#"{"reason":"compiler-message","message":{"message":"aborting due to 1 previous error","code":null,"level":"error","spans":[],"children":[],"rendered":"error: aborting"}}"#,
+            r#"{"reason":"build-finished","success":false}"#,
+        ]
+        .join("\n");
+
+        let diagnostics = parse(&output);
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic {
+                    file: "src/main.rs".to_string(),
+                    line: 12,
+                    column: Some(18),
+                    severity: "error".to_string(),
+                    code: Some("E0308".to_string()),
+                    message: "mismatched types".to_string(),
+                },
+                Diagnostic {
+                    file: "src/lib.rs".to_string(),
+                    line: 3,
+                    column: Some(9),
+                    severity: "warning".to_string(),
+                    code: Some("unused_variables".to_string()),
+                    message: "unused variable: `x`".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            render(&diagnostics, 50),
+            "1 error(s), 1 warning(s)\nsrc/main.rs:12:18: error[E0308]: mismatched types\nsrc/lib.rs:3:9: warning[unused_variables]: unused variable: `x`"
+        );
+    }
+
+    #[test]
+    fn test_parse_text_diagnostics() {
+        let output = "src/app.ts(4,7): error TS2322: Type 'string' is not assignable to type 'number'.\n\
+                      pkg/util.py:10:1: F401 [*] `os` imported but unused\n\
+                      Found 1 error.\n";
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].render(), "src/app.ts:4:7: error[TS2322]: Type 'string' is not assignable to type 'number'.");
+        assert_eq!(diagnostics[1].render(), "pkg/util.py:10:1: error[F401]: [*] `os` imported but unused");
+    }
+}
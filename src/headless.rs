@@ -0,0 +1,297 @@
+//! Non-interactive mode: `--print <prompt>` runs one turn and writes the reply to stdout
+//!
+//! A spinner with the latest status or reasoning is drawn on one stderr line,
+//! but only when attached to a terminal. In CI or when piped, nothing is written
+//! until the turn ends: then the final reply, or with `--json` one JSON object
+//! per line for each message, usage update, error and the end of the turn.
+
+use crate::app::{App, AppEvent};
+use crate::ui::{truncate, SPINNER_FRAMES};
+use serde_json::json;
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// How often the event queue is polled (and the spinner advanced)
+const POLL_INTERVAL: Duration = Duration::from_millis(80);
+
+/// Whether the turn is still running after an event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    Continue,
+    Done,
+}
+
+/// Where headless output goes: `out` gets the reply or JSON events, `status`
+/// (stderr) gets errors and, on a terminal, the single-line spinner
+pub struct OutputSink<O: Write, S: Write> {
+    out: O,
+    status: S,
+    json: bool,
+    tty: bool,
+    spinner: usize,
+    status_text: Option<String>,
+    thinking: String,
+    reply: Option<String>,
+    failed: bool,
+}
+
+impl<O: Write, S: Write> OutputSink<O, S> {
+    pub fn new(out: O, status: S, json: bool, tty: bool) -> Self {
+        OutputSink { out, status, json, tty, spinner: 0, status_text: None, thinking: String::new(), reply: None, failed: false }
+    }
+
+    /// Whether the turn ended in an error or an unanswerable approval
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Advance the spinner (terminal only)
+    pub fn tick(&mut self) -> io::Result<()> {
+        self.spinner = self.spinner.wrapping_add(1);
+        self.draw_status()
+    }
+
+    fn set_status(&mut self, text: &str) -> io::Result<()> {
+        let line = text.replace('\n', " ");
+        self.status_text = Some(line.trim().to_string());
+        self.draw_status()
+    }
+
+    fn draw_status(&mut self) -> io::Result<()> {
+        if !self.tty {
+            return Ok(());
+        }
+        let Some(text) = &self.status_text else { return Ok(()) };
+        let frame = SPINNER_FRAMES[self.spinner % SPINNER_FRAMES.len()];
+        write!(self.status, "\r\x1b[2K{} {}", frame, truncate(text, 70))?;
+        self.status.flush()
+    }
+
+    fn clear_status(&mut self) -> io::Result<()> {
+        if self.tty && self.status_text.take().is_some() {
+            write!(self.status, "\r\x1b[2K")?;
+            self.status.flush()?;
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, event: serde_json::Value) -> io::Result<()> {
+        writeln!(self.out, "{}", event)?;
+        self.out.flush()
+    }
+
+    fn error(&mut self, message: &str) -> io::Result<()> {
+        self.failed = true;
+        self.clear_status()?;
+        if self.json {
+            self.emit(json!({"type": "error", "message": message}))
+        } else {
+            writeln!(self.status, "Error: {}", message)
+        }
+    }
+
+    /// Handle one event from the conversation
+    pub fn handle(&mut self, event: AppEvent) -> io::Result<Step> {
+        match event {
+            AppEvent::StatusUpdate(status) => self.set_status(&status)?,
+            // The status line follows the end of the reasoning
+            AppEvent::ThinkingToken(token) if self.tty => {
+                self.thinking.push_str(&token);
+                let chars: Vec<char> = self.thinking.chars().collect();
+                let tail: String = chars[chars.len().saturating_sub(60)..].iter().collect();
+                self.thinking = tail.clone();
+                self.set_status(&format!("💭 {}", tail))?;
+            }
+            AppEvent::NewMessage(message) => {
+                if message.role == "assistant" && message.tool_calls.is_none() && message.content.is_some() {
+                    self.reply = message.content.clone();
+                }
+                if self.json {
+                    self.emit(json!({"type": "message", "message": message}))?;
+                }
+            }
+            AppEvent::UsageUpdate(input_tokens, output_tokens) if self.json => {
+                self.emit(json!({"type": "usage", "input_tokens": input_tokens, "output_tokens": output_tokens}))?;
+            }
+            AppEvent::Error(e) => self.error(&e)?,
+            AppEvent::BashApprovalRequest(_, command) => {
+                self.error(&format!("`{}` needs approval, which --print can't ask for; add it to allowed_commands first", command))?;
+                return self.finish();
+            }
+            AppEvent::WebSearchApprovalRequest(_, query) => {
+                self.error(&format!("web search '{}' needs approval, which --print can't ask for", query))?;
+                return self.finish();
+            }
+            AppEvent::WriteApprovalRequest(_, target, _) => {
+                self.error(&format!("changing {} needs approval, which --print can't ask for", target))?;
+                return self.finish();
+            }
+            AppEvent::PlanStepPreview(_, target, _) => {
+                self.error(&format!("plan step on {} is waiting for a preview, which --print can't show; turn off preview_plan_steps", target))?;
+                return self.finish();
+            }
+            AppEvent::PlanningRequest(question, ..) | AppEvent::ConfirmationRequest(question, _) => {
+                self.error(&format!("the model asked a question, which --print can't answer: {}", question))?;
+                return self.finish();
+            }
+            AppEvent::Finished => return self.finish(),
+            _ => {}
+        }
+        Ok(Step::Continue)
+    }
+
+    fn finish(&mut self) -> io::Result<Step> {
+        self.clear_status()?;
+        let reply = self.reply.take();
+        if self.json {
+            self.emit(json!({"type": "done", "reply": reply, "success": !self.failed}))?;
+        } else if let Some(reply) = reply {
+            writeln!(self.out, "{}", reply.trim_end())?;
+            self.out.flush()?;
+        }
+        Ok(Step::Done)
+    }
+}
+
+/// Send `prompt` and report the turn to stdout; returns the process exit code
+pub async fn run(app: &mut App<'_>, prompt: &str, json: bool) -> i32 {
+    use std::io::IsTerminal;
+    let tty = io::stdout().is_terminal() && io::stderr().is_terminal();
+    let mut sink = OutputSink::new(io::stdout(), io::stderr(), json, tty);
+
+    let before = app.messages.len();
+    app.input.insert_str(prompt);
+    app.submit_message();
+    if !app.is_loading {
+        // Handled locally (a slash command): print what it said
+        for message in app.messages[before.min(app.messages.len())..].iter().filter(|m| m.role != "user") {
+            if let Some(content) = &message.content {
+                println!("{}", content);
+            }
+        }
+        return 0;
+    }
+
+    drive(app, &mut sink).await
+}
+
+/// Report the running turn's events until it finishes; returns the exit code.
+/// A turn whose task ends without sending Finished (it panicked) is an error
+async fn drive<O: Write, S: Write>(app: &mut App<'_>, sink: &mut OutputSink<O, S>) -> i32 {
+    loop {
+        // Checked first, so everything the task sent before it ended is drained below
+        let ended = app.turn_task.as_ref().is_some_and(|task| task.is_finished());
+        while let Ok(event) = app.rx.try_recv() {
+            match sink.handle(event) {
+                Ok(Step::Continue) => {}
+                Ok(Step::Done) => return if sink.failed() { 1 } else { 0 },
+                Err(_) => return 1,
+            }
+        }
+        if ended {
+            let reason = match app.turn_task.take() {
+                Some(task) => match task.await {
+                    Err(e) => e.to_string(),
+                    Ok(()) => "no reason given".to_string(),
+                },
+                None => "no reason given".to_string(),
+            };
+            let _ = sink.error(&format!("the turn stopped before it finished ({})", reason));
+            let _ = sink.finish();
+            return 1;
+        }
+        let _ = sink.tick();
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Message;
+
+    fn assistant(content: &str) -> Message {
+        Message { role: "assistant".to_string(), content: Some(content.to_string()), tool_calls: None, tool_call_id: None }
+    }
+
+    fn run_events(json: bool, tty: bool, events: Vec<AppEvent>) -> (String, String, bool) {
+        let mut out = Vec::new();
+        let mut status = Vec::new();
+        let failed = {
+            let mut sink = OutputSink::new(&mut out, &mut status, json, tty);
+            for event in events {
+                sink.tick().unwrap();
+                if sink.handle(event).unwrap() == Step::Done {
+                    break;
+                }
+            }
+            sink.failed()
+        };
+        (String::from_utf8(out).unwrap(), String::from_utf8(status).unwrap(), failed)
+    }
+
+    fn turn() -> Vec<AppEvent> {
+        vec![
+            AppEvent::StatusUpdate("Thinking...".to_string()),
+            AppEvent::ThinkingToken("The user wants a haiku".to_string()),
+            AppEvent::Token("Autumn ".to_string()),
+            AppEvent::NewMessage(assistant("Autumn moonlight\n")),
+            AppEvent::UsageUpdate(120, 8),
+            AppEvent::Finished,
+        ]
+    }
+
+    #[test]
+    fn test_non_tty_suppresses_spinner() {
+        let (out, status, failed) = run_events(false, false, turn());
+        assert_eq!(out, "Autumn moonlight\n");
+        assert_eq!(status, "", "no spinner or thinking without a terminal");
+        assert!(!failed);
+
+        // On a terminal the spinner line is drawn, then cleared before the reply
+        let (out, status, _) = run_events(false, true, turn());
+        assert_eq!(out, "Autumn moonlight\n");
+        assert!(status.contains("⠙ Thinking..."));
+        assert!(status.contains("💭 The user wants a haiku"));
+        assert!(status.ends_with("\r\x1b[2K"));
+    }
+
+    #[test]
+    fn test_json_events_and_unanswerable_approval() {
+        let (out, status, _) = run_events(true, false, turn());
+        let events: Vec<serde_json::Value> = out.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        let types: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+        assert_eq!(types, ["message", "usage", "done"]);
+        assert_eq!(events[2]["reply"], "Autumn moonlight\n");
+        assert_eq!(status, "");
+
+        let tc = crate::tools::ToolCall {
+            id: "call_1".to_string(),
+            r#type: "function".to_string(),
+            function: crate::tools::FunctionCall { name: "Bash".to_string(), arguments: "{}".to_string() },
+        };
+        let (out, status, failed) = run_events(false, false, vec![AppEvent::BashApprovalRequest(tc, "rm -rf build".to_string())]);
+        assert!(failed);
+        assert_eq!(out, "");
+        assert!(status.contains("`rm -rf build` needs approval"));
+    }
+
+    #[tokio::test]
+    async fn test_turn_that_dies_without_finishing_is_an_error() {
+        let mut app = crate::app::tests::test_app();
+        let tx = app.tx.clone();
+        app.turn_task = Some(tokio::spawn(async move {
+            tx.send(AppEvent::NewMessage(assistant("half a reply"))).unwrap();
+            panic!("lost the connection pool");
+        }));
+
+        let (mut out, mut status) = (Vec::new(), Vec::new());
+        let code = drive(&mut app, &mut OutputSink::new(&mut out, &mut status, true, false)).await;
+        assert_eq!(code, 1);
+        let events: Vec<serde_json::Value> = String::from_utf8(out).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        let types: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+        assert_eq!(types, ["message", "error", "done"]);
+        assert!(events[1]["message"].as_str().unwrap().contains("stopped before it finished"));
+        assert_eq!(events[2]["success"], false);
+    }
+}
@@ -0,0 +1,198 @@
+//! Inline images in the chat
+//!
+//! Messages that reference local image files get a `[image: path]` line. On
+//! terminals with a graphics protocol (Kitty, or iTerm2's inline images, also
+//! spoken by WezTerm) the image itself is drawn into rows reserved beneath it.
+
+use base64::Engine;
+use std::path::Path;
+
+/// Rows reserved under the placeholder for an inline image
+pub const IMAGE_ROWS: u16 = 10;
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "bmp"];
+
+/// Graphics protocol understood by the terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageProtocol {
+    Kitty,
+    ITerm2,
+    Unsupported,
+}
+
+/// An image to draw at a screen position after the frame is rendered
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineImage {
+    pub path: String,
+    pub x: u16,
+    pub y: u16,
+    pub cols: u16,
+    pub rows: u16,
+}
+
+/// Detect the graphics protocol from environment variables (`get` looks one up)
+pub fn detect_protocol_with(get: impl Fn(&str) -> Option<String>) -> ImageProtocol {
+    if get("GROK_INLINE_IMAGES").is_some_and(|v| v == "0" || v.eq_ignore_ascii_case("false")) {
+        return ImageProtocol::Unsupported;
+    }
+    // Multiplexers swallow graphics escapes unless specially configured
+    if get("TMUX").is_some() || get("STY").is_some() {
+        return ImageProtocol::Unsupported;
+    }
+    if get("KITTY_WINDOW_ID").is_some() || get("TERM").is_some_and(|t| t == "xterm-kitty") {
+        return ImageProtocol::Kitty;
+    }
+    let program = get("TERM_PROGRAM").unwrap_or_default();
+    if program == "iTerm.app" || program == "WezTerm" || get("LC_TERMINAL").is_some_and(|t| t == "iTerm2") {
+        return ImageProtocol::ITerm2;
+    }
+    ImageProtocol::Unsupported
+}
+
+lazy_static::lazy_static! {
+    static ref PROTOCOL: ImageProtocol = detect_protocol_with(|name| std::env::var(name).ok());
+}
+
+/// Protocol of the current terminal (detected once)
+pub fn protocol() -> ImageProtocol {
+    *PROTOCOL
+}
+
+fn is_image_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Local image files referenced in `text`, either as Markdown images
+/// (`![alt](path)`) or bare paths. Only files that exist are returned.
+pub fn image_refs(text: &str) -> Vec<String> {
+    let mut refs: Vec<String> = Vec::new();
+    for token in text.split_whitespace() {
+        let candidate = match token.find("](") {
+            Some(start) => token[start + 2..].split(')').next().unwrap_or(""),
+            None => token.trim_matches(|c: char| "`'\"()<>,;".contains(c)),
+        };
+        let candidate = candidate.trim_end_matches(['.', ':']);
+        if candidate.contains("://") || !is_image_path(candidate) || !Path::new(candidate).is_file() {
+            continue;
+        }
+        if !refs.iter().any(|r| r == candidate) {
+            refs.push(candidate.to_string());
+        }
+    }
+    refs
+}
+
+/// Text shown in place of (or above) an image
+pub fn placeholder(path: &str) -> String {
+    format!("[image: {}]", path)
+}
+
+/// Whether an image referenced at `path` can be drawn inline, or only shown as a placeholder
+pub fn can_render(protocol: ImageProtocol, path: &str) -> bool {
+    match protocol {
+        ImageProtocol::Unsupported => false,
+        // Kitty's direct transmission only takes PNG (other formats need decoding)
+        ImageProtocol::Kitty => path.to_lowercase().ends_with(".png") && Path::new(path).is_file(),
+        ImageProtocol::ITerm2 => Path::new(path).is_file(),
+    }
+}
+
+/// Escape sequence that draws `data` at the cursor, scaled into `cols` x `rows` cells
+pub fn escape_sequence(protocol: ImageProtocol, data: &[u8], cols: u16, rows: u16) -> Option<String> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(data);
+    match protocol {
+        ImageProtocol::Unsupported => None,
+        ImageProtocol::ITerm2 => Some(format!(
+            "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=1:{}\x07",
+            data.len(), cols, rows, encoded
+        )),
+        ImageProtocol::Kitty => {
+            // Payloads are sent in chunks of at most 4096 bytes; m=1 means more follow
+            let chunks: Vec<&str> = encoded
+                .as_bytes()
+                .chunks(4096)
+                .map(|c| std::str::from_utf8(c).unwrap_or(""))
+                .collect();
+            let mut seq = String::new();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let more = if i + 1 < chunks.len() { 1 } else { 0 };
+                if i == 0 {
+                    seq.push_str(&format!("\x1b_Gf=100,a=T,q=2,c={},r={},m={};{}\x1b\\", cols, rows, more, chunk));
+                } else {
+                    seq.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk));
+                }
+            }
+            Some(seq)
+        }
+    }
+}
+
+/// Escape sequence removing previously drawn images (Kitty keeps them as an overlay)
+pub fn clear_sequence(protocol: ImageProtocol) -> &'static str {
+    match protocol {
+        ImageProtocol::Kitty => "\x1b_Ga=d,q=2\x1b\\",
+        _ => "",
+    }
+}
+
+/// Draw `images` at their positions (after the frame has been rendered)
+pub fn draw(protocol: ImageProtocol, images: &[InlineImage], out: &mut impl std::io::Write) -> std::io::Result<()> {
+    out.write_all(clear_sequence(protocol).as_bytes())?;
+    for image in images {
+        let Ok(data) = std::fs::read(&image.path) else { continue };
+        if let Some(seq) = escape_sequence(protocol, &data, image.cols, image.rows) {
+            crossterm::queue!(out, crossterm::cursor::MoveTo(image.x, image.y))?;
+            out.write_all(seq.as_bytes())?;
+        }
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let map: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| map.get(name).cloned()
+    }
+
+    #[test]
+    fn test_detect_protocol() {
+        assert_eq!(detect_protocol_with(env(&[("TERM", "xterm-kitty")])), ImageProtocol::Kitty);
+        assert_eq!(detect_protocol_with(env(&[("TERM_PROGRAM", "iTerm.app")])), ImageProtocol::ITerm2);
+        assert_eq!(detect_protocol_with(env(&[("TERM_PROGRAM", "WezTerm")])), ImageProtocol::ITerm2);
+        assert_eq!(detect_protocol_with(env(&[("TERM", "xterm-256color")])), ImageProtocol::Unsupported);
+        // Inside tmux, or explicitly disabled, fall back to placeholders
+        assert_eq!(detect_protocol_with(env(&[("TERM", "xterm-kitty"), ("TMUX", "/tmp/tmux")])), ImageProtocol::Unsupported);
+        assert_eq!(detect_protocol_with(env(&[("KITTY_WINDOW_ID", "1"), ("GROK_INLINE_IMAGES", "0")])), ImageProtocol::Unsupported);
+    }
+
+    #[test]
+    fn test_image_refs_and_placeholder_fallback() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = dir.path().join("chart.png");
+        let jpg = dir.path().join("photo.jpg");
+        std::fs::write(&png, b"\x89PNG").unwrap();
+        std::fs::write(&jpg, b"\xff\xd8").unwrap();
+        let (png, jpg) = (png.to_str().unwrap().to_string(), jpg.to_str().unwrap().to_string());
+
+        let text = format!("See ![chart]({}) and `{}`, not missing.png or https://x.io/a.png", png, jpg);
+        assert_eq!(image_refs(&text), vec![png.clone(), jpg.clone()]);
+
+        assert_eq!(placeholder(&png), format!("[image: {}]", png));
+        assert!(!can_render(ImageProtocol::Unsupported, &png));
+        assert!(can_render(ImageProtocol::Kitty, &png));
+        // Kitty can't take JPEG directly, so that one stays a placeholder
+        assert!(!can_render(ImageProtocol::Kitty, &jpg));
+        assert!(can_render(ImageProtocol::ITerm2, &jpg));
+
+        assert!(escape_sequence(ImageProtocol::Unsupported, b"x", 10, 5).is_none());
+        let seq = escape_sequence(ImageProtocol::ITerm2, b"x", 10, 5).unwrap();
+        assert!(seq.starts_with("\x1b]1337;File=inline=1;size=1;width=10;height=5"));
+    }
+}
@@ -0,0 +1,158 @@
+//! Size-capped log files
+//!
+//! Debug, error and crash logs are appended to on every write. Before opening,
+//! a log past the size cap is rotated to `<name>.1` (shifting older copies up
+//! to `<name>.<keep>`), so logs stay bounded without losing recent history.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+
+/// Rotation policy for the CLI's log files
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Rotate once a log reaches this many bytes
+    pub max_bytes: u64,
+    /// Number of rotated copies to keep (0 = just truncate)
+    pub keep: usize,
+    /// Debug log detail; `trace` logs every stream chunk in full
+    pub level: LogLevel,
+    /// At `debug` level, stream chunks are cut to this many characters (0 = not logged)
+    pub chunk_chars: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            max_bytes: 10 * 1024 * 1024,
+            keep: 3,
+            level: LogLevel::default(),
+            chunk_chars: 200,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    #[default]
+    Debug,
+    Trace,
+}
+
+/// How a raw stream chunk appears in the debug log under `config`, if at all
+pub fn chunk_log_line(chunk: &str, config: &LogConfig) -> Option<String> {
+    if config.level == LogLevel::Trace {
+        return Some(format!("CHUNK: {}", chunk));
+    }
+    if config.chunk_chars == 0 {
+        return None;
+    }
+    if chunk.chars().count() <= config.chunk_chars {
+        Some(format!("CHUNK: {}", chunk))
+    } else {
+        let truncated: String = chunk.chars().take(config.chunk_chars).collect();
+        Some(format!("CHUNK: {}...", truncated))
+    }
+}
+
+/// Rotate `path` if it has reached the size cap
+pub fn rotate_if_needed(path: &str, config: &LogConfig) -> io::Result<()> {
+    let size = match fs::metadata(path) {
+        Ok(meta) => meta.len(),
+        Err(_) => return Ok(()),
+    };
+    if size < config.max_bytes {
+        return Ok(());
+    }
+
+    if config.keep == 0 {
+        return fs::remove_file(path);
+    }
+
+    // Drop the oldest copy, then shift the rest up by one
+    let _ = fs::remove_file(format!("{}.{}", path, config.keep));
+    for i in (1..config.keep).rev() {
+        let from = format!("{}.{}", path, i);
+        if fs::metadata(&from).is_ok() {
+            fs::rename(&from, format!("{}.{}", path, i + 1))?;
+        }
+    }
+    fs::rename(path, format!("{}.1", path))
+}
+
+/// Open a log for appending, rotating it first if it's over the cap in `config`
+pub fn open_log(path: &str, config: &LogConfig) -> io::Result<File> {
+    // Rotation failures shouldn't stop logging
+    let _ = rotate_if_needed(path, config);
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_rotation_preserves_old_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("grok-debug.log");
+        let path = path.to_str().unwrap();
+        let config = LogConfig { max_bytes: 16, keep: 2, ..LogConfig::default() };
+
+        let mut file = open_log(path, &config).unwrap();
+        file.write_all(b"first generation\n").unwrap();
+        drop(file);
+
+        // Past the cap: next open rotates to .1 and starts fresh
+        let mut file = open_log(path, &config).unwrap();
+        file.write_all(b"second generation\n").unwrap();
+        drop(file);
+        assert_eq!(fs::read_to_string(format!("{}.1", path)).unwrap(), "first generation\n");
+        assert_eq!(fs::read_to_string(path).unwrap(), "second generation\n");
+
+        // Older copies shift up, and nothing beyond `keep` is retained
+        open_log(path, &config).unwrap();
+        assert_eq!(fs::read_to_string(format!("{}.2", path)).unwrap(), "first generation\n");
+        assert_eq!(fs::read_to_string(format!("{}.1", path)).unwrap(), "second generation\n");
+        assert_eq!(fs::read_to_string(path).unwrap(), "");
+
+        fs::write(path, "third generation\n").unwrap();
+        open_log(path, &config).unwrap();
+        assert!(fs::metadata(format!("{}.3", path)).is_err());
+        assert_eq!(fs::read_to_string(format!("{}.2", path)).unwrap(), "second generation\n");
+    }
+
+    #[test]
+    fn test_small_log_is_not_rotated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("errors.log");
+        let path = path.to_str().unwrap();
+        fs::write(path, "short\n").unwrap();
+
+        rotate_if_needed(path, &LogConfig::default()).unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), "short\n");
+        assert!(fs::metadata(format!("{}.1", path)).is_err());
+    }
+
+    #[test]
+    fn test_chunk_logging_respects_length() {
+        let chunk = r#"{"choices":[{"delta":{"content":"hello world"}}]}"#;
+
+        let short = LogConfig { chunk_chars: 12, ..LogConfig::default() };
+        assert_eq!(chunk_log_line(chunk, &short).unwrap(), r#"CHUNK: {"choices":[..."#);
+
+        // Short chunks are logged as-is, and 0 turns chunk logging off
+        let roomy = LogConfig { chunk_chars: 500, ..LogConfig::default() };
+        assert_eq!(chunk_log_line(chunk, &roomy).unwrap(), format!("CHUNK: {}", chunk));
+        assert!(chunk_log_line(chunk, &LogConfig { chunk_chars: 0, ..LogConfig::default() }).is_none());
+
+        // Trace logs everything regardless of the length
+        let trace = LogConfig { level: LogLevel::Trace, chunk_chars: 0, ..LogConfig::default() };
+        assert_eq!(chunk_log_line(chunk, &trace).unwrap(), format!("CHUNK: {}", chunk));
+
+        let config: LogConfig = serde_json::from_str(r#"{"level": "trace"}"#).unwrap();
+        assert_eq!((config.level, config.chunk_chars), (LogLevel::Trace, 200));
+    }
+}
@@ -1,7 +1,7 @@
 use std::{io, io::{BufRead, Write}, time::Duration, panic, sync::atomic::{AtomicBool, Ordering}};
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+    event::{self, DisableMouseCapture, Event, KeyCode, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -29,12 +29,25 @@ mod megamind;
 mod transactions;
 mod settings;
 mod tool_plugins;
+mod terminal;
+mod version;
+mod notebook;
+mod logging;
+mod web_search;
+mod images;
+mod check;
+mod diagnostics;
+mod compare;
+mod theme;
+mod project;
+mod headless;
+mod strings;
 
 use crate::api::{GrokClient, Message};
-use crate::persistence::{save_history, load_history, save_context, DEFAULT_HISTORY_FILE, DEFAULT_CONTEXT_FILE};
-use crate::app::{App, AppMode, AppEvent, PlanningState};
+use crate::persistence::{save_history, load_history, save_context, save_draft, load_named_session, most_recent_session, DEFAULT_DRAFT_FILE, DEFAULT_HISTORY_FILE, DEFAULT_CONTEXT_FILE, SESSIONS_DIR};
+use crate::app::{App, AppMode, ApprovalDecision, AppEvent, PlanningState, DRAFT_OPTIONS};
 use crate::ui::ui;
-use crate::config::{load_config, Config, save_config};
+use crate::config::{get_config_path, load_config, Config, save_config};
 // use crate::tools::execute_tool;  // Used by tool execution in app.rs
 
 fn get_default_system_prompt() -> String {
@@ -54,25 +67,32 @@ fn get_default_system_prompt() -> String {
         }
         section
     };
+    let project_section = crate::project::project_section(std::path::Path::new(&cwd));
 
     format!(r#"You are Grok CLI, an AI coding assistant running in the user's terminal.
 
 # Environment
 - Working directory: {}
 - Platform: {}
-
+{}
 # Available Tools
 You have access to the following tools to help complete tasks:
 
 ## File Operations
 - **Read**: Read file contents with line numbers. Always read before editing.
-- **Edit**: Exact string replacement in files. old_string must match exactly.
+- **Edit**: Exact string replacement in files. old_string must match exactly; several changes to one file go in one call via edits.
+- **EditCell**: Replace one cell's source in a Jupyter notebook (Read shows .ipynb files as cells).
+- **RenameSymbol**: Whole-word rename of a symbol across files (optionally scoped by glob).
+- **ProjectReplace**: Replace a literal string or regex across files (optionally scoped by glob); the user confirms a preview first.
 - **Write**: Create new files or completely overwrite existing ones.
 - **Glob**: Find files by pattern (e.g., "**/*.rs", "src/**/*.ts").
 - **Grep**: Search file contents with regex patterns.
 - **List**: List directory contents.
 
 ## System
+- **EnvInfo**: OS, architecture, project type, toolchain versions and allowlisted environment variables. No approval needed - prefer it over Bash for such facts.
+- **GitContext**: Recent git log for a file and optional blame for a line range. Requires user approval.
+- **Diagnostics**: Run the project's check command (cargo check, tsc, ruff...) and get a deduplicated file:line list of errors and warnings. Use after edits instead of Bash builds. Requires user approval.
 - **Bash**: Execute shell commands. Use for git, builds, running programs. Requires user approval.
 - **WebSearch**: Search the web for current information. Requires user approval.
 
@@ -80,6 +100,7 @@ You have access to the following tools to help complete tasks:
 - **AskUser**: Ask the user a multiple choice question.
 - **ConfirmPlan**: Present a plan for user confirmation before executing.
 - **TodoWrite**: Update task progress and track multiple steps.
+- **Summarize**: Replace completed turns (numbered from 1 by user message) with your own summary to free context. Keep file paths, decisions and open issues.
 {}
 # Guidelines
 1. Read files before editing them - never guess at content.
@@ -94,16 +115,62 @@ You have access to the following tools to help complete tasks:
 - Keep responses brief and focused
 - Use markdown formatting sparingly
 - Show file paths and code when relevant
-- Explain what you're doing before using tools"#, cwd, std::env::consts::OS, plugin_section)
+- Explain what you're doing before using tools"#, cwd, std::env::consts::OS, project_section, plugin_section)
+}
+
+/// Terse system prompt for `compact_prompt` - tools are already described by their schemas
+fn get_compact_system_prompt() -> String {
+    let cwd = std::env::current_dir()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| ".".to_string());
+
+    let project_section = crate::project::project_section(std::path::Path::new(&cwd));
+
+    format!(
+        "You are Grok CLI, a terminal coding assistant. Working directory: {} ({}). \
+Read files before editing; prefer Edit over Write for existing files. \
+Bash and WebSearch need user approval. Be brief.{}",
+        cwd,
+        std::env::consts::OS,
+        project_section.trim_end()
+    )
+}
+
+/// Absolute path of the `--cwd` directory, which must exist
+fn resolve_cwd(dir: &std::path::Path) -> std::result::Result<std::path::PathBuf, String> {
+    let resolved = std::fs::canonicalize(dir).map_err(|e| format!("--cwd {}: {}", dir.display(), e))?;
+    if !resolved.is_dir() {
+        return Err(format!("--cwd {}: not a directory", dir.display()));
+    }
+    Ok(resolved)
+}
+
+/// Session-wide answer to approval prompts from --approve-all / --reject-all
+fn approval_decision(args: &Args) -> ApprovalDecision {
+    if args.approve_all {
+        ApprovalDecision::ApproveAll
+    } else if args.reject_all {
+        ApprovalDecision::RejectAll
+    } else {
+        ApprovalDecision::Ask
+    }
 }
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, disable_version_flag = true)]
 struct Args {
+    /// Print version, config path, active model, and build details
+    #[arg(short = 'V', long)]
+    version: bool,
+
     /// Resume the previous chat session
     #[arg(short, long)]
     resume: bool,
 
+    /// Continue the most recently modified named session
+    #[arg(long = "continue", conflicts_with = "resume")]
+    continue_session: bool,
+
     /// Set a custom system prompt
     #[arg(short, long)]
     system: Option<String>,
@@ -124,9 +191,53 @@ struct Args {
     #[arg(long)]
     mcp: bool,
 
+    /// Safe mode: sandbox on, approve every file change (with a dry-run preview), no web search
+    #[arg(long)]
+    safe: bool,
+
+    /// Approve every command, web search and file change without asking (for unattended runs)
+    #[arg(long, conflicts_with = "reject_all")]
+    approve_all: bool,
+
+    /// Reject everything that needs approval without asking; the model gets a rejection result
+    #[arg(long)]
+    reject_all: bool,
+
     /// Auto-fix mode: review crash log and attempt to fix
     #[arg(long)]
     auto_fix: bool,
+
+    /// Disable colors (also honoured: the NO_COLOR environment variable)
+    #[arg(long)]
+    no_color: bool,
+
+    /// Context window of the model in tokens, for models the CLI doesn't know (not saved; see model_context in config)
+    #[arg(long, value_name = "TOKENS", value_parser = clap::value_parser!(u64).range(1..))]
+    model_context: Option<u64>,
+
+    /// Color theme for this run: dark, light or high-contrast (/theme changes and saves it)
+    #[arg(long, value_parser = crate::theme::parse_theme_arg)]
+    theme: Option<crate::theme::ThemeName>,
+
+    /// Working directory for this session (sandbox root, relative paths, session files) instead of the current one
+    #[arg(long, value_name = "PATH")]
+    cwd: Option<std::path::PathBuf>,
+
+    /// Validate config, plugins, API key and model, print a PASS/FAIL report and exit (non-zero on failure)
+    #[arg(long)]
+    check: bool,
+
+    /// Print the effective config (config.json with --model applied) as JSON, secrets redacted, and exit
+    #[arg(long, alias = "export-config")]
+    print_config: bool,
+
+    /// Run one prompt without the TUI, print the final reply and exit (for scripts and CI)
+    #[arg(short, long, value_name = "PROMPT")]
+    print: Option<String>,
+
+    /// With --print: write one JSON object per event instead of the plain reply
+    #[arg(long, requires = "print")]
+    json: bool,
 }
 
 // MCP JSON-RPC structures
@@ -215,7 +326,7 @@ fn handle_mcp_request(request: JsonRpcRequest, config: &Config) -> JsonRpcRespon
             }
         }
         "tools/list" => {
-            let tools = crate::tools::get_tool_definitions()
+            let tools = config.tool_filter().apply(crate::tools::get_tool_definitions())
                 .into_iter()
                 .enumerate()
                 .map(|(i, def)| {
@@ -242,7 +353,7 @@ fn handle_mcp_request(request: JsonRpcRequest, config: &Config) -> JsonRpcRespon
                     if let Some(args) = params.get("arguments") {
                         let args_json = args.to_string();
                         let sandbox_cwd = None; // MCP doesn't use sandbox mode
-                        let output = crate::tools::execute_tool(name, &args_json, sandbox_cwd);
+                        let output = crate::tools::execute_tool_with(name, &args_json, sandbox_cwd, &config.tool_settings());
 
                         let result = ToolCallResult {
                             content: vec![Content {
@@ -317,15 +428,18 @@ fn handle_mcp_request(request: JsonRpcRequest, config: &Config) -> JsonRpcRespon
     }
 }
 
-/// Set up panic hook to log crashes and restore terminal
-fn setup_panic_hook() {
+/// Set up panic hook to log crashes (rotated under `logging`) and restore terminal
+fn setup_panic_hook(logging: crate::logging::LogConfig) {
     let original_hook = panic::take_hook();
 
     panic::set_hook(Box::new(move |panic_info| {
         // Restore terminal state first
         if TERMINAL_RAW.load(Ordering::SeqCst) {
             let _ = disable_raw_mode();
-            let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture);
+            if crate::terminal::is_mouse_captured() {
+                let _ = execute!(io::stdout(), DisableMouseCapture);
+            }
+            let _ = execute!(io::stdout(), LeaveAlternateScreen);
         }
 
         // Build crash report
@@ -356,11 +470,7 @@ fn setup_panic_hook() {
         );
 
         // Write to crash log
-        if let Ok(mut file) = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(CRASH_LOG_FILE)
-        {
+        if let Ok(mut file) = crate::logging::open_log(CRASH_LOG_FILE, &logging) {
             use std::io::Write;
             let _ = file.write_all(crash_report.as_bytes());
         }
@@ -488,22 +598,10 @@ fn run_auto_fix() -> Result<()> {
     Ok(())
 }
 
-/// Initialize default tool YAML files if they don't exist
-fn init_default_tools() -> Result<usize> {
-    use std::fs;
-    use std::path::PathBuf;
-
-    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-    let tools_dir = PathBuf::from(home).join(".config/grok-cli/tools");
-
-    // Create tools directory if it doesn't exist
-    fs::create_dir_all(&tools_dir)?;
-
-    let mut created_count = 0;
-
-    // Define default tools
-    let default_tools = vec![
-        ("system_info.yaml", r#"# System Info Tool
+/// Tool files `/init` writes. Placeholders are never put inside quotes: the
+/// value is single-quoted when substituted, and quotes around that would undo it
+const DEFAULT_TOOLS: &[(&str, &str)] = &[
+    ("system_info.yaml", r#"# System Info Tool
 # Get system information like hostname, OS, uptime, and memory usage
 
 name: SystemInfo
@@ -517,7 +615,7 @@ parameters:
     default: all
 
 command: |
-  case "{{info_type}}" in
+  case {{info_type}} in
     hostname) hostname;;
     os) uname -a;;
     uptime) uptime;;
@@ -538,10 +636,11 @@ command: |
   esac
 
 sandbox_aware: false
+read_only: true
 category: system
 icon: "🖥️"
 "#),
-        ("git_status.yaml", r#"# Git Status Tool
+    ("git_status.yaml", r#"# Git Status Tool
 # Quickly check git repository status
 
 name: GitStatus
@@ -555,7 +654,7 @@ parameters:
     default: "false"
 
 command: |
-  if [ "{{verbose}}" = "true" ]; then
+  if [ {{verbose}} = "true" ]; then
     echo "=== Branch ===" &&
     git branch --show-current 2>/dev/null || echo "(not a git repo)" &&
     echo "" &&
@@ -569,10 +668,11 @@ command: |
   fi
 
 sandbox_aware: true
+read_only: true
 category: git
 icon: "📋"
 "#),
-        ("word_count.yaml", r#"# Word Count Tool
+    ("word_count.yaml", r#"# Word Count Tool
 # Count lines, words, and characters in files
 
 name: WordCount
@@ -590,17 +690,18 @@ parameters:
     default: "false"
 
 command: |
-  if [ "{{summary_only}}" = "true" ]; then
-    wc {{pattern}} 2>/dev/null | tail -1 || echo "No matching files"
+  if [ {{summary_only}} = "true" ]; then
+    wc -- {{pattern}} 2>/dev/null | tail -1 || echo "No matching files"
   else
-    wc {{pattern}} 2>/dev/null || echo "No matching files"
+    wc -- {{pattern}} 2>/dev/null || echo "No matching files"
   fi
 
 sandbox_aware: true
+read_only: true
 category: files
 icon: "🔢"
 "#),
-        ("find_large_files.yaml", r#"# Find Large Files Tool
+    ("find_large_files.yaml", r#"# Find Large Files Tool
 # Find the largest files in a directory
 
 name: FindLargeFiles
@@ -619,13 +720,15 @@ parameters:
     default: "10"
 
 command: |
+  case {{path}} in -*) echo "Error: path must not start with -"; exit 1;; esac
   find {{path}} -type f -exec du -h {} + 2>/dev/null | sort -rh | head -{{count}}
 
 sandbox_aware: true
+read_only: true
 category: files
 icon: "📦"
 "#),
-        ("process_list.yaml", r#"# Process List Tool
+    ("process_list.yaml", r#"# Process List Tool
 # List running processes
 
 name: ProcessList
@@ -639,19 +742,32 @@ parameters:
     default: ""
 
 command: |
-  if [ -z "{{filter}}" ]; then
+  if [ -z {{filter}} ]; then
     ps aux --sort=-%mem 2>/dev/null | head -15 || ps aux | head -15
   else
-    ps aux 2>/dev/null | grep -i "{{filter}}" | grep -v grep || echo "No matching processes"
+    ps aux 2>/dev/null | grep -i -- {{filter}} | grep -v grep || echo "No matching processes"
   fi
 
 sandbox_aware: false
+read_only: true
 category: system
 icon: "⚙️"
 "#),
-    ];
+];
+
+/// Initialize default tool YAML files if they don't exist
+fn init_default_tools() -> Result<usize> {
+    use std::fs;
+    use std::path::PathBuf;
 
-    for (filename, content) in default_tools {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    let tools_dir = PathBuf::from(home).join(".config/grok-cli/tools");
+
+    // Create tools directory if it doesn't exist
+    fs::create_dir_all(&tools_dir)?;
+
+    let mut created_count = 0;
+    for (filename, content) in DEFAULT_TOOLS {
         let file_path = tools_dir.join(filename);
         if !file_path.exists() {
             fs::write(&file_path, content)?;
@@ -662,13 +778,58 @@ icon: "⚙️"
     Ok(created_count)
 }
 
+/// `config` with this run's command-line overrides applied, as `--print-config` shows it
+fn effective_config(mut config: Config, args: &Args) -> Config {
+    if let Some(model) = &args.model {
+        config.model = model.clone();
+    }
+    config
+}
+
 #[tokio::main] async fn main() -> Result<()> {
     // Set up panic hook for crash recovery
-    setup_panic_hook();
+    setup_panic_hook(load_config().logging);
 
-    dotenv::dotenv().ok();
     let args = Args::parse();
 
+    // --cwd: behave exactly as if started from that directory (sandbox, prompt,
+    // relative tool paths and session files), so switch to it first
+    if let Some(dir) = &args.cwd {
+        match resolve_cwd(dir) {
+            Ok(dir) => std::env::set_current_dir(&dir)?,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+    dotenv::dotenv().ok();
+
+    // Handle setup validation
+    if args.check {
+        let config_path = get_config_path();
+        let config_dir = config_path.parent().unwrap_or(std::path::Path::new("."));
+        let report = crate::check::run(config_dir, std::env::var(crate::api::API_KEY_ENV).ok());
+        println!("{}", report.render());
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
+
+    // Handle effective config dump
+    if args.print_config {
+        println!("{}", crate::config::redacted_config_json(&effective_config(load_config(), &args)));
+        return Ok(());
+    }
+
+    // Handle version report
+    if args.version {
+        let mut config = load_config();
+        if let Some(model) = &args.model {
+            config.model = model.clone();
+        }
+        println!("{}", crate::version::VersionInfo::collect(&config).render());
+        return Ok(());
+    }
+
     // Handle init mode
     if args.init {
         let config = Config::default();
@@ -716,22 +877,44 @@ icon: "⚙️"
         crate::config::save_config(&config).ok();
     }
 
-    let client = match GrokClient::new(config.model.clone()) {
-        Ok(c) => c,
+    let client = match GrokClient::with_config_key(config.model.clone(), config.api_key.as_deref()) {
+        Ok(c) => c.with_stream_format(config.stream_format.clone()),
         Err(_) => {
-            eprintln!("Error: XAI_API_KEY not found.");
+            eprintln!("Error: no API key found. Set XAI_API_KEY or add \"api_key\" to {}.", get_config_path().display());
             std::process::exit(1);
         }
     };
 
-    let mut messages = if args.resume {
+    // --continue picks the newest named session and restores it like /load,
+    // then starts up as a resume; with no named sessions it falls back to --resume
+    let mut continued: Option<String> = None;
+    if args.continue_session {
+        match most_recent_session(SESSIONS_DIR).map(|name| (load_named_session(&name, SESSIONS_DIR), name)) {
+            Some((Ok(session), name)) => {
+                save_history(&session.messages, DEFAULT_HISTORY_FILE).ok();
+                save_context(&session.api_messages, DEFAULT_CONTEXT_FILE).ok();
+                continued = Some(name);
+            }
+            Some((Err(e), name)) => eprintln!("Warning: could not load session '{}': {}", name, e),
+            None => eprintln!("No saved sessions in {}; resuming the previous chat instead.", SESSIONS_DIR),
+        }
+    }
+    let resume = args.resume || args.continue_session;
+
+    let mut messages = if resume {
         load_history(DEFAULT_HISTORY_FILE).unwrap_or_default()
     } else {
         Vec::new()
     };
 
     if messages.is_empty() {
-        let system_prompt = args.system.unwrap_or_else(|| get_default_system_prompt());
+        let system_prompt = args.system.unwrap_or_else(|| {
+            if config.compact_prompt {
+                get_compact_system_prompt()
+            } else {
+                get_default_system_prompt()
+            }
+        });
         messages.push(Message {
             role: "system".to_string(),
             content: Some(system_prompt),
@@ -740,20 +923,53 @@ icon: "⚙️"
         });
     }
 
+    // Headless: one turn, no terminal UI
+    if let Some(prompt) = &args.print {
+        let mut app = App::new(client, messages, &config, args.debug, resume);
+        if args.safe {
+            app.apply_safe_profile();
+        }
+        app.safety.approval = approval_decision(&args);
+        if let Some(tokens) = args.model_context {
+            app.set_model_context(tokens as usize);
+        }
+        let code = crate::headless::run(&mut app, prompt, args.json).await;
+        std::process::exit(code);
+    }
+
     // Enter raw mode and track state for panic recovery
     enable_raw_mode()?;
     TERMINAL_RAW.store(true, Ordering::SeqCst);
 
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new(client, messages, &config, args.debug);
-    
+    let mut app = App::new(client, messages, &config, args.debug, resume);
+    if let Some(name) = continued {
+        app.status_message = format!("Continued session '{}'", name);
+    }
+    if args.safe {
+        app.apply_safe_profile();
+    }
+    app.safety.approval = approval_decision(&args);
+    if args.no_color {
+        app.color_enabled = false;
+    }
+    if let Some(theme) = args.theme {
+        app.theme = crate::theme::Theme::new(theme);
+    }
+    if let Some(tokens) = args.model_context {
+        app.set_model_context(tokens as usize);
+    }
+    app.mouse_capture.set(config.settings.mouse_capture_enabled, terminal.backend_mut())?;
+    app.restore_draft(DEFAULT_DRAFT_FILE);
+    app.save_session_modes();
+
     // Initialize transaction manager with sandbox settings
     crate::transactions::init_transaction_manager(
-        if config.sandbox_enabled {
+        if app.sandbox_enabled {
             Some(std::env::current_dir()
                 .map(|p| p.to_string_lossy().to_string())
                 .unwrap_or_else(|_| ".".to_string()))
@@ -763,16 +979,19 @@ icon: "⚙️"
     );
 
     let res = run_app(&mut terminal, &mut app).await;
+    // Remember where the user was reading for --resume
+    app.save_session_modes();
+    // Drafts are only kept for crashes
+    if res.is_ok() {
+        save_draft("", DEFAULT_DRAFT_FILE).ok();
+    }
 
     // Clean up terminal and clear panic tracking
     disable_raw_mode()?;
     TERMINAL_RAW.store(false, Ordering::SeqCst);
 
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
+    app.mouse_capture.set(false, terminal.backend_mut())?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
 
     if let Err(err) = res {
@@ -783,19 +1002,39 @@ icon: "⚙️"
 }
 
 async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> Result<()> {
+    let mut drawn_images: Vec<crate::images::InlineImage> = Vec::new();
     loop {
-        if app.is_loading {
-             app.spinner_index = app.spinner_index.wrapping_add(1);
+        if app.redraw_requested {
+            app.redraw_requested = false;
+            terminal.clear()?;
+            drawn_images.clear();
         }
         terminal.draw(|f| ui(f, app))?;
 
+        // Images are only resent when they move, since each one is a large escape sequence
+        if app.inline_images != drawn_images {
+            let protocol = crate::images::protocol();
+            if protocol == crate::images::ImageProtocol::ITerm2 {
+                // Old iTerm2 images sit in cells ratatui thinks are blank, so repaint everything
+                terminal.clear()?;
+                terminal.draw(|f| ui(f, app))?;
+            }
+            crate::images::draw(protocol, &app.inline_images, &mut io::stdout()).ok();
+            drawn_images = app.inline_images.clone();
+        }
+
         if app.should_quit {
             break;
         }
+        app.autosave_if_due(std::time::Instant::now());
 
         // Event Handling
         if event::poll(Duration::from_millis(50))? {
             let event = event::read()?;
+            if let Event::Resize(..) = event {
+                app.handle_resize();
+                continue;
+            }
             if let Event::Key(key) = &event {
                 match &mut app.mode {
                      AppMode::Chat => {
@@ -884,15 +1123,21 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> R
                                      app.toggle_tool_expansion();
                                      continue;
                                  }
+                                 KeyCode::Char('l') => {
+                                     app.toggle_todos();
+                                     continue;
+                                 }
+                                 KeyCode::Char('t') => {
+                                     app.toggle_thinking();
+                                     continue;
+                                 }
                                  KeyCode::Char('c') => {
                                      let now = std::time::Instant::now();
                                      let input_empty = app.input.lines().join("").is_empty();
 
                                      // If loading/thinking, cancel the operation
                                      if app.is_loading {
-                                         app.cancel_requested = true;
-                                         app.is_loading = false;
-                                         app.status_message = "Cancelled".to_string();
+                                         app.cancel_task();
                                          continue;
                                      }
 
@@ -949,6 +1194,14 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> R
                                     app.mode = AppMode::ErrorView;
                                 }
                             }
+                            KeyCode::F(2) => {
+                                // Toggle mouse capture so native text selection works
+                                match app.mouse_capture.toggle(&mut io::stdout()) {
+                                    Ok(true) => app.status_message = "Mouse capture on".to_string(),
+                                    Ok(false) => app.status_message = "Mouse capture off - native text selection enabled".to_string(),
+                                    Err(e) => app.errors.push(format!("Failed to toggle mouse capture: {}", e)),
+                                }
+                            }
                             KeyCode::PageUp => {
                                 let i = app.list_state.selected().unwrap_or(0);
                                 if i > 0 {
@@ -966,9 +1219,7 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> R
                             KeyCode::Esc => {
                                 // If loading/thinking, cancel the operation
                                 if app.is_loading {
-                                    app.cancel_requested = true;
-                                    app.is_loading = false;
-                                    app.status_message = "Cancelled".to_string();
+                                    app.cancel_task();
                                 } else if app.input.lines().join("").is_empty() {
                                     break;
                                 } else {
@@ -977,19 +1228,8 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> R
                             }
                             KeyCode::Enter => {
                                 // Multi-line input: Ctrl+Enter, Shift+Enter, or line ending with \
-                                let content = app.input.lines().join("\n");
-                                let ends_with_backslash = content.ends_with('\\');
-
-                                if key.modifiers.contains(KeyModifiers::CONTROL)
-                                    || key.modifiers.contains(KeyModifiers::SHIFT) {
-                                    app.input.insert_newline();
-                                } else if ends_with_backslash {
-                                    // Remove trailing backslash and add newline
-                                    app.input.delete_char();
-                                    app.input.insert_newline();
-                                } else {
-                                    app.submit_message();
-                                }
+                                // (the same for plan feedback while a confirmation is pending)
+                                app.press_enter(key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::SHIFT));
                             }
                             _ => {
                                 app.input.input(event);
@@ -1015,6 +1255,26 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> R
                              KeyCode::Enter => {
                                  app.handle_planning_selection();
                              }
+                             KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                 // Same escape hatch as in chat: reject the pending tool and stop
+                                 app.cancel_planning();
+                                 save_history(&app.messages, DEFAULT_HISTORY_FILE).ok();
+                                 save_context(&app.api_messages, DEFAULT_CONTEXT_FILE).ok();
+                                 app.save_session_modes();
+                             }
+                             _ => {}
+                         }
+                     }
+                     AppMode::DraftChoice(state) => {
+                         match key.code {
+                             KeyCode::Up => state.list_state.select(Some(0)),
+                             KeyCode::Down => state.list_state.select(Some(DRAFT_OPTIONS.len() - 1)),
+                             KeyCode::Enter => app.handle_draft_choice(),
+                             KeyCode::Esc => app.resolve_draft(true),
+                             KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                 // Dismissing the question loses nothing
+                                 app.resolve_draft(true);
+                             }
                              _ => {}
                          }
                      }
@@ -1048,6 +1308,11 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> R
                                              app.config.settings.rate_limiter_enabled = !app.config.settings.rate_limiter_enabled;
                                              save_config(&app.config).ok();
                                          }
+                                         "mouse_capture_enabled" => {
+                                             app.config.settings.mouse_capture_enabled = !app.config.settings.mouse_capture_enabled;
+                                             save_config(&app.config).ok();
+                                             app.mouse_capture.set(app.config.settings.mouse_capture_enabled, &mut io::stdout()).ok();
+                                         }
                                          _ => {}
                                      }
                                  }
@@ -1074,16 +1339,7 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> R
 
             match event {
                 AppEvent::NewMessage(msg) => {
-                    if let Some(last) = app.messages.last_mut() {
-                        if last.role == "assistant" && msg.role == "assistant" {
-                             *last = msg.clone();
-                        } else {
-                            app.messages.push(msg.clone());
-                        }
-                    } else {
-                        app.messages.push(msg.clone());
-                    }
-                    app.api_messages.push(msg);
+                    app.add_message(msg);
                     save_context(&app.api_messages, DEFAULT_CONTEXT_FILE).ok();
                     app.auto_scroll = true; // Enable auto-scroll for new messages
                     app.list_state.select(Some(app.messages.len().saturating_sub(1)));
@@ -1115,71 +1371,29 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> R
                     app.list_state.select(Some(app.messages.len().saturating_sub(1)));
                 },
                 AppEvent::ThinkingToken(s) => {
-                    // Update thinking preview for status bar
-                    if let Some(ref mut preview) = app.thinking_preview {
-                        preview.push_str(&s);
-                        // Keep only last ~100 chars for display
-                        if preview.len() > 150 {
-                            let start = preview.len() - 100;
-                            // Find a safe char boundary
-                            let start = preview.char_indices()
-                                .find(|(i, _)| *i >= start)
-                                .map(|(i, _)| i)
-                                .unwrap_or(0);
-                            *preview = preview[start..].to_string();
-                        }
-                    } else {
-                        app.thinking_preview = Some(s.clone());
-                    }
-
-                    if let Some(last) = app.messages.last_mut() {
-                        if last.role == "thought" {
-                             if let Some(content) = &mut last.content {
-                                 content.push_str(&s);
-                             } else {
-                                 last.content = Some(s);
-                             }
-                        } else {
-                            app.messages.push(Message {
-                                role: "thought".to_string(),
-                                content: Some(s),
-                                tool_calls: None,
-                                tool_call_id: None,
-                            });
-                        }
-                    } else {
-                         app.messages.push(Message {
-                            role: "thought".to_string(),
-                            content: Some(s),
-                            tool_calls: None,
-                            tool_call_id: None,
-                        });
-                    }
-                    app.list_state.select(Some(app.messages.len().saturating_sub(1)));
+                    app.push_thinking(&s);
                 },
                 AppEvent::StatusUpdate(s) => {
                     app.status_message = s;
                 },
+                AppEvent::ToolOutputChunk(id, line) => {
+                    app.push_tool_output(id, line);
+                },
                 AppEvent::Error(e) => {
                     // Store error in hidden list (accessible via F12)
                     app.errors.push(e.clone());
                     app.status_message = "Error occurred".to_string();
                     app.is_loading = false;
+                    app.thinking.clear();
                     // Log to file (no terminal spam)
-                    if let Ok(mut file) = std::fs::OpenOptions::new().create(true).append(true).open("grok-cli-errors.log") {
+                    if let Ok(mut file) = crate::logging::open_log("grok-cli-errors.log", &app.config.logging) {
                         use std::io::Write;
                         let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
                         writeln!(file, "[{}] {}", timestamp, e).ok();
                     }
                 },
                 AppEvent::Finished => {
-                    // Capture task duration
-                    if let Some(start) = app.task_start.take() {
-                        app.last_task_duration = Some(start.elapsed());
-                    }
-                    app.is_loading = false;
-                    app.status_message = "Ready".to_string();
-                    app.thinking_preview = None; // Clear thinking preview
+                    app.finish_task();
                     save_history(&app.messages, DEFAULT_HISTORY_FILE).ok();
                 }
                 AppEvent::UsageUpdate(input_tokens, output_tokens) => {
@@ -1222,57 +1436,72 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> R
                     app.status_message = "Confirm plan: y/n or provide feedback".to_string();
                 }
                 AppEvent::BashApprovalRequest(tc, cmd) => {
-                    // Use Planning modal for bash command approval
-                    let truncated_cmd = if cmd.len() > 60 {
-                        format!("{}...", &cmd[..60])
-                    } else {
-                        cmd.clone()
-                    };
+                    // Use Planning modal for bash command approval, saved right
+                    // away so a crash before answering doesn't lose the turn
+                    app.request_command_approval(tc, cmd);
+                    app.save_session_modes();
+                }
+                AppEvent::WebSearchApprovalRequest(tc, query) => {
+                    // Use Planning modal for web search approval
+                    let truncated_query = crate::ui::truncate(&query, 51);
                     app.mode = AppMode::Planning(PlanningState {
-                        question: format!("Execute command?\n$ {}", truncated_cmd),
+                        question: format!("Web search ({})?\n🔍 {}", app.config.web_search.backend.name(), truncated_query),
                         options: vec![
                             "Approve".to_string(),
-                            "Always Approve (save to config)".to_string(),
                             "Reject".to_string(),
                         ],
-                        selected: vec![false, false, false],
+                        selected: vec![false, false],
                         tool_call_id: tc.id.clone(),
-                        tool_call_cmd: Some((tc, cmd)),
+                        tool_call_cmd: Some((tc, query)),
                         list_state: ratatui::widgets::ListState::default(),
                     });
                     if let AppMode::Planning(ref mut s) = app.mode {
                         s.list_state.select(Some(0));
                     }
                     app.is_loading = false;
-                    app.status_message = "Command approval required".to_string();
+                    app.status_message = "Web search approval required".to_string();
                 }
-                AppEvent::WebSearchApprovalRequest(tc, query) => {
-                    // Use Planning modal for web search approval
-                    let truncated_query = if query.len() > 50 {
-                        format!("{}...", &query[..50])
+                AppEvent::WriteApprovalRequest(tc, target, preview) => {
+                    // Show the dry run in the chat, then ask in the Planning modal
+                    if let Some(preview) = preview {
+                        app.messages.push(Message {
+                            role: "system".to_string(),
+                            content: Some(preview),
+                            tool_calls: None,
+                            tool_call_id: None,
+                        });
+                        app.auto_scroll = true;
+                    }
+                    let char_count = target.chars().count();
+                    let truncated_target = if char_count > 50 {
+                        format!("...{}", target.chars().skip(char_count - 47).collect::<String>())
                     } else {
-                        query.clone()
+                        target.clone()
                     };
                     app.mode = AppMode::Planning(PlanningState {
-                        question: format!("Web search?\n🔍 {}", truncated_query),
+                        question: format!("Apply {} to {}?", tc.function.name, truncated_target),
                         options: vec![
                             "Approve".to_string(),
                             "Reject".to_string(),
                         ],
                         selected: vec![false, false],
                         tool_call_id: tc.id.clone(),
-                        tool_call_cmd: Some((tc, query)),
+                        tool_call_cmd: Some((tc, target)),
                         list_state: ratatui::widgets::ListState::default(),
                     });
                     if let AppMode::Planning(ref mut s) = app.mode {
                         s.list_state.select(Some(0));
                     }
                     app.is_loading = false;
-                    app.status_message = "Web search approval required".to_string();
+                    app.status_message = "File change approval required".to_string();
+                }
+                AppEvent::PlanStepPreview(tc, target, preview) => {
+                    app.request_plan_step_preview(tc, target, preview);
                 }
                 AppEvent::RoleSwitch(from, to) => {
                     // Update status to show role switch
                     app.status_message = format!("Switching @{} → @{}", from, to);
+                    app.last_role = Some(to);
                 }
                 AppEvent::TodoUpdate(new_todos) => {
                     // Smart merge: preserve completed items from old list that might have been dropped
@@ -1347,6 +1576,20 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> R
                     app.auto_scroll = true;
                     save_history(&app.messages, DEFAULT_HISTORY_FILE).ok();
                 }
+                AppEvent::ContextReplaced(api_messages) => {
+                    // Only the API context shrinks; the transcript keeps every message
+                    app.api_messages = api_messages;
+                    save_context(&app.api_messages, DEFAULT_CONTEXT_FILE).ok();
+                    app.status_message = "Context summarized".to_string();
+                }
+                AppEvent::Compacted(summarized) => {
+                    app.record_compaction(summarized);
+                    save_history(&app.messages, DEFAULT_HISTORY_FILE).ok();
+                }
+                AppEvent::CompareComplete(answers) => {
+                    app.show_comparison(answers);
+                    save_history(&app.messages, DEFAULT_HISTORY_FILE).ok();
+                }
                 AppEvent::RateLimitPause(seconds) => {
                     app.rate_limit_paused = true;
                     app.rate_limit_resume_at = Some(std::time::Instant::now() + Duration::from_secs(seconds));
@@ -1374,4 +1617,91 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> R
         }
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_print_config_applies_overrides_and_redacts_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, r#"{"model": "grok-3", "api_key": "xai-secret", "web_search": {"backend": "brave", "api_key": "brave-secret"}}"#).unwrap();
+        let args = Args::try_parse_from(["grok-cli", "--print-config", "--model", "grok-3-mini"]).unwrap();
+
+        let printed = crate::config::redacted_config_json(&effective_config(crate::config::load_config_from(&path), &args));
+        let value: Value = serde_json::from_str(&printed).unwrap();
+        assert_eq!(value["model"], "grok-3-mini");
+        assert_eq!(value["web_search"]["backend"], "brave");
+        assert_eq!(value["api_key"], "<redacted>");
+        assert_eq!(value["web_search"]["api_key"], "<redacted>");
+        assert!(!printed.contains("secret"));
+    }
+
+    #[test]
+    fn test_compact_prompt_is_shorter_and_names_cwd() {
+        let full = get_default_system_prompt();
+        let compact = get_compact_system_prompt();
+        let cwd = std::env::current_dir().unwrap().to_string_lossy().to_string();
+
+        assert!(compact.len() * 3 < full.len(), "compact prompt should be well under a third of the full prompt");
+        assert!(compact.contains(&cwd));
+    }
+
+    #[test]
+    fn test_cwd_flag_sets_sandbox_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("project")).unwrap();
+        std::fs::write(dir.path().join("project/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(dir.path().join("file.txt"), "x").unwrap();
+
+        let args = Args::try_parse_from(["grok-cli", "--cwd", dir.path().join("project/../project").to_str().unwrap()]).unwrap();
+        let resolved = resolve_cwd(args.cwd.as_deref().unwrap()).unwrap();
+        assert_eq!(resolved, std::fs::canonicalize(dir.path().join("project")).unwrap());
+
+        // The sandbox is rooted at the resolved directory
+        let root = resolved.to_str().unwrap();
+        assert!(crate::tools::is_path_in_sandbox(&format!("{}/main.rs", root), root));
+        assert!(!crate::tools::is_path_in_sandbox(dir.path().join("file.txt").to_str().unwrap(), root));
+
+        assert!(resolve_cwd(&dir.path().join("missing")).unwrap_err().contains("--cwd"));
+        assert!(resolve_cwd(&dir.path().join("file.txt")).unwrap_err().contains("not a directory"));
+    }
+
+    #[test]
+    fn test_approve_all_and_reject_all_flags() {
+        let decision = |flags: &[&str]| {
+            let args = Args::try_parse_from(std::iter::once("grok-cli").chain(flags.iter().copied())).unwrap();
+            approval_decision(&args)
+        };
+        assert_eq!(decision(&[]), ApprovalDecision::Ask);
+        assert_eq!(decision(&["--approve-all"]), ApprovalDecision::ApproveAll);
+        assert_eq!(decision(&["--reject-all"]), ApprovalDecision::RejectAll);
+        assert!(Args::try_parse_from(["grok-cli", "--approve-all", "--reject-all"]).is_err());
+    }
+
+    #[test]
+    fn test_default_tools_do_not_run_their_arguments() {
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = dir.path().to_str().unwrap();
+        std::fs::write(dir.path().join("keep.txt"), "data").unwrap();
+
+        for (filename, content) in DEFAULT_TOOLS {
+            let path = dir.path().join(filename);
+            std::fs::write(&path, content).unwrap();
+            let tool = crate::tool_plugins::parse_tool_file(&path)
+                .unwrap_or_else(|e| panic!("{}: {}", filename, e));
+            assert!(tool.read_only, "{}", filename);
+
+            for payload in ["$(touch pwned)", "`touch pwned`", "x; touch pwned", "\"; touch pwned; \"", "-delete"] {
+                let args: serde_json::Map<String, Value> = tool.parameters.iter()
+                    .map(|p| (p.name.clone(), Value::String(payload.to_string())))
+                    .collect();
+                tool.execute(&Value::Object(args), Some(cwd));
+                assert!(!dir.path().join("pwned").exists(), "{} ran {:?}", filename, payload);
+                assert!(dir.path().join("keep.txt").exists(), "{} ran {:?}", filename, payload);
+            }
+        }
+    }
+}
@@ -16,10 +16,17 @@ pub fn render_markdown(text: &str, width: usize) -> Vec<ListItem<'static>> {
 pub fn render_markdown_lines(text: &str, width: usize) -> Vec<Line<'static>> {
     let mut lines = Vec::new();
     let mut in_code_block = false;
+    let mut code_syntax = None;
+    let source: Vec<&str> = text.lines().collect();
+    let mut next = 0;
 
-    for line in text.lines() {
+    while next < source.len() {
+        let line = source[next];
+        next += 1;
         if line.trim().starts_with("```") {
             in_code_block = !in_code_block;
+            // The opening fence names the language (```rust)
+            code_syntax = line.trim().trim_start_matches('`').split_whitespace().next().and_then(syntax_for);
             // Render the marker itself
             lines.push(Line::from(Span::styled(
                 line.to_string(),
@@ -28,13 +35,21 @@ pub fn render_markdown_lines(text: &str, width: usize) -> Vec<Line<'static>> {
             continue;
         }
 
+        // A table: header row, then a |---|---| separator, then body rows
+        if !in_code_block && is_table_row(line) && source.get(next).is_some_and(|l| is_table_separator(l)) {
+            let start = next - 1;
+            while next < source.len() && is_table_row(source[next]) {
+                next += 1;
+            }
+            lines.extend(render_table(&source[start..next], width));
+            continue;
+        }
+
         if in_code_block {
-            let wrapped = wrap_code(line, width);
-            for w in wrapped {
-                lines.push(Line::from(Span::styled(
-                    format!("  {}", w), // Indent code
-                    Style::default().fg(Color::Cyan)
-                )));
+            for row in wrap_spans(highlight_code(line, code_syntax), width) {
+                let mut spans = vec![Span::raw("  ")]; // Indent code
+                spans.extend(row);
+                lines.push(Line::from(spans));
             }
         } else {
             // Normal text processing
@@ -106,56 +121,372 @@ pub fn wrap_text(text: &str, width: usize) -> Vec<String> {
     lines
 }
 
-fn wrap_code(text: &str, width: usize) -> Vec<String> {
-    let mut lines = Vec::new();
-    let chars: Vec<char> = text.chars().collect();
-    for chunk in chars.chunks(width) {
-        lines.push(chunk.iter().collect());
+fn is_table_row(line: &str) -> bool {
+    line.trim_start().starts_with('|')
+}
+
+/// `|---|:--:|--:|`: every cell dashes, optionally with alignment colons
+fn is_table_separator(line: &str) -> bool {
+    is_table_row(line)
+        && table_cells(line).iter().all(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+        })
+}
+
+/// The trimmed cells of a `| a | b |` row
+fn table_cells(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').unwrap_or(line);
+    line.split('|').map(|cell| cell.trim().to_string()).collect()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CellAlign {
+    Left,
+    Center,
+    Right,
+}
+
+/// Between columns, and the width it takes
+const TABLE_DIVIDER: &str = " │ ";
+const TABLE_DIVIDER_WIDTH: usize = 3;
+/// Narrowest a column is squeezed to before columns are dropped
+const MIN_TABLE_COLUMN: usize = 3;
+
+fn table_width(widths: &[usize]) -> usize {
+    widths.iter().sum::<usize>() + TABLE_DIVIDER_WIDTH * widths.len().saturating_sub(1)
+}
+
+/// Shrink the widest columns first (ties: the leftmost) until the table fits
+/// `max_width` or every column is down to `MIN_TABLE_COLUMN`
+fn shrink_columns(natural: &[usize], max_width: usize) -> Vec<usize> {
+    let mut widths: Vec<usize> = natural.iter().map(|&w| w.max(1)).collect();
+    while table_width(&widths) > max_width {
+        let (widest, &width) = widths.iter().enumerate().rev().max_by_key(|&(_, w)| *w).unwrap();
+        if width <= MIN_TABLE_COLUMN {
+            break;
+        }
+        widths[widest] -= 1;
     }
-    if lines.is_empty() { lines.push("".to_string()); }
-    lines
+    widths
 }
 
-fn parse_inline(text: String) -> Line<'static> {
-    // Basic detection of `code`
-    if text.contains('`') {
+/// Column widths that fit `max_width`. When squeezing isn't enough, trailing
+/// columns are dropped (returns true) to make room for a `…` column.
+fn table_widths(natural: &[usize], max_width: usize) -> (Vec<usize>, bool) {
+    let widths = shrink_columns(natural, max_width);
+    if table_width(&widths) <= max_width {
+        return (widths, false);
+    }
+    if widths.len() == 1 {
+        return (vec![max_width.max(1)], false);
+    }
+    let room = max_width.saturating_sub(TABLE_DIVIDER_WIDTH + 1);
+    let mut keep = widths.len() - 1;
+    while keep > 1 && MIN_TABLE_COLUMN * keep + TABLE_DIVIDER_WIDTH * (keep - 1) > room {
+        keep -= 1;
+    }
+    let mut widths = shrink_columns(&natural[..keep], room);
+    // A single column still too wide is cut to fit
+    widths[0] = widths[0].min(room.max(1));
+    (widths, true)
+}
+
+/// `text` cut (with …) or padded to exactly `width` characters
+fn fit_cell(text: &str, width: usize, align: CellAlign) -> String {
+    let len = text.chars().count();
+    if len > width {
+        let kept: String = text.chars().take(width.saturating_sub(1)).collect();
+        return format!("{}…", kept);
+    }
+    let pad = width - len;
+    let (left, right) = match align {
+        CellAlign::Left => (0, pad),
+        CellAlign::Right => (pad, 0),
+        CellAlign::Center => (pad / 2, pad - pad / 2),
+    };
+    format!("{}{}{}", " ".repeat(left), text, " ".repeat(right))
+}
+
+/// A pipe table (header, separator, body rows) as aligned columns
+fn render_table(rows: &[&str], max_width: usize) -> Vec<Line<'static>> {
+    let header = table_cells(rows[0]);
+    let aligns: Vec<CellAlign> = table_cells(rows[1])
+        .iter()
+        .map(|cell| match (cell.starts_with(':'), cell.ends_with(':')) {
+            (true, true) => CellAlign::Center,
+            (false, true) => CellAlign::Right,
+            _ => CellAlign::Left,
+        })
+        .collect();
+    let columns = header.len();
+    let body: Vec<Vec<String>> = rows[2..].iter().map(|row| table_cells(row)).collect();
+
+    let mut natural = vec![0; columns];
+    for row in std::iter::once(&header).chain(&body) {
+        for (col, cell) in row.iter().take(columns).enumerate() {
+            natural[col] = natural[col].max(cell.chars().count());
+        }
+    }
+    let (widths, dropped) = table_widths(&natural, max_width);
+
+    let divider = Style::default().fg(Color::DarkGray);
+    let render_row = |cells: &[String], style: Style| {
         let mut spans = Vec::new();
-        
-        // Let's do a manual scan
-        let mut current_segment = String::new();
-        let mut in_code = false;
-        
-        // If it starts with backtick, the loop below will catch the *second* backtick closing it,
-        // or we need to handle the first char carefully.
-        // Actually, if I just iterate chars, I can toggle.
-        
-        for c in text.chars() {
-            if c == '`' {
-                if !current_segment.is_empty() {
-                    spans.push(if in_code {
-                        Span::styled(current_segment.clone(), Style::default().fg(Color::Cyan))
-                    } else {
-                        Span::raw(current_segment.clone())
-                    });
-                    current_segment.clear();
+        for (col, &width) in widths.iter().enumerate() {
+            if col > 0 {
+                spans.push(Span::styled(TABLE_DIVIDER, divider));
+            }
+            let cell = cells.get(col).map(String::as_str).unwrap_or("");
+            let align = aligns.get(col).copied().unwrap_or(CellAlign::Left);
+            spans.push(Span::styled(fit_cell(cell, width, align), style));
+        }
+        if dropped {
+            spans.push(Span::styled(TABLE_DIVIDER, divider));
+            spans.push(Span::styled("…", divider));
+        }
+        Line::from(spans)
+    };
+
+    let mut lines = vec![render_row(&header, Style::default().add_modifier(Modifier::BOLD))];
+    let mut rule: Vec<String> = widths.iter().map(|&w| "─".repeat(w)).collect();
+    if dropped {
+        rule.push("─".to_string());
+    }
+    lines.push(Line::from(Span::styled(rule.join("─┼─"), divider)));
+    lines.extend(body.iter().map(|row| render_row(row, Style::default())));
+    lines
+}
+
+/// Break styled spans into rows of at most `width` characters, keeping each
+/// piece's style (code is cut anywhere, not at words)
+fn wrap_spans(spans: Vec<Span<'static>>, width: usize) -> Vec<Vec<Span<'static>>> {
+    let width = width.max(1);
+    let mut rows = vec![Vec::new()];
+    let mut used = 0;
+    for span in spans {
+        let mut piece = String::new();
+        for c in span.content.chars() {
+            if used == width {
+                if !piece.is_empty() {
+                    rows.last_mut().unwrap().push(Span::styled(std::mem::take(&mut piece), span.style));
                 }
-                in_code = !in_code;
-            } else {
-                current_segment.push(c);
+                rows.push(Vec::new());
+                used = 0;
             }
+            piece.push(c);
+            used += 1;
+        }
+        if !piece.is_empty() {
+            rows.last_mut().unwrap().push(Span::styled(piece, span.style));
+        }
+    }
+    rows
+}
+
+/// What the code highlighter knows about a language
+struct Syntax {
+    keywords: &'static [&'static str],
+    line_comment: &'static str,
+    quotes: &'static [char],
+}
+
+static RUST: Syntax = Syntax {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false", "fn",
+        "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self",
+        "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+    ],
+    line_comment: "//",
+    quotes: &['"'],
+};
+
+static PYTHON: Syntax = Syntax {
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else", "except",
+        "False", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "None", "nonlocal", "not",
+        "or", "pass", "raise", "return", "self", "True", "try", "while", "with", "yield",
+    ],
+    line_comment: "#",
+    quotes: &['"', '\''],
+};
+
+static JAVASCRIPT: Syntax = Syntax {
+    keywords: &[
+        "async", "await", "break", "case", "catch", "class", "const", "continue", "default", "delete", "do", "else",
+        "export", "extends", "false", "finally", "for", "from", "function", "if", "import", "in", "instanceof",
+        "interface", "let", "new", "null", "of", "return", "switch", "this", "throw", "true", "try", "type", "typeof",
+        "undefined", "var", "void", "while", "yield",
+    ],
+    line_comment: "//",
+    quotes: &['"', '\'', '`'],
+};
+
+static GO: Syntax = Syntax {
+    keywords: &[
+        "break", "case", "chan", "const", "continue", "default", "defer", "else", "fallthrough", "false", "for",
+        "func", "go", "goto", "if", "import", "interface", "map", "nil", "package", "range", "return", "select",
+        "struct", "switch", "true", "type", "var",
+    ],
+    line_comment: "//",
+    quotes: &['"', '`'],
+};
+
+static SHELL: Syntax = Syntax {
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in", "local",
+        "return", "then", "until", "while",
+    ],
+    line_comment: "#",
+    quotes: &['"', '\''],
+};
+
+/// The highlighter for a code fence's language tag, if it knows it
+fn syntax_for(lang: &str) -> Option<&'static Syntax> {
+    match lang.to_lowercase().as_str() {
+        "rust" | "rs" => Some(&RUST),
+        "python" | "py" => Some(&PYTHON),
+        "javascript" | "js" | "jsx" | "typescript" | "ts" | "tsx" => Some(&JAVASCRIPT),
+        "go" | "golang" => Some(&GO),
+        "sh" | "bash" | "shell" | "zsh" => Some(&SHELL),
+        _ => None,
+    }
+}
+
+/// Add `text` to `spans`, joining it to the last span when the style matches
+fn push_code_span(spans: &mut Vec<Span<'static>>, text: String, style: Style) {
+    match spans.last_mut() {
+        Some(last) if last.style == style => last.content.to_mut().push_str(&text),
+        _ => spans.push(Span::styled(text, style)),
+    }
+}
+
+/// Color one line of code: keywords, strings, comments, numbers, types and
+/// calls. Lines are colored on their own, so a string or comment spanning
+/// lines is only colored on its first. Unknown languages stay plain.
+fn highlight_code(line: &str, syntax: Option<&Syntax>) -> Vec<Span<'static>> {
+    let plain = Style::default().fg(Color::Gray);
+    let Some(syntax) = syntax else {
+        return vec![Span::styled(line.to_string(), plain)];
+    };
+    let keyword = Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD);
+    let string = Style::default().fg(Color::Green);
+    let comment = Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC);
+    let number = Style::default().fg(Color::LightYellow);
+    let type_name = Style::default().fg(Color::Yellow);
+    let call = Style::default().fg(Color::LightBlue);
+
+    let chars: Vec<char> = line.chars().collect();
+    let comment_start: Vec<char> = syntax.line_comment.chars().collect();
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        // `#` only starts a comment at a word boundary (not in `$#` or `a#b`)
+        let comment_here = chars[i..].starts_with(&comment_start)
+            && (syntax.line_comment != "#" || i == 0 || chars[i - 1].is_whitespace());
+        if comment_here {
+            push_code_span(&mut spans, chars[i..].iter().collect(), comment);
+            break;
         }
-        if !current_segment.is_empty() {
-             spans.push(if in_code {
-                Span::styled(current_segment, Style::default().fg(Color::Cyan))
+        let start = i;
+        let style = if syntax.quotes.contains(&c) {
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            i = (i + 1).min(chars.len());
+            string
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            number
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if syntax.keywords.contains(&word.as_str()) {
+                keyword
+            } else if matches!(chars.get(i).copied(), Some('(') | Some('!')) {
+                call
+            } else if word.starts_with(|c: char| c.is_uppercase()) {
+                type_name
             } else {
-                Span::raw(current_segment)
-            });
+                plain
+            }
+        } else {
+            i += 1;
+            plain
+        };
+        push_code_span(&mut spans, chars[start..i].iter().collect(), style);
+    }
+    spans
+}
+
+fn parse_inline(text: String) -> Line<'static> {
+    // Basic detection of `code` and **bold**
+    if !text.contains('`') && !text.contains("**") {
+        return Line::from(Span::raw(text));
+    }
+
+    let mut spans = Vec::new();
+    let mut current_segment = String::new();
+    let mut in_code = false;
+    let mut in_bold = false;
+    let style = |in_code: bool, in_bold: bool| {
+        let style = if in_code { Style::default().fg(Color::Cyan) } else { Style::default() };
+        if in_bold { style.add_modifier(Modifier::BOLD) } else { style }
+    };
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        // Markers toggle the style of what follows; ** inside code is literal,
+        // and so is an opening ** with no closing one later in the line
+        let toggles_bold = c == '*'
+            && !in_code
+            && chars.peek() == Some(&'*')
+            && (in_bold || chars.clone().skip(1).collect::<String>().contains("**"));
+        if c == '`' || toggles_bold {
+            if !current_segment.is_empty() {
+                spans.push(Span::styled(std::mem::take(&mut current_segment), style(in_code, in_bold)));
+            }
+            if toggles_bold {
+                chars.next();
+                in_bold = !in_bold;
+            } else {
+                in_code = !in_code;
+            }
+        } else {
+            current_segment.push(c);
         }
-        
-        Line::from(spans)
-    } else {
-        Line::from(Span::raw(text))
     }
+    if !current_segment.is_empty() {
+        spans.push(Span::styled(current_segment, style(in_code, in_bold)));
+    }
+
+    Line::from(spans)
+}
+
+/// Markdown source as-is (`/raw`): no styling, no markers removed, line breaks
+/// and indentation kept; only lines wider than `width` are word-wrapped
+pub fn render_raw_lines(text: &str, width: usize) -> Vec<Line<'static>> {
+    text.lines()
+        .flat_map(|line| {
+            if line.chars().count() <= width {
+                vec![line.to_string()]
+            } else {
+                wrap_text(line, width)
+            }
+        })
+        .map(Line::from)
+        .collect()
 }
 
 #[cfg(test)]
@@ -179,4 +510,91 @@ mod tests {
         assert_eq!(line.spans[1].content, "code");
         assert_eq!(line.spans[1].style.fg, Some(Color::Cyan));
     }
+
+    #[test]
+    fn test_unmatched_bold_marker_is_literal() {
+        let line = parse_inline("2 ** 3 is 8".to_string());
+        assert_eq!(line.spans.len(), 1);
+        assert_eq!(line.spans[0].content, "2 ** 3 is 8");
+        assert!(!line.spans[0].style.add_modifier.contains(Modifier::BOLD));
+
+        let line = parse_inline("**Note:** 2 ** 3 is 8".to_string());
+        assert_eq!(line.spans.len(), 2);
+        assert_eq!(line.spans[0].content, "Note:");
+        assert!(line.spans[0].style.add_modifier.contains(Modifier::BOLD));
+        assert_eq!(line.spans[1].content, " 2 ** 3 is 8");
+        assert!(!line.spans[1].style.add_modifier.contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn test_rust_code_block_is_highlighted() {
+        let text = "```rust\nfn main() { let n = 42; println!(\"hi\"); } // done\n```";
+        let rendered = render_markdown_lines(text, 80);
+        assert_eq!(rendered.len(), 3);
+        let code = &rendered[1];
+        assert_eq!(code.to_string(), "  fn main() { let n = 42; println!(\"hi\"); } // done");
+
+        let style_of = |text: &str| code.spans.iter().find(|s| s.content == text).unwrap().style;
+        let styles = [style_of("fn"), style_of("main"), style_of("42"), style_of("\"hi\""), style_of("// done")];
+        for (i, a) in styles.iter().enumerate() {
+            for b in &styles[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+        assert_eq!(style_of("fn"), style_of("let"));
+
+        // Unknown languages get one plain style
+        let rendered = render_markdown_lines("```brainfuck\n++[>+<-] fn 42\n```", 80);
+        assert_eq!(rendered[1].spans.len(), 2);
+        assert_eq!(rendered[1].spans[1].style, Style::default().fg(Color::Gray));
+
+        // Wrapping keeps each piece's style
+        let rendered = render_markdown_lines("```py\nreturn 'abcdef'\n```", 10);
+        assert_eq!(rendered[1].to_string(), "  return 'ab");
+        assert_eq!(rendered[2].to_string(), "  cdef'");
+        assert_eq!(rendered[2].spans[1].style, rendered[1].spans.last().unwrap().style);
+    }
+
+    #[test]
+    fn test_table_columns_are_aligned() {
+        let text = "Results:\n| Crate | Version | Downloads |\n|-------|:-------:|----------:|\n| serde | 1.0 | 300M |\n| ratatui | 0.29.0 | 9M |\nDone.";
+        let rendered: Vec<String> = render_markdown_lines(text, 80).iter().map(|l| l.to_string()).collect();
+        assert_eq!(rendered, [
+            "Results:",
+            "Crate   │ Version │ Downloads",
+            "────────┼─────────┼──────────",
+            "serde   │   1.0   │      300M",
+            "ratatui │ 0.29.0  │        9M",
+            "Done.",
+        ]);
+        let header = &render_markdown_lines(text, 80)[1];
+        assert!(header.spans[0].style.add_modifier.contains(Modifier::BOLD));
+
+        // Too narrow: the widest columns shrink, with cut cells marked
+        let narrow: Vec<String> = render_markdown_lines(text, 24).iter().map(|l| l.to_string()).collect();
+        assert_eq!(narrow[1], "Crate  │ Versi… │ Downl…");
+        assert_eq!(narrow[4], "ratat… │ 0.29.0 │     9M");
+        assert!(narrow[1..5].iter().all(|l| l.chars().count() == 24));
+
+        // Narrower still: trailing columns go, behind an ellipsis column
+        let cramped: Vec<String> = render_markdown_lines(text, 14).iter().map(|l| l.to_string()).collect();
+        assert_eq!(cramped[1], "Cr… │ Ver… │ …");
+        assert_eq!(cramped[2], "────┼──────┼──");
+        assert_eq!(cramped[3], "se… │ 1.0  │ …");
+    }
+
+    #[test]
+    fn test_raw_mode_skips_markdown_styling() {
+        let text = "Use **bold** for `names`.\n  indented";
+
+        let rendered = render_markdown_lines(text, 80);
+        let bold = rendered[0].spans.iter().find(|s| s.content == "bold").unwrap();
+        assert!(bold.style.add_modifier.contains(Modifier::BOLD));
+
+        let raw = render_raw_lines(text, 80);
+        assert_eq!(raw.len(), 2);
+        assert_eq!(raw[0].to_string(), "Use **bold** for `names`.");
+        assert_eq!(raw[1].to_string(), "  indented");
+        assert!(raw.iter().flat_map(|l| &l.spans).all(|s| s.style == Style::default()));
+    }
 }
@@ -1,8 +1,34 @@
 use crate::api::{GrokClient, Message};
 use crate::app::AppEvent;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::{mpsc, Arc};
 
+/// Models used by `/megamind`: cheap, fast ones suit the brainstorming agents,
+/// while the synthesis step may be worth a stronger reasoning model
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct MegamindConfig {
+    /// Model for every agent; when unset each agent uses its built-in model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_model: Option<String>,
+    /// Model that turns the agents' ideas into the final actionable points
+    pub synthesis_model: String,
+}
+
+impl Default for MegamindConfig {
+    fn default() -> Self {
+        MegamindConfig { agent_model: None, synthesis_model: "grok-3-mini".to_string() }
+    }
+}
+
+impl MegamindConfig {
+    /// Model the given agent is asked with
+    pub fn agent_model<'a>(&'a self, agent: &'a MegamindAgent) -> &'a str {
+        self.agent_model.as_deref().filter(|m| !m.trim().is_empty()).unwrap_or(agent.model)
+    }
+}
+
 /// Megamind agent definition
 pub struct MegamindAgent {
     pub name: &'static str,
@@ -84,6 +110,7 @@ impl MegamindSession {
 pub async fn run_megamind(
     client: Arc<GrokClient>,
     topic: String,
+    models: MegamindConfig,
     tx: mpsc::Sender<AppEvent>,
 ) {
     let mut session = MegamindSession::new(topic);
@@ -120,7 +147,7 @@ pub async fn run_megamind(
 
             // Call API with agent's model (no tools for brainstorming)
             match client
-                .chat_completion_stream_with_model(messages, vec![], agent.model)
+                .chat_completion_stream_with_model(messages, vec![], models.agent_model(agent))
                 .await
             {
                 Ok(mut response) => {
@@ -200,17 +227,13 @@ pub async fn run_megamind(
 
     // Synthesize final result
     let _ = tx.send(AppEvent::StatusUpdate("Megamind: Synthesizing...".to_string()));
-    synthesize_megamind(&client, &session, &tx).await;
+    synthesize_megamind(&client, &session, &models, &tx).await;
 
     let _ = tx.send(AppEvent::Finished);
 }
 
-/// Synthesize all ideas into actionable points
-async fn synthesize_megamind(
-    client: &Arc<GrokClient>,
-    session: &MegamindSession,
-    tx: &mpsc::Sender<AppEvent>,
-) {
+/// Messages and model for the synthesis call
+fn synthesis_request(session: &MegamindSession, models: &MegamindConfig) -> (Vec<Message>, String) {
     let synthesis_prompt =
         "Synthesize the brainstorming into 3-5 actionable points. Be brief and practical.";
 
@@ -236,10 +259,19 @@ async fn synthesize_megamind(
             tool_call_id: None,
         },
     ];
+    (messages, models.synthesis_model.clone())
+}
 
-    // Use fast model for synthesis
+/// Synthesize all ideas into actionable points
+async fn synthesize_megamind(
+    client: &Arc<GrokClient>,
+    session: &MegamindSession,
+    models: &MegamindConfig,
+    tx: &mpsc::Sender<AppEvent>,
+) {
+    let (messages, model) = synthesis_request(session, models);
     match client
-        .chat_completion_stream_with_model(messages, vec![], "grok-3-mini")
+        .chat_completion_stream_with_model(messages, vec![], &model)
         .await
     {
         Ok(mut response) => {
@@ -294,3 +326,29 @@ async fn synthesize_megamind(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthesis_uses_configured_model() {
+        let mut session = MegamindSession::new("Cache invalidation".to_string());
+        session.agent_responses.push(("Pragmatist".to_string(), "Use TTLs".to_string()));
+        session.agent_responses.push(("Critic".to_string(), "Stale reads".to_string()));
+
+        let models = MegamindConfig {
+            agent_model: Some("grok-4-1-fast-non-reasoning".to_string()),
+            synthesis_model: "grok-4-1-fast-reasoning".to_string(),
+        };
+        let (messages, model) = synthesis_request(&session, &models);
+        assert_eq!(model, "grok-4-1-fast-reasoning");
+        assert_eq!(messages[1].content.as_deref(), Some("TOPIC: Cache invalidation\n\nIDEAS:\n[P] Use TTLs\n\n[C] Stale reads"));
+        assert_eq!(models.agent_model(&AGENTS[0]), "grok-4-1-fast-non-reasoning");
+
+        // Defaults keep the previous behavior: the agents' own model, grok-3-mini to synthesize
+        let defaults = MegamindConfig::default();
+        assert_eq!(synthesis_request(&session, &defaults).1, "grok-3-mini");
+        assert_eq!(defaults.agent_model(&AGENTS[2]), AGENTS[2].model);
+    }
+}
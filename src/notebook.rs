@@ -0,0 +1,160 @@
+//! Jupyter notebook (.ipynb) support for Read and EditCell
+//!
+//! Renders cells as numbered source plus outputs instead of raw JSON, and
+//! rewrites a single cell's source while leaving the rest of the document intact.
+
+use serde_json::Value;
+use std::path::Path;
+
+/// Outputs longer than this are cut short when rendering
+const MAX_OUTPUT_LINES: usize = 20;
+
+pub fn is_notebook(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ipynb"))
+}
+
+/// Notebook text fields are either a string or a list of line strings
+fn join_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(parts) => parts.iter().filter_map(|p| p.as_str()).collect(),
+        _ => String::new(),
+    }
+}
+
+fn parse_cells(content: &str) -> Result<(Value, usize), String> {
+    let notebook: Value = serde_json::from_str(content)
+        .map_err(|e| format!("Error: invalid notebook JSON: {}", e))?;
+    let count = notebook["cells"]
+        .as_array()
+        .map(|cells| cells.len())
+        .ok_or_else(|| "Error: notebook has no cells array".to_string())?;
+    Ok((notebook, count))
+}
+
+fn render_output(output: &Value) -> String {
+    match output["output_type"].as_str().unwrap_or("") {
+        "stream" => join_text(&output["text"]),
+        "execute_result" | "display_data" => {
+            let data = &output["data"];
+            if !data["text/plain"].is_null() {
+                join_text(&data["text/plain"])
+            } else if let Some(obj) = data.as_object() {
+                let kinds: Vec<&str> = obj.keys().map(|k| k.as_str()).collect();
+                format!("[{} output]", kinds.join(", "))
+            } else {
+                String::new()
+            }
+        }
+        "error" => format!(
+            "{}: {}",
+            output["ename"].as_str().unwrap_or("Error"),
+            output["evalue"].as_str().unwrap_or("")
+        ),
+        other => format!("[{} output]", other),
+    }
+}
+
+/// Render a notebook as readable cells (numbered source lines, then outputs)
+pub fn render(content: &str) -> Result<String, String> {
+    let (notebook, count) = parse_cells(content)?;
+    if count == 0 {
+        return Ok("(empty notebook)".to_string());
+    }
+
+    let mut result = String::new();
+    for (idx, cell) in notebook["cells"].as_array().into_iter().flatten().enumerate() {
+        let cell_type = cell["cell_type"].as_str().unwrap_or("unknown");
+        match cell["execution_count"].as_u64() {
+            Some(n) => result.push_str(&format!("[cell {}] {} (execution_count {})\n", idx, cell_type, n)),
+            None => result.push_str(&format!("[cell {}] {}\n", idx, cell_type)),
+        }
+
+        let source = join_text(&cell["source"]);
+        for (line_idx, line) in source.lines().enumerate() {
+            result.push_str(&format!("{:>4}\t{}\n", line_idx + 1, line));
+        }
+
+        for output in cell["outputs"].as_array().into_iter().flatten() {
+            let text = render_output(output);
+            let lines: Vec<&str> = text.lines().collect();
+            result.push_str("  --- output ---\n");
+            for line in lines.iter().take(MAX_OUTPUT_LINES) {
+                result.push_str(&format!("  {}\n", line));
+            }
+            if lines.len() > MAX_OUTPUT_LINES {
+                result.push_str(&format!("  ... {} more lines\n", lines.len() - MAX_OUTPUT_LINES));
+            }
+        }
+        result.push('\n');
+    }
+
+    result.push_str(&format!("[{} cells. Use EditCell with cell_index to change a cell's source.]", count));
+    Ok(result)
+}
+
+/// Replace the source of cell `index`, returning the updated notebook JSON.
+/// Output uses nbformat's layout (sorted keys, one-space indent, trailing newline).
+pub fn set_cell_source(content: &str, index: usize, new_source: &str) -> Result<String, String> {
+    let (mut notebook, count) = parse_cells(content)?;
+    if index >= count {
+        return Err(format!("Error: cell_index {} out of range (notebook has {} cells)", index, count));
+    }
+
+    // nbformat stores source as a list of lines, each keeping its trailing newline
+    let lines: Vec<Value> = new_source
+        .split_inclusive('\n')
+        .map(|line| Value::String(line.to_string()))
+        .collect();
+    notebook["cells"][index]["source"] = Value::Array(lines);
+
+    let mut buf = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b" ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buf, formatter);
+    serde::Serialize::serialize(&notebook, &mut serializer)
+        .map_err(|e| format!("Error: could not serialize notebook: {}", e))?;
+    let mut updated = String::from_utf8(buf).map_err(|e| format!("Error: {}", e))?;
+    updated.push('\n');
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+ "cells": [
+  {"cell_type": "markdown", "metadata": {}, "source": ["# Title\n", "Intro"]},
+  {"cell_type": "code", "execution_count": 2, "metadata": {"tags": ["keep"]},
+   "outputs": [{"name": "stdout", "output_type": "stream", "text": ["3\n"]}],
+   "source": ["x = 1\n", "print(x + 2)"]}
+ ],
+ "metadata": {"kernelspec": {"name": "python3"}},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"##;
+
+    #[test]
+    fn test_render_cells() {
+        let rendered = render(NOTEBOOK).unwrap();
+        assert!(rendered.contains("[cell 0] markdown\n   1\t# Title\n   2\tIntro\n"));
+        assert!(rendered.contains("[cell 1] code (execution_count 2)\n   1\tx = 1\n   2\tprint(x + 2)\n  --- output ---\n  3\n"));
+        assert!(rendered.ends_with("[2 cells. Use EditCell with cell_index to change a cell's source.]"));
+    }
+
+    #[test]
+    fn test_set_cell_source_preserves_structure() {
+        let updated = set_cell_source(NOTEBOOK, 1, "y = 2\nprint(y)").unwrap();
+        let notebook: Value = serde_json::from_str(&updated).unwrap();
+
+        assert_eq!(notebook["cells"][1]["source"], serde_json::json!(["y = 2\n", "print(y)"]));
+        assert_eq!(notebook["cells"][0]["source"], serde_json::json!(["# Title\n", "Intro"]));
+        assert_eq!(notebook["cells"][1]["metadata"]["tags"][0], "keep");
+        assert_eq!(notebook["cells"][1]["outputs"][0]["text"][0], "3\n");
+        assert_eq!(notebook["nbformat"], 4);
+
+        assert!(set_cell_source(NOTEBOOK, 5, "z").unwrap_err().contains("out of range"));
+    }
+}
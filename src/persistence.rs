@@ -1,10 +1,102 @@
 use crate::api::Message;
+use crate::tools::ToolCall;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
+use std::time::SystemTime;
 
 pub const DEFAULT_HISTORY_FILE: &str = ".grok_history.json";
 pub const DEFAULT_CONTEXT_FILE: &str = ".grok_context.json";
+pub const DEFAULT_SESSION_FILE: &str = ".grok_session.json";
+pub const SESSIONS_DIR: &str = ".grok_sessions";
+pub const DEFAULT_DRAFT_FILE: &str = ".grok_draft.txt";
+
+/// A named session saved with `/save` and restored with `/load`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NamedSession {
+    pub messages: Vec<Message>,
+    pub api_messages: Vec<Message>,
+}
+
+/// Runtime mode toggles restored with `--resume` (config stays the default for fresh sessions)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct SessionModes {
+    pub sandbox_enabled: bool,
+    pub converse_mode: bool,
+    pub debug_mode: bool,
+    /// Where the user was reading when the session was last saved
+    pub scroll: ScrollPosition,
+    /// Working directory the session was started in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_cwd: Option<String>,
+    /// Command approval that was on screen, so a crash doesn't lose the turn
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_approval: Option<PendingApproval>,
+}
+
+/// A tool call waiting for the user to approve its command
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PendingApproval {
+    pub tool_call: ToolCall,
+    pub command: String,
+}
+
+impl PendingApproval {
+    /// Whether `history` still ends in this call with no result for it; if the
+    /// answer was saved before the app went down there is nothing to re-ask
+    pub fn is_unanswered(&self, history: &[Message]) -> bool {
+        let id = &self.tool_call.id;
+        let requested = history.iter().any(|m| {
+            m.tool_calls.as_ref().is_some_and(|calls| calls.iter().any(|tc| &tc.id == id))
+        });
+        let answered = history.iter().any(|m| m.role == "tool" && m.tool_call_id.as_ref() == Some(id));
+        requested && !answered
+    }
+}
+
+/// Warning for resuming a session somewhere other than where it started, since
+/// the sandbox and every relative path in the conversation would point elsewhere
+pub fn cwd_warning(session_cwd: Option<&str>, current: &str) -> Option<String> {
+    let session_cwd = session_cwd?;
+    let canonical = |p: &str| fs::canonicalize(p).unwrap_or_else(|_| Path::new(p).to_path_buf());
+    if canonical(session_cwd) == canonical(current) {
+        return None;
+    }
+    Some(format!(
+        "This session was started in {} but the working directory is now {}; relative paths and the sandbox now refer to the new directory",
+        session_cwd, current
+    ))
+}
+
+/// Selected message and auto-scroll state of the message list
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct ScrollPosition {
+    pub selected: Option<usize>,
+    pub auto_scroll: bool,
+}
+
+impl Default for ScrollPosition {
+    fn default() -> Self {
+        ScrollPosition { selected: None, auto_scroll: true }
+    }
+}
+
+impl ScrollPosition {
+    /// Fit the position to `message_count` messages (the history may have
+    /// changed since it was saved): out-of-range selections go to the last message
+    pub fn clamped(self, message_count: usize) -> ScrollPosition {
+        if message_count == 0 {
+            return ScrollPosition::default();
+        }
+        ScrollPosition {
+            selected: self.selected.map(|i| i.min(message_count - 1)),
+            auto_scroll: self.auto_scroll,
+        }
+    }
+}
 
 pub fn save_history(messages: &[Message], path: &str) -> Result<()> {
     let json = serde_json::to_string_pretty(messages)?;
@@ -12,6 +104,65 @@ pub fn save_history(messages: &[Message], path: &str) -> Result<()> {
     Ok(())
 }
 
+/// Save the unsent input; an empty draft removes the file
+pub fn save_draft(draft: &str, path: &str) -> Result<()> {
+    if draft.is_empty() {
+        if Path::new(path).exists() {
+            fs::remove_file(path)?;
+        }
+        return Ok(());
+    }
+    fs::write(path, draft)?;
+    Ok(())
+}
+
+/// The input left unsent when the app last went down, if any
+pub fn load_draft(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().filter(|draft| !draft.trim().is_empty())
+}
+
+/// Decides when the event loop saves the session in the background: at most
+/// once per interval, and only if something changed since the last save
+#[derive(Debug, Clone)]
+pub struct AutoSaveTimer {
+    interval: Option<std::time::Duration>,
+    last_check: std::time::Instant,
+    saved: Option<u64>,
+}
+
+impl AutoSaveTimer {
+    /// `interval_secs` of 0 disables auto-save. `saved` fingerprints the state
+    /// already on disk, so a session nobody touches is never rewritten
+    pub fn new(interval_secs: u64, now: std::time::Instant, saved: u64) -> Self {
+        AutoSaveTimer {
+            interval: (interval_secs > 0).then(|| std::time::Duration::from_secs(interval_secs)),
+            last_check: now,
+            saved: Some(saved),
+        }
+    }
+
+    /// Record that the state with this fingerprint is on disk now
+    pub fn mark_saved(&mut self, fingerprint: u64) {
+        self.saved = Some(fingerprint);
+    }
+
+    /// Whether to save now. `fingerprint` summarizes the state to save and is
+    /// only computed once the interval has passed.
+    pub fn should_save(&mut self, now: std::time::Instant, fingerprint: impl FnOnce() -> u64) -> bool {
+        let Some(interval) = self.interval else { return false };
+        if now.duration_since(self.last_check) < interval {
+            return false;
+        }
+        self.last_check = now;
+        let current = fingerprint();
+        if self.saved == Some(current) {
+            return false;
+        }
+        self.saved = Some(current);
+        true
+    }
+}
+
 pub fn load_history(path: &str) -> Result<Vec<Message>> {
     if !Path::new(path).exists() {
         return Ok(Vec::new());
@@ -38,11 +189,226 @@ pub fn load_context(path: &str) -> Result<Vec<Message>> {
     Ok(messages)
 }
 
+/// Save the session's mode toggles
+pub fn save_session_modes(modes: &SessionModes, path: &str) -> Result<()> {
+    let json = serde_json::to_string_pretty(modes)?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+/// Load the session's mode toggles, if a session file exists
+pub fn load_session_modes(path: &str) -> Result<Option<SessionModes>> {
+    if !Path::new(path).exists() {
+        return Ok(None);
+    }
+    let json = fs::read_to_string(path)?;
+    let modes: SessionModes = serde_json::from_str(&json)?;
+    Ok(Some(modes))
+}
+
+fn session_path(name: &str, dir: &str) -> std::path::PathBuf {
+    Path::new(dir).join(format!("{}.json", name))
+}
+
+/// Session names may only use characters that are safe in a file name
+pub fn is_valid_session_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !name.starts_with('.')
+}
+
+/// Save a named session under `dir`
+pub fn save_named_session(name: &str, session: &NamedSession, dir: &str) -> Result<()> {
+    if !is_valid_session_name(name) {
+        anyhow::bail!("invalid session name '{}' (use letters, digits, '-', '_' or '.')", name);
+    }
+    fs::create_dir_all(dir)?;
+    let json = serde_json::to_string_pretty(session)?;
+    fs::write(session_path(name, dir), json)?;
+    Ok(())
+}
+
+/// Load a named session from `dir`
+pub fn load_named_session(name: &str, dir: &str) -> Result<NamedSession> {
+    if !is_valid_session_name(name) {
+        anyhow::bail!("invalid session name '{}'", name);
+    }
+    let path = session_path(name, dir);
+    if !path.exists() {
+        anyhow::bail!("no session named '{}'", name);
+    }
+    let json = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// A saved session and when it was last written
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionInfo {
+    pub name: String,
+    pub modified: SystemTime,
+}
+
+/// Metadata for the sessions saved under `dir`, sorted by name
+pub fn session_metadata(dir: &str) -> Vec<SessionInfo> {
+    let mut sessions: Vec<SessionInfo> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| {
+                    let path = e.path();
+                    if path.extension().is_none_or(|ext| ext != "json") {
+                        return None;
+                    }
+                    let name = path.file_stem()?.to_string_lossy().to_string();
+                    let modified = e.metadata().and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
+                    Some(SessionInfo { name, modified })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    sessions.sort_by(|a, b| a.name.cmp(&b.name));
+    sessions
+}
+
+/// Names of the sessions saved under `dir`, sorted
+pub fn list_sessions(dir: &str) -> Vec<String> {
+    session_metadata(dir).into_iter().map(|s| s.name).collect()
+}
+
+/// The most recently modified session under `dir` (used by `--continue`)
+pub fn most_recent_session(dir: &str) -> Option<String> {
+    session_metadata(dir)
+        .into_iter()
+        .max_by_key(|s| s.modified)
+        .map(|s| s.name)
+}
+
+/// A message in a saved session that mentions a search term
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionMatch {
+    pub session: String,
+    pub role: String,
+    pub snippet: String,
+}
+
+/// Case-insensitive search of the user and assistant messages in the sessions
+/// saved under `dir`, most recently saved session first, up to `limit` matches
+pub fn search_sessions(dir: &str, term: &str, limit: usize) -> Vec<SessionMatch> {
+    let needle: Vec<char> = term.trim().chars().flat_map(char::to_lowercase).collect();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    let mut sessions = session_metadata(dir);
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.modified));
+
+    let mut matches = Vec::new();
+    for info in sessions {
+        let Ok(session) = load_named_session(&info.name, dir) else { continue };
+        for msg in &session.messages {
+            if msg.role != "user" && msg.role != "assistant" {
+                continue;
+            }
+            let Some(snippet) = msg.content.as_deref().and_then(|c| match_snippet(c, &needle)) else { continue };
+            matches.push(SessionMatch { session: info.name.clone(), role: msg.role.clone(), snippet });
+            if matches.len() >= limit {
+                return matches;
+            }
+        }
+    }
+    matches
+}
+
+/// About 100 characters of `content` around the first occurrence of `needle`
+/// (already lowercased), on one line
+fn match_snippet(content: &str, needle: &[char]) -> Option<String> {
+    const CONTEXT: usize = 40;
+    let text: Vec<char> = content.split_whitespace().collect::<Vec<_>>().join(" ").chars().collect();
+    let lower: Vec<char> = text.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let at = lower.windows(needle.len()).position(|w| w == needle)?;
+    let start = at.saturating_sub(CONTEXT);
+    let end = (at + needle.len() + CONTEXT).min(text.len());
+    let mut snippet: String = text[start..end].iter().collect();
+    if start > 0 {
+        snippet.insert(0, '…');
+    }
+    if end < text.len() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
+
+/// Search results as shown by `/history search` and the SearchHistory tool
+pub fn format_session_matches(term: &str, matches: &[SessionMatch]) -> String {
+    if matches.is_empty() {
+        return format!("No saved sessions mention '{}'", term);
+    }
+    let mut out = format!("{} matches for '{}' in saved sessions:", matches.len(), term);
+    for m in matches {
+        out.push_str(&format!("\n  [{}] {}: {}", m.session, m.role, m.snippet));
+    }
+    out
+}
+
+/// Write a readable Markdown transcript of the conversation
+pub fn export_transcript(messages: &[Message], path: &str) -> Result<()> {
+    let mut out = String::from("# Grok CLI transcript\n");
+    for msg in messages {
+        let Some(content) = msg.content.as_deref().filter(|c| !c.trim().is_empty()) else { continue };
+        match msg.role.as_str() {
+            "user" => out.push_str(&format!("\n## User\n\n{}\n", content)),
+            "assistant" => out.push_str(&format!("\n## Assistant\n\n{}\n", content)),
+            "tool" => out.push_str(&format!("\n### Tool result\n\n```\n{}\n```\n", content)),
+            _ => {}
+        }
+    }
+    if let Some(parent) = Path::new(path).parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    fs::write(path, out)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::NamedTempFile;
 
+    #[test]
+    fn test_autosave_only_when_due_and_dirty() {
+        let start = std::time::Instant::now();
+        let secs = |n: u64| start + std::time::Duration::from_secs(n);
+        let mut timer = AutoSaveTimer::new(30, start, 0);
+
+        // Not before the interval has passed, and the state isn't even looked at
+        assert!(!timer.should_save(secs(29), || panic!("fingerprint computed early")));
+        // Nothing changed since startup
+        assert!(!timer.should_save(secs(30), || 0));
+        assert!(timer.should_save(secs(60), || 1));
+        // Nothing changed since the last save
+        assert!(!timer.should_save(secs(90), || 1));
+        // Changed, but wait for the next interval
+        assert!(!timer.should_save(secs(91), || 2));
+        assert!(timer.should_save(secs(120), || 2));
+
+        let mut disabled = AutoSaveTimer::new(0, start, 0);
+        assert!(!disabled.should_save(secs(3600), || 1));
+    }
+
+    #[test]
+    fn test_empty_draft_removes_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("draft.txt");
+        let path = path.to_str().unwrap();
+        save_draft("half a thought", path).unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), "half a thought");
+        save_draft("", path).unwrap();
+        assert!(!Path::new(path).exists());
+        save_draft("", path).unwrap();
+        assert_eq!(load_draft(path), None);
+    }
+
     #[test]
     fn test_save_and_load_history() {
         let file = NamedTempFile::new().unwrap();
@@ -63,5 +429,148 @@ mod tests {
         assert_eq!(loaded.len(), 1);
         assert_eq!(loaded[0].content.as_ref().unwrap(), "hello");
     }
-}
 
+    #[test]
+    fn test_session_modes_round_trip() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let modes = SessionModes {
+            sandbox_enabled: true,
+            converse_mode: true,
+            debug_mode: false,
+            scroll: ScrollPosition::default(),
+            session_cwd: Some("/work/project".to_string()),
+            pending_approval: None,
+        };
+
+        save_session_modes(&modes, path).unwrap();
+        assert_eq!(load_session_modes(path).unwrap(), Some(modes));
+
+        // No session file means nothing to restore
+        assert_eq!(load_session_modes("/nonexistent/.grok_session.json").unwrap(), None);
+    }
+
+    #[test]
+    fn test_resume_in_other_directory_warns() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        let started = tempfile::tempdir().unwrap();
+        let elsewhere = tempfile::tempdir().unwrap();
+        let started_path = started.path().to_str().unwrap();
+
+        let modes = SessionModes { session_cwd: Some(started_path.to_string()), ..SessionModes::default() };
+        save_session_modes(&modes, path).unwrap();
+        let session_cwd = load_session_modes(path).unwrap().unwrap().session_cwd;
+
+        let warning = cwd_warning(session_cwd.as_deref(), elsewhere.path().to_str().unwrap()).unwrap();
+        assert!(warning.contains(started_path));
+        assert!(warning.contains(elsewhere.path().to_str().unwrap()));
+
+        // Same directory, even spelled differently: no warning
+        assert_eq!(cwd_warning(session_cwd.as_deref(), started_path), None);
+        assert_eq!(cwd_warning(session_cwd.as_deref(), &format!("{}/.", started_path)), None);
+        // Sessions saved before the cwd was recorded can't be checked
+        assert_eq!(cwd_warning(None, started_path), None);
+    }
+
+    #[test]
+    fn test_scroll_position_round_trip_and_clamp() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let modes = SessionModes {
+            scroll: ScrollPosition { selected: Some(12), auto_scroll: false },
+            ..SessionModes::default()
+        };
+        save_session_modes(&modes, path).unwrap();
+        let scroll = load_session_modes(path).unwrap().unwrap().scroll;
+        assert_eq!(scroll, ScrollPosition { selected: Some(12), auto_scroll: false });
+
+        // Still in range: unchanged
+        assert_eq!(scroll.clamped(20), scroll);
+        // History shrank: the last message instead
+        assert_eq!(scroll.clamped(5), ScrollPosition { selected: Some(4), auto_scroll: false });
+        // Nothing to select
+        assert_eq!(scroll.clamped(0), ScrollPosition::default());
+
+        // Session files written before scroll was saved start at the bottom
+        std::fs::write(path, r#"{"sandbox_enabled": true}"#).unwrap();
+        assert_eq!(load_session_modes(path).unwrap().unwrap().scroll, ScrollPosition::default());
+    }
+
+    #[test]
+    fn test_named_sessions_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().to_str().unwrap();
+        let msg = Message { role: "user".to_string(), content: Some("hi".to_string()), tool_calls: None, tool_call_id: None };
+        let session = NamedSession { messages: vec![msg.clone()], api_messages: vec![msg] };
+
+        save_named_session("beta", &session, dir).unwrap();
+        save_named_session("alpha", &session, dir).unwrap();
+        assert!(save_named_session("../escape", &session, dir).is_err());
+
+        assert_eq!(list_sessions(dir), vec!["alpha", "beta"]);
+        assert_eq!(load_named_session("alpha", dir).unwrap().messages[0].content.as_deref(), Some("hi"));
+        assert!(load_named_session("missing", dir).is_err());
+    }
+
+    #[test]
+    fn test_most_recent_session_by_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir_str = dir.path().to_str().unwrap();
+        let session = NamedSession { messages: Vec::new(), api_messages: Vec::new() };
+        assert_eq!(most_recent_session(dir_str), None);
+
+        // Alphabetical order and mtime order disagree on purpose
+        let now = SystemTime::now();
+        for (name, age_secs) in [("alpha", 60), ("middle", 10), ("zulu", 3600)] {
+            save_named_session(name, &session, dir_str).unwrap();
+            let file = fs::File::options().write(true).open(dir.path().join(format!("{}.json", name))).unwrap();
+            file.set_modified(now - std::time::Duration::from_secs(age_secs)).unwrap();
+        }
+
+        assert_eq!(most_recent_session(dir_str).as_deref(), Some("middle"));
+        assert_eq!(list_sessions(dir_str), vec!["alpha", "middle", "zulu"]);
+    }
+
+    #[test]
+    fn test_search_sessions_finds_term_with_session_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().to_str().unwrap();
+        let msg = crate::app::tests::msg;
+        let auth = NamedSession {
+            messages: vec![
+                msg("user", "How should we store the tokens?"),
+                msg("assistant", "We decided to keep refresh tokens in the OS keychain, not on disk."),
+                msg("tool", "keychain.rs: 40 lines"),
+            ],
+            api_messages: Vec::new(),
+        };
+        let parser = NamedSession {
+            messages: vec![msg("user", "Rewrite the parser with nom")],
+            api_messages: Vec::new(),
+        };
+        save_named_session("auth-design", &auth, dir).unwrap();
+        save_named_session("parser", &parser, dir).unwrap();
+
+        let matches = search_sessions(dir, "KEYCHAIN", 10);
+        assert_eq!(matches.len(), 1, "tool output isn't searched: {:?}", matches);
+        assert_eq!(matches[0].session, "auth-design");
+        assert_eq!(matches[0].role, "assistant");
+        assert!(matches[0].snippet.contains("OS keychain"));
+
+        let text = format_session_matches("nom", &search_sessions(dir, "nom", 10));
+        assert!(text.contains("[parser] user: Rewrite the parser with nom"), "{}", text);
+        assert_eq!(format_session_matches("graphql", &search_sessions(dir, "graphql", 10)), "No saved sessions mention 'graphql'");
+
+        // Long messages are cut down around the match
+        let long = NamedSession {
+            messages: vec![msg("user", &format!("{} needle {}", "a ".repeat(200), "b ".repeat(200)))],
+            api_messages: Vec::new(),
+        };
+        save_named_session("long", &long, dir).unwrap();
+        let snippet = &search_sessions(dir, "needle", 10)[0].snippet;
+        assert!(snippet.starts_with('…') && snippet.ends_with('…') && snippet.chars().count() < 100, "{}", snippet);
+    }
+}
@@ -0,0 +1,157 @@
+//! Project-specific part of the system prompt
+//!
+//! The project type is detected from marker files in the working directory or,
+//! failing that, the git root, and turned into a short hint with the usual
+//! build/test commands. A GROK.md in either place replaces the hint entirely.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Project instructions file that overrides the detected hint
+pub const PROJECT_INSTRUCTIONS_FILE: &str = "GROK.md";
+
+/// A project type, recognised by a marker file in its directory
+pub struct ProjectKind {
+    pub marker: &'static str,
+    pub name: &'static str,
+    /// Build/test hint for the system prompt
+    pub hint: Option<&'static str>,
+    /// What Diagnostics runs when diagnostics.command isn't set
+    pub check: Option<&'static str>,
+}
+
+/// Every project type we recognise. Where several match, the first with a
+/// hint or check command wins, so tsconfig.json comes before package.json
+pub const PROJECT_KINDS: &[ProjectKind] = &[
+    ProjectKind {
+        marker: "Cargo.toml",
+        name: "Rust",
+        hint: Some(
+            "Rust project (Cargo). Build with `cargo build`, test with `cargo test`, lint with `cargo clippy`. \
+Format with `cargo fmt`; prefer `Result` and `?` over `unwrap()` outside tests.",
+        ),
+        check: Some("cargo check --message-format=json"),
+    },
+    ProjectKind { marker: "tsconfig.json", name: "TypeScript", hint: None, check: Some("npx tsc --noEmit --pretty false") },
+    ProjectKind {
+        marker: "package.json",
+        name: "Node.js",
+        hint: Some(
+            "Node.js project. Check package.json scripts first; usually `npm install`, `npm run build` and `npm test` \
+(use yarn or pnpm instead if their lockfile is present). Follow the existing module style (ESM vs CommonJS).",
+        ),
+        check: None,
+    },
+    ProjectKind {
+        marker: "pyproject.toml",
+        name: "Python",
+        hint: Some(
+            "Python project (pyproject.toml). Test with `pytest`, lint with `ruff check`; \
+run tools inside the project's virtualenv if there is one. Follow PEP 8 and keep type hints.",
+        ),
+        check: Some("ruff check --output-format=concise"),
+    },
+    ProjectKind { marker: "ruff.toml", name: "Python", hint: None, check: Some("ruff check --output-format=concise") },
+    ProjectKind { marker: "requirements.txt", name: "Python", hint: None, check: None },
+    ProjectKind {
+        marker: "go.mod",
+        name: "Go",
+        hint: Some(
+            "Go module. Build with `go build ./...`, test with `go test ./...`, check with `go vet ./...`; \
+format with `gofmt`. Return errors rather than panicking.",
+        ),
+        check: Some("go vet ./..."),
+    },
+    ProjectKind { marker: "pom.xml", name: "Java (Maven)", hint: None, check: None },
+    ProjectKind { marker: "build.gradle", name: "Java (Gradle)", hint: None, check: None },
+    ProjectKind { marker: "Gemfile", name: "Ruby", hint: None, check: None },
+    ProjectKind { marker: "CMakeLists.txt", name: "C/C++ (CMake)", hint: None, check: None },
+    ProjectKind { marker: "Makefile", name: "Make", hint: None, check: None },
+];
+
+/// Project types whose marker is in `dir` itself, in table order
+pub fn detect(dir: &Path) -> impl Iterator<Item = &'static ProjectKind> + '_ {
+    PROJECT_KINDS.iter().filter(move |kind| dir.join(kind.marker).exists())
+}
+
+/// Nearest ancestor of `dir` (itself included) containing `.git`
+pub fn git_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().find(|d| d.join(".git").exists()).map(Path::to_path_buf)
+}
+
+/// Where to look for markers and GROK.md: `dir`, then its git root
+fn search_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![dir.to_path_buf()];
+    if let Some(root) = git_root(dir).filter(|root| root != dir) {
+        dirs.push(root);
+    }
+    dirs
+}
+
+/// Build/test hint for the project in `dir`, if its type is recognised
+pub fn project_hint(dir: &Path) -> Option<&'static str> {
+    search_dirs(dir).iter().find_map(|d| {
+        detect(d).find_map(|kind| kind.hint)
+    })
+}
+
+/// Contents of GROK.md in `dir` or its git root
+pub fn project_instructions(dir: &Path) -> Option<String> {
+    search_dirs(dir).iter().find_map(|d| {
+        fs::read_to_string(d.join(PROJECT_INSTRUCTIONS_FILE)).ok().filter(|s| !s.trim().is_empty())
+    })
+}
+
+/// System prompt section for the project in `dir` (empty if there's nothing to say)
+pub fn project_section(dir: &Path) -> String {
+    match (project_instructions(dir), project_hint(dir)) {
+        (Some(instructions), _) => format!("\n# Project Instructions ({})\n{}\n", PROJECT_INSTRUCTIONS_FILE, instructions.trim()),
+        (None, Some(hint)) => format!("\n# Project\n{}\n", hint),
+        (None, None) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cargo_project_gets_rust_hint() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+
+        let section = project_section(dir.path());
+        assert!(section.contains("Rust project"));
+        assert!(section.contains("`cargo build`"));
+        assert!(section.contains("`cargo test`"));
+
+        // Markers at the git root count from a subdirectory
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        assert!(project_section(&dir.path().join("src")).contains("`cargo test`"));
+
+        // GROK.md replaces the detected hint
+        fs::write(dir.path().join(PROJECT_INSTRUCTIONS_FILE), "Run `make check` before committing.\n").unwrap();
+        let section = project_section(&dir.path().join("src"));
+        assert!(section.contains("Run `make check` before committing."));
+        assert!(!section.contains("cargo build"));
+
+        let empty = tempfile::tempdir().unwrap();
+        assert_eq!(project_section(empty.path()), "");
+    }
+
+    #[test]
+    fn test_one_table_drives_hint_check_and_env_info() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("package.json"), "{}").unwrap();
+        fs::write(dir.path().join("tsconfig.json"), "{}").unwrap();
+
+        let kinds: Vec<&str> = detect(dir.path()).map(|kind| kind.name).collect();
+        assert_eq!(kinds, ["TypeScript", "Node.js"]);
+        assert!(project_hint(dir.path()).unwrap().starts_with("Node.js project"));
+        assert_eq!(crate::diagnostics::detect_command(dir.path()).as_deref(), Some("npx tsc --noEmit --pretty false"));
+
+        let result = crate::tools::execute_tool("EnvInfo", "{}", dir.path().to_str());
+        assert!(result.contains("Project: TypeScript (tsconfig.json), Node.js (package.json)"), "{}", result);
+    }
+}
@@ -7,6 +7,9 @@ pub struct Settings {
     /// Enable rate limiter - pauses execution when approaching API limits
     #[serde(default = "default_true")]
     pub rate_limiter_enabled: bool,
+    /// Capture mouse events - disable to use the terminal's native text selection
+    #[serde(default = "default_true")]
+    pub mouse_capture_enabled: bool,
 }
 
 fn default_true() -> bool {
@@ -17,6 +20,7 @@ impl Default for Settings {
     fn default() -> Self {
         Settings {
             rate_limiter_enabled: true,
+            mouse_capture_enabled: true,
         }
     }
 }
@@ -37,6 +41,11 @@ pub fn get_settings_info() -> Vec<SettingInfo> {
             name: "Rate Limiter",
             description: "Pauses execution when approaching the API context/min rate limit until it clears",
         },
+        SettingInfo {
+            key: "mouse_capture_enabled",
+            name: "Mouse Capture",
+            description: "Capture mouse events. Turn off (or press F2) to select and copy text natively",
+        },
     ]
 }
 
@@ -0,0 +1,70 @@
+//! User-facing interface text
+//!
+//! The input placeholders and the most common status messages live here so
+//! they can be overridden (and later translated) from `strings.json` next to
+//! the global config. Keys left out of that file keep their defaults.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const STRINGS_FILE: &str = "strings.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct UiStrings {
+    /// Input placeholder while chatting
+    pub placeholder: String,
+    /// Input placeholder after `/plan`, while waiting for the goal
+    pub plan_placeholder: String,
+    /// Status bar when idle
+    pub ready: String,
+    /// Status bar while waiting for the model
+    pub thinking: String,
+}
+
+impl Default for UiStrings {
+    fn default() -> Self {
+        UiStrings {
+            placeholder: "Type a message... ( / commands, @ roles )".to_string(),
+            plan_placeholder: "Describe your goal...".to_string(),
+            ready: "Ready".to_string(),
+            thinking: "Thinking...".to_string(),
+        }
+    }
+}
+
+/// `strings.json` in the global config directory
+pub fn strings_path() -> PathBuf {
+    crate::config::get_config_path().with_file_name(STRINGS_FILE)
+}
+
+/// Defaults with the overrides from `path` applied; a missing or unreadable
+/// file changes nothing
+pub fn load_from(path: &Path) -> UiStrings {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_override_replaces_default_placeholder() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STRINGS_FILE);
+        std::fs::write(&path, r#"{"placeholder": "Écrivez un message..."}"#).unwrap();
+
+        let strings = load_from(&path);
+        assert_eq!(strings.placeholder, "Écrivez un message...");
+        // Everything not overridden keeps its default
+        assert_eq!(strings.ready, UiStrings::default().ready);
+        assert_eq!(strings.plan_placeholder, "Describe your goal...");
+
+        assert_eq!(load_from(&dir.path().join("missing.json")), UiStrings::default());
+        std::fs::write(&path, "not json").unwrap();
+        assert_eq!(load_from(&path), UiStrings::default());
+    }
+}
@@ -0,0 +1,124 @@
+//! Terminal mode control
+//!
+//! Tracks whether mouse capture is active so it can be toggled at runtime
+//! (to allow native text selection) and torn down symmetrically on exit or panic.
+
+use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Global flag mirroring the real terminal state, read by the panic hook
+static MOUSE_CAPTURED: AtomicBool = AtomicBool::new(false);
+
+/// Anything that can switch mouse capture on and off (mockable in tests)
+pub trait MouseCaptureControl {
+    fn enable_mouse_capture(&mut self) -> io::Result<()>;
+    fn disable_mouse_capture(&mut self) -> io::Result<()>;
+}
+
+impl<W: io::Write> MouseCaptureControl for W {
+    fn enable_mouse_capture(&mut self) -> io::Result<()> {
+        execute!(self, EnableMouseCapture)
+    }
+
+    fn disable_mouse_capture(&mut self) -> io::Result<()> {
+        execute!(self, DisableMouseCapture)
+    }
+}
+
+/// Mouse capture state for the running session
+#[derive(Debug, Default)]
+pub struct MouseCapture {
+    enabled: bool,
+}
+
+impl MouseCapture {
+    /// Starts disabled - call `set` once the terminal is initialized
+    pub fn new() -> Self {
+        MouseCapture { enabled: false }
+    }
+
+    #[allow(dead_code)]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable or disable capture, only issuing a terminal command on change
+    pub fn set<C: MouseCaptureControl>(&mut self, enabled: bool, control: &mut C) -> io::Result<()> {
+        if enabled == self.enabled {
+            return Ok(());
+        }
+        if enabled {
+            control.enable_mouse_capture()?;
+        } else {
+            control.disable_mouse_capture()?;
+        }
+        self.enabled = enabled;
+        MOUSE_CAPTURED.store(enabled, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Flip the current state, returning the new one
+    pub fn toggle<C: MouseCaptureControl>(&mut self, control: &mut C) -> io::Result<bool> {
+        self.set(!self.enabled, control)?;
+        Ok(self.enabled)
+    }
+}
+
+/// Whether mouse capture is currently active (used by the panic hook)
+pub fn is_mouse_captured() -> bool {
+    MOUSE_CAPTURED.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct MockControl {
+        calls: Vec<&'static str>,
+    }
+
+    impl MouseCaptureControl for MockControl {
+        fn enable_mouse_capture(&mut self) -> io::Result<()> {
+            self.calls.push("enable");
+            Ok(())
+        }
+
+        fn disable_mouse_capture(&mut self) -> io::Result<()> {
+            self.calls.push("disable");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_toggle_issues_symmetric_calls() {
+        let mut control = MockControl::default();
+        let mut capture = MouseCapture::new();
+
+        capture.set(true, &mut control).unwrap();
+        assert!(capture.is_enabled());
+
+        assert!(!capture.toggle(&mut control).unwrap());
+        assert!(capture.toggle(&mut control).unwrap());
+
+        // Cleanup path disables exactly once
+        capture.set(false, &mut control).unwrap();
+        capture.set(false, &mut control).unwrap();
+
+        assert_eq!(control.calls, vec!["enable", "disable", "enable", "disable"]);
+        assert!(!capture.is_enabled());
+    }
+
+    #[test]
+    fn test_set_disabled_when_already_disabled_is_noop() {
+        let mut control = MockControl::default();
+        let mut capture = MouseCapture::new();
+
+        capture.set(false, &mut control).unwrap();
+        assert!(control.calls.is_empty());
+    }
+}
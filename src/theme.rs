@@ -0,0 +1,130 @@
+//! Color themes
+//!
+//! The UI is drawn with the dark palette; other themes remap those colors
+//! cell by cell after drawing, the same way `--no-color` strips them.
+
+use ratatui::style::{Color, Style};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    /// For terminals with a light background
+    Light,
+    /// Bright colors only, for low-vision use or washed-out displays
+    HighContrast,
+}
+
+impl ThemeName {
+    pub const ALL: [ThemeName; 3] = [ThemeName::Dark, ThemeName::Light, ThemeName::HighContrast];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThemeName::Dark => "dark",
+            ThemeName::Light => "light",
+            ThemeName::HighContrast => "high-contrast",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<ThemeName> {
+        let name = name.trim().to_lowercase().replace('_', "-");
+        ThemeName::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    /// The theme after this one, for cycling with a bare `/theme`
+    pub fn next(&self) -> ThemeName {
+        let idx = ThemeName::ALL.iter().position(|t| t == self).unwrap_or(0);
+        ThemeName::ALL[(idx + 1) % ThemeName::ALL.len()]
+    }
+}
+
+/// clap value parser for `--theme`
+pub fn parse_theme_arg(name: &str) -> Result<ThemeName, String> {
+    ThemeName::parse(name).ok_or_else(|| format!("unknown theme '{}' (expected one of: {})", name, theme_list()))
+}
+
+/// "dark, light, high-contrast"
+pub fn theme_list() -> String {
+    ThemeName::ALL.iter().map(|t| t.name()).collect::<Vec<_>>().join(", ")
+}
+
+/// The active theme: how each color of the dark palette is drawn
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub name: ThemeName,
+    palette: Vec<(Color, Color)>,
+}
+
+impl Theme {
+    pub fn new(name: ThemeName) -> Self {
+        let palette = match name {
+            ThemeName::Dark => vec![],
+            ThemeName::Light => vec![
+                (Color::White, Color::Black),
+                (Color::Gray, Color::DarkGray),
+                (Color::Yellow, Color::Rgb(150, 100, 0)),
+                (Color::Cyan, Color::Blue),
+                (Color::Green, Color::Rgb(0, 120, 0)),
+                (Color::Magenta, Color::Rgb(140, 0, 140)),
+                (Color::Rgb(50, 50, 70), Color::Rgb(205, 210, 235)),
+            ],
+            ThemeName::HighContrast => vec![
+                (Color::DarkGray, Color::White),
+                (Color::Gray, Color::White),
+                (Color::Yellow, Color::LightYellow),
+                (Color::Cyan, Color::LightCyan),
+                (Color::Green, Color::LightGreen),
+                (Color::Red, Color::LightRed),
+                (Color::Magenta, Color::LightMagenta),
+                (Color::Blue, Color::LightBlue),
+                (Color::Rgb(50, 50, 70), Color::Blue),
+            ],
+        };
+        Theme { name, palette }
+    }
+
+    pub fn color(&self, color: Color) -> Color {
+        self.palette.iter().find(|(from, _)| *from == color).map(|(_, to)| *to).unwrap_or(color)
+    }
+
+    pub fn apply(&self, style: Style) -> Style {
+        Style {
+            fg: style.fg.map(|c| self.color(c)),
+            bg: style.bg.map(|c| self.color(c)),
+            underline_color: style.underline_color.map(|c| self.color(c)),
+            ..style
+        }
+    }
+
+    /// Dark is what the UI draws natively, so it needs no remapping
+    pub fn is_native(&self) -> bool {
+        self.palette.is_empty()
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::new(ThemeName::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_names_and_palettes() {
+        assert_eq!(ThemeName::parse("High_Contrast"), Some(ThemeName::HighContrast));
+        assert_eq!(ThemeName::parse("solarized"), None);
+        assert_eq!(ThemeName::HighContrast.next(), ThemeName::Dark);
+        assert!(parse_theme_arg("neon").unwrap_err().contains("dark, light, high-contrast"));
+
+        let light = Theme::new(ThemeName::Light);
+        let style = light.apply(Style::default().fg(Color::White).bg(Color::Rgb(50, 50, 70)));
+        assert_eq!((style.fg, style.bg), (Some(Color::Black), Some(Color::Rgb(205, 210, 235))));
+        assert_eq!(light.color(Color::Red), Color::Red);
+        assert!(Theme::default().is_native());
+    }
+}
@@ -11,7 +11,6 @@ use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
-use std::process::Command;
 
 const TOOLS_DIR: &str = ".config/grok-cli/tools";
 
@@ -57,6 +56,25 @@ pub struct YamlTool {
     /// Icon/emoji for display (optional)
     #[serde(default)]
     pub icon: Option<String>,
+    /// What the command prints: `text` (default, passed through) or `json`
+    /// (validated, so the model only ever sees well-formed JSON)
+    #[serde(default)]
+    pub output_format: OutputFormat,
+    /// The command only reads (no files changed, nothing started)
+    #[serde(default)]
+    pub read_only: bool,
+    /// Ask before running it, like Bash (default: unless `read_only`)
+    #[serde(default)]
+    pub requires_approval: Option<bool>,
+}
+
+/// Format of a plugin command's stdout
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
 }
 
 fn default_true() -> bool {
@@ -94,9 +112,13 @@ impl YamlTool {
         })
     }
 
-    /// Execute the tool with given arguments
-    pub fn execute(&self, args: &Value, sandbox_cwd: Option<&str>) -> String {
-        // Build the command with parameter substitution
+    /// Whether a call has to be approved like a Bash command
+    pub fn needs_approval(&self) -> bool {
+        self.requires_approval.unwrap_or(!self.read_only)
+    }
+
+    /// The shell command a call runs, with its arguments substituted
+    pub fn render_command(&self, args: &Value) -> String {
         let mut command = self.command.clone();
 
         // Substitute parameters
@@ -120,6 +142,12 @@ impl YamlTool {
 
             command = command.replace(&placeholder, &shell_escape(&value));
         }
+        command
+    }
+
+    /// Execute the tool with given arguments
+    pub fn execute(&self, args: &Value, sandbox_cwd: Option<&str>) -> String {
+        let command = self.render_command(args);
 
         // Determine working directory
         let work_dir = self.working_dir.as_deref().unwrap_or(".");
@@ -144,25 +172,17 @@ impl YamlTool {
         }
 
         // Execute the command
-        let output = if let Some(cwd) = sandbox_cwd {
-            Command::new("sh")
-                .arg("-c")
-                .arg(&command)
-                .current_dir(cwd)
-                .output()
-        } else {
-            Command::new("sh")
-                .arg("-c")
-                .arg(&command)
-                .current_dir(work_dir)
-                .output()
-        };
+        let output = crate::tools::shell_command(&command, Some(sandbox_cwd.unwrap_or(work_dir))).output();
 
         match output {
             Ok(out) => {
                 let stdout = String::from_utf8_lossy(&out.stdout);
                 let stderr = String::from_utf8_lossy(&out.stderr);
 
+                if self.output_format == OutputFormat::Json {
+                    return self.json_output(&stdout, &stderr);
+                }
+
                 let mut result = String::new();
                 if !stdout.is_empty() {
                     result.push_str(&stdout);
@@ -182,6 +202,26 @@ impl YamlTool {
             Err(e) => format!("Error executing command: {}", e),
         }
     }
+
+    /// Parsed stdout re-serialized compactly, or an error quoting what was printed.
+    /// stderr is dropped on success so warnings can't corrupt the JSON.
+    fn json_output(&self, stdout: &str, stderr: &str) -> String {
+        match serde_json::from_str::<Value>(stdout) {
+            Ok(value) => value.to_string(),
+            Err(e) => {
+                let mut error = format!(
+                    "Error: {} was declared to output JSON but printed invalid JSON ({}):\n{}",
+                    self.name,
+                    e,
+                    crate::ui::truncate(stdout.trim(), 500)
+                );
+                if !stderr.trim().is_empty() {
+                    error.push_str(&format!("\nstderr: {}", crate::ui::truncate(stderr.trim(), 500)));
+                }
+                error
+            }
+        }
+    }
 }
 
 /// Escape special shell characters
@@ -190,6 +230,71 @@ fn shell_escape(s: &str) -> String {
     format!("'{}'", s.replace("'", "'\"'\"'"))
 }
 
+/// YAML tool files (`*.yaml` and `*.yml`) in `dir`, sorted
+pub fn plugin_files(dir: &std::path::Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = ["*.yaml", "*.yml"]
+        .iter()
+        .filter_map(|ext| glob::glob(&dir.join(ext).to_string_lossy()).ok())
+        .flat_map(|entries| entries.flatten())
+        .collect();
+    files.sort();
+    files
+}
+
+/// Read and validate a single tool definition
+pub fn parse_tool_file(path: &std::path::Path) -> Result<YamlTool, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+
+    let tool: YamlTool = serde_yaml::from_str(&content)
+        .map_err(|e| format!("Failed to parse YAML: {}", e))?;
+
+    // Validate required fields
+    if tool.name.is_empty() {
+        return Err("Tool name is required".to_string());
+    }
+    if tool.description.is_empty() {
+        return Err("Tool description is required".to_string());
+    }
+    if tool.command.is_empty() {
+        return Err("Tool command is required".to_string());
+    }
+    if let Some(name) = quoted_placeholder(&tool.command, &tool.parameters) {
+        return Err(format!(
+            "{{{{{}}}}} is inside quotes in the command; remove the quotes, values are quoted automatically",
+            name
+        ));
+    }
+
+    Ok(tool)
+}
+
+/// First parameter whose placeholder sits inside quotes in `command`.
+/// Substituted values are already single-quoted, so a surrounding quote
+/// closes that quoting and lets the value run as shell code
+fn quoted_placeholder<'a>(command: &str, parameters: &'a [ToolParameter]) -> Option<&'a str> {
+    let mut quote: Option<char> = None;
+    let mut rest = command;
+    while let Some(c) = rest.chars().next() {
+        if quote.is_some() {
+            if let Some(param) = parameters.iter().find(|p| rest.starts_with(&format!("{{{{{}}}}}", p.name))) {
+                return Some(&param.name);
+            }
+        }
+        let mut skip = c.len_utf8();
+        match (quote, c) {
+            (None, '\\') | (Some('"'), '\\') => {
+                skip += rest[skip..].chars().next().map_or(0, char::len_utf8);
+            }
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => {}
+        }
+        rest = &rest[skip..];
+    }
+    None
+}
+
 /// Tool plugin manager
 pub struct ToolPluginManager {
     /// Loaded tools from YAML files
@@ -224,52 +329,17 @@ impl ToolPluginManager {
             }
         }
 
-        // Find all YAML files
-        let pattern = tools_dir.join("*.yaml");
-        let pattern_str = pattern.to_string_lossy();
-
-        if let Ok(entries) = glob::glob(&pattern_str) {
-            for entry in entries.flatten() {
-                if let Err(e) = self.load_tool_file(&entry) {
-                    eprintln!("Warning: Failed to load tool {}: {}", entry.display(), e);
-                }
-            }
-        }
-
-        // Also check .yml extension
-        let pattern = tools_dir.join("*.yml");
-        let pattern_str = pattern.to_string_lossy();
-
-        if let Ok(entries) = glob::glob(&pattern_str) {
-            for entry in entries.flatten() {
-                if let Err(e) = self.load_tool_file(&entry) {
-                    eprintln!("Warning: Failed to load tool {}: {}", entry.display(), e);
-                }
+        for entry in plugin_files(&tools_dir) {
+            match parse_tool_file(&entry) {
+                Ok(tool) => self.insert(tool),
+                Err(e) => eprintln!("Warning: Failed to load tool {}: {}", entry.display(), e),
             }
         }
     }
 
-    /// Load a single tool from a YAML file
-    fn load_tool_file(&mut self, path: &std::path::Path) -> Result<(), String> {
-        let content = fs::read_to_string(path)
-            .map_err(|e| format!("Failed to read file: {}", e))?;
-
-        let tool: YamlTool = serde_yaml::from_str(&content)
-            .map_err(|e| format!("Failed to parse YAML: {}", e))?;
-
-        // Validate required fields
-        if tool.name.is_empty() {
-            return Err("Tool name is required".to_string());
-        }
-        if tool.description.is_empty() {
-            return Err("Tool description is required".to_string());
-        }
-        if tool.command.is_empty() {
-            return Err("Tool command is required".to_string());
-        }
-
+    /// Add a tool, replacing any with the same name
+    pub fn insert(&mut self, tool: YamlTool) {
         self.tools.insert(tool.name.clone(), tool);
-        Ok(())
     }
 
     /// Get all loaded tools
@@ -331,6 +401,15 @@ pub fn execute_plugin_tool(name: &str, args: &str, sandbox_cwd: Option<&str>) ->
     TOOL_PLUGINS.lock().ok()?.execute_tool(name, args, sandbox_cwd)
 }
 
+/// The command a plugin call would run, if the plugin requires approval
+/// (None for read-only plugins and for names that aren't plugins)
+pub fn approval_command(name: &str, args: &str) -> Option<String> {
+    let manager = TOOL_PLUGINS.lock().ok()?;
+    let tool = manager.get_tool(name).filter(|t| t.needs_approval())?;
+    let args: Value = serde_json::from_str(args).unwrap_or(json!({}));
+    Some(tool.render_command(&args))
+}
+
 /// Check if a tool name is a plugin
 pub fn is_plugin_tool(name: &str) -> bool {
     TOOL_PLUGINS.lock()
@@ -359,7 +438,7 @@ parameters:
     type: string
     description: The search query
     required: true
-command: echo "{{query}}"
+command: echo {{query}}
 "#;
 
         let tool: YamlTool = serde_yaml::from_str(yaml).unwrap();
@@ -387,10 +466,82 @@ command: echo "{{query}}"
             sandbox_aware: true,
             category: None,
             icon: None,
+            output_format: OutputFormat::Text,
+            read_only: false,
+            requires_approval: None,
         };
 
         let args = json!({"message": "hello world"});
         let result = tool.execute(&args, None);
         assert!(result.contains("hello world"));
     }
+
+    #[test]
+    fn test_approval_defaults_to_unless_read_only() {
+        let parse = |extra: &str| -> YamlTool {
+            serde_yaml::from_str(&format!("name: t\ndescription: d\ncommand: ls {{{{dir}}}}\n{}", extra)).unwrap()
+        };
+        assert!(parse("").needs_approval());
+        assert!(!parse("read_only: true").needs_approval());
+        assert!(parse("read_only: true\nrequires_approval: true").needs_approval());
+        assert!(!parse("requires_approval: false").needs_approval());
+
+        let mut tool = parse("");
+        tool.parameters.push(ToolParameter {
+            name: "dir".to_string(),
+            param_type: "string".to_string(),
+            description: "Directory".to_string(),
+            required: true,
+            default: None,
+        });
+        assert_eq!(tool.render_command(&json!({"dir": "it's here"})), r#"ls 'it'"'"'s here'"#);
+    }
+
+    #[test]
+    fn test_quoted_placeholders_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let load = |command: &str| {
+            let path = dir.path().join("tool.yaml");
+            fs::write(&path, format!(
+                "name: t\ndescription: d\nparameters:\n  - name: filter\n    type: string\n    description: f\ncommand: |\n  {}\n",
+                command
+            )).unwrap();
+            parse_tool_file(&path)
+        };
+
+        for command in [r#"grep -i "{{filter}}""#, "grep -i '{{filter}}'", r#"echo "x {{filter}} y""#] {
+            let err = load(command).unwrap_err();
+            assert!(err.contains("{{filter}} is inside quotes"), "{}: {}", command, err);
+        }
+        for command in [
+            "grep -i -- {{filter}}",
+            r#"[ {{filter}} = "true" ] && echo 'done'"#,
+            r#"echo \"{{filter}}\""#,
+            r#"echo "a\"b" {{filter}}"#,
+            "echo {{other}} '{{other}}'",
+        ] {
+            assert!(load(command).is_ok(), "{}", command);
+        }
+    }
+
+    #[test]
+    fn test_json_output_format() {
+        let yaml = r#"
+name: json_tool
+description: Prints JSON
+command: "true"
+output_format: json
+"#;
+        let mut tool: YamlTool = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(tool.output_format, OutputFormat::Json);
+
+        tool.command = r#"printf '{ "status": "ok",\n  "count": 2 }\n'; echo warning >&2"#.to_string();
+        let result = tool.execute(&json!({}), None);
+        assert_eq!(result, r#"{"count":2,"status":"ok"}"#);
+
+        tool.command = "echo 'status: ok'".to_string();
+        let result = tool.execute(&json!({}), None);
+        assert!(result.starts_with("Error: json_tool was declared to output JSON but printed invalid JSON"), "{}", result);
+        assert!(result.contains("status: ok"));
+    }
 }
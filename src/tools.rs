@@ -6,6 +6,93 @@ use std::path::Path;
 use std::process::Command;
 use crate::tool_plugins;
 
+lazy_static::lazy_static! {
+    /// Content hash of each file as of the model's last Read (or its own write),
+    /// used by Edit to detect external modifications in between
+    static ref READ_HASHES: std::sync::Mutex<std::collections::HashMap<std::path::PathBuf, u64>> =
+        std::sync::Mutex::new(std::collections::HashMap::new());
+}
+
+fn content_hash(content: &str) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn hash_key(path: &str) -> std::path::PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| Path::new(path).to_path_buf())
+}
+
+/// Remember the content the model has seen for `path`
+fn record_read_hash(path: &str, content: &str) {
+    if let Ok(mut hashes) = READ_HASHES.lock() {
+        hashes.insert(hash_key(path), content_hash(content));
+    }
+}
+
+/// A single-file write applied during a turn
+struct AppliedWrite {
+    path: std::path::PathBuf,
+    /// Content hash of the file right afterwards
+    hash: u64,
+    result: String,
+}
+
+/// Writes applied during one turn, keyed by (tool, arguments)
+type TurnWrites = std::collections::HashMap<(String, String), AppliedWrite>;
+
+lazy_static::lazy_static! {
+    /// Writes applied by each turn that is still going, keyed by its
+    /// transaction. Writes outside a turn (the MCP server) aren't kept: no
+    /// turn end would ever clear them
+    static ref APPLIED_WRITES: std::sync::Mutex<std::collections::HashMap<crate::transactions::TransactionId, TurnWrites>> =
+        std::sync::Mutex::new(std::collections::HashMap::new());
+}
+
+/// Forget the writes of a turn that is over: a later turn may repeat them on purpose
+pub fn end_turn(transaction: crate::transactions::TransactionId) {
+    if let Ok(mut applied) = APPLIED_WRITES.lock() {
+        applied.remove(&transaction);
+    }
+}
+
+/// File a Write/Edit/EditCell call changes
+fn single_file_write_target<'a>(name: &str, args: &'a Value) -> Option<&'a str> {
+    match canonical_tool_name(name) {
+        "Write" | "Edit" | "EditCell" => args["file_path"].as_str().filter(|p| !p.is_empty()),
+        _ => None,
+    }
+}
+
+fn record_applied_write(key: (String, String), path: &Path, result: &str) {
+    let Some(transaction) = crate::transactions::current_transaction() else { return };
+    let Ok(content) = fs::read_to_string(path) else { return };
+    if let Ok(mut applied) = APPLIED_WRITES.lock() {
+        let write = AppliedWrite { path: hash_key(&path.to_string_lossy()), hash: content_hash(&content), result: result.to_string() };
+        applied.entry(transaction).or_default().insert(key, write);
+    }
+}
+
+/// The earlier result, if this exact write was applied this turn and the file
+/// still holds what it left behind (so repeating it would change nothing intended)
+fn repeated_write(key: &(String, String)) -> Option<String> {
+    let transaction = crate::transactions::current_transaction()?;
+    let applied = APPLIED_WRITES.lock().ok()?;
+    let write = applied.get(&transaction)?.get(key)?;
+    let current = fs::read_to_string(&write.path).ok()?;
+    (content_hash(&current) == write.hash).then(|| write.result.clone())
+}
+
+/// True if `path` was Read and its content has changed since then
+fn changed_since_read(path: &str, current: &str) -> bool {
+    READ_HASHES
+        .lock()
+        .ok()
+        .and_then(|hashes| hashes.get(&hash_key(path)).copied())
+        .is_some_and(|hash| hash != content_hash(current))
+}
+
 /// Default patterns to ignore (used when no .grokignore exists)
 const DEFAULT_IGNORE_PATTERNS: &[&str] = &[
     ".git",
@@ -44,6 +131,41 @@ fn default_ignore_patterns() -> Vec<String> {
     DEFAULT_IGNORE_PATTERNS.iter().map(|s| s.to_string()).collect()
 }
 
+/// Describe why a .grokignore pattern can't be honored by `should_ignore`, if it can't.
+/// The matcher compares patterns against single path components, so gitignore
+/// features that depend on full paths are not supported yet.
+pub fn unsupported_ignore_syntax(pattern: &str) -> Option<&'static str> {
+    if pattern.starts_with('!') {
+        Some("negation (!) is not supported")
+    } else if pattern.contains("**") {
+        Some("'**' is not supported")
+    } else if pattern.starts_with('/') {
+        Some("anchored patterns (leading /) are not supported")
+    } else if pattern.ends_with('/') {
+        Some("directory-only patterns (trailing /) are not supported")
+    } else if pattern.contains('/') {
+        Some("patterns are matched per path component, so '/' never matches")
+    } else {
+        None
+    }
+}
+
+/// Check .grokignore for patterns using unsupported syntax.
+/// Returns one warning per offending pattern (empty if the file is fine or missing).
+pub fn check_grokignore() -> Vec<String> {
+    if !Path::new(".grokignore").exists() {
+        return Vec::new();
+    }
+
+    read_grokignore()
+        .iter()
+        .filter_map(|pattern| {
+            unsupported_ignore_syntax(pattern)
+                .map(|reason| format!(".grokignore: '{}' - {}", pattern, reason))
+        })
+        .collect()
+}
+
 /// Create a default .grokignore file in the current working directory
 /// Returns Ok(true) if created, Ok(false) if already exists, Err on failure
 pub fn create_default_grokignore() -> Result<bool, std::io::Error> {
@@ -135,19 +257,255 @@ fn generate_diff_snippet(old_string: &str, new_string: &str) -> String {
     result.trim_end().to_string()
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// One replacement of an Edit call: the top-level old_string/new_string, or an
+/// entry of its `edits` array
+struct EditSpec<'a> {
+    old_string: &'a str,
+    new_string: &'a str,
+    replace_all: bool,
+}
+
+/// The replacements an Edit call asks for, in order
+fn edit_specs(args: &Value) -> Result<Vec<EditSpec<'_>>, String> {
+    let Some(edits) = args.get("edits").filter(|e| !e.is_null()) else {
+        return Ok(vec![EditSpec {
+            old_string: args["old_string"].as_str().unwrap_or(""),
+            new_string: args["new_string"].as_str().unwrap_or(""),
+            replace_all: args["replace_all"].as_bool().unwrap_or(false),
+        }]);
+    };
+    let Some(edits) = edits.as_array().filter(|e| !e.is_empty()) else {
+        return Err("Error: edits must be a non-empty array of {old_string, new_string, replace_all}".to_string());
+    };
+    edits
+        .iter()
+        .enumerate()
+        .map(|(i, edit)| match (edit["old_string"].as_str(), edit["new_string"].as_str()) {
+            (Some(old_string), Some(new_string)) => Ok(EditSpec {
+                old_string,
+                new_string,
+                replace_all: edit["replace_all"].as_bool().unwrap_or(false),
+            }),
+            _ => Err(format!("Error: edit {} needs old_string and new_string", i + 1)),
+        })
+        .collect()
+}
+
+/// Apply one replacement to `content`, returning the new content and how many
+/// occurrences were replaced
+fn apply_edit(content: &str, edit: &EditSpec, file_path: &str) -> Result<(String, usize), std::io::Error> {
+    let count = content.matches(edit.old_string).count();
+
+    if count == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!(
+                "Error: old_string not found in {}\n\n\
+                 The text must match EXACTLY, including:\n\
+                 - All whitespace and indentation\n\
+                 - Line endings\n\
+                 - Any special characters\n\n\
+                 Tip: Copy the exact text from the Read output.",
+                file_path
+            )
+        ));
+    }
+
+    if count > 1 && !edit.replace_all {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "Error: old_string appears {} times in the file.\n\n\
+                To fix:\n\
+                - Include more surrounding context to make old_string unique, OR\n\
+                - Use replace_all: true to replace all occurrences",
+                count
+            )
+        ));
+    }
+
+    let new_content = if edit.replace_all {
+        content.replace(edit.old_string, edit.new_string)
+    } else {
+        content.replacen(edit.old_string, edit.new_string, 1)
+    };
+    Ok((new_content, count))
+}
+
+/// Tools that modify files (gated behind approval in safe mode)
+pub fn is_mutating_tool(name: &str) -> bool {
+    matches!(canonical_tool_name(name), "Edit" | "EditCell" | "Write" | "RenameSymbol" | "ProjectReplace")
+}
+
+/// Tools with no side effects, which can run concurrently within a turn
+pub fn is_read_only_tool(name: &str) -> bool {
+    matches!(canonical_tool_name(name), "Read" | "Grep" | "Glob" | "List" | "SearchHistory")
+}
+
+/// Describe what a mutating tool call would do without touching the filesystem.
+/// Returns (target, preview) for the approval prompt.
+pub fn preview_file_change(name: &str, args_json: &str, sandbox_cwd: Option<&str>) -> (String, String) {
+    let args: Value = serde_json::from_str(args_json).unwrap_or(json!({}));
+    let file_path = args["file_path"].as_str().unwrap_or("?").to_string();
+
+    match canonical_tool_name(name) {
+        "Edit" => {
+            let preview = match edit_specs(&args) {
+                Ok(edits) if edits.len() > 1 => format!(
+                    "Dry run - Edit {} ({} edits):\n{}",
+                    file_path,
+                    edits.len(),
+                    edits.iter().map(|e| generate_diff_snippet(e.old_string, e.new_string)).collect::<Vec<_>>().join("\n\n")
+                ),
+                Ok(edits) => {
+                    let all = if edits[0].replace_all { " (all occurrences)" } else { "" };
+                    format!(
+                        "Dry run - Edit {}{}:\n{}",
+                        file_path,
+                        all,
+                        generate_diff_snippet(edits[0].old_string, edits[0].new_string)
+                    )
+                }
+                Err(e) => e,
+            };
+            (file_path, preview)
+        }
+        "Write" => {
+            let content = args["content"].as_str().unwrap_or("");
+            let action = if Path::new(&file_path).exists() { "overwrite" } else { "create" };
+            let preview = format!(
+                "Dry run - Write would {} {} ({} lines, {} bytes)",
+                action,
+                file_path,
+                content.lines().count(),
+                content.len()
+            );
+            (file_path, preview)
+        }
+        "EditCell" => {
+            let cell_index = args["cell_index"].as_u64().unwrap_or(0);
+            let new_source = args["new_source"].as_str().unwrap_or("");
+            let preview = format!(
+                "Dry run - EditCell {} [cell {}] new source:\n{}",
+                file_path,
+                cell_index,
+                generate_diff_snippet("", new_source)
+            );
+            (file_path, preview)
+        }
+        "RenameSymbol" => {
+            let old = args["old"].as_str().unwrap_or("?");
+            let new = args["new"].as_str().unwrap_or("?");
+            let scope = args["glob"].as_str().unwrap_or("**/*").to_string();
+            let preview = format!("Dry run - RenameSymbol '{}' → '{}' in files matching {}", old, new, scope);
+            (scope, preview)
+        }
+        "ProjectReplace" => {
+            let pattern = args["pattern"].as_str().unwrap_or("");
+            let replacement = args["replacement"].as_str().unwrap_or("");
+            let is_regex = args["regex"].as_bool().unwrap_or(false);
+            let scope = args["glob"].as_str().unwrap_or("**/*").to_string();
+            let preview = preview_project_replace(pattern, replacement, is_regex, &scope, sandbox_cwd);
+            (scope, preview)
+        }
+        _ => (file_path, format!("Dry run - {} {}", name, args_json)),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ToolCall {
     pub id: String,
     pub r#type: String,
     pub function: FunctionCall,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FunctionCall {
     pub name: String,
     pub arguments: String,
 }
 
+/// Snake-case names the model sometimes uses instead of a builtin's own
+const TOOL_ALIASES: &[(&str, &str)] = &[
+    ("run_shell_command", "Bash"),
+    ("read_file", "Read"),
+    ("read_lines", "Read"),
+    ("edit_file", "Edit"),
+    ("edit_cell", "EditCell"),
+    ("rename_symbol", "RenameSymbol"),
+    ("project_replace", "ProjectReplace"),
+    ("write_file", "Write"),
+    ("glob_files", "Glob"),
+    ("grep", "Grep"),
+    ("search_files", "Grep"),
+    ("search_content", "Grep"),
+    ("list_directory", "List"),
+    ("git_context", "GitContext"),
+    ("diagnostics", "Diagnostics"),
+    ("env_info", "EnvInfo"),
+    ("file_info", "FileInfo"),
+    ("ask_multiple_choice", "AskUser"),
+    ("confirm_plan", "ConfirmPlan"),
+    ("web_search", "WebSearch"),
+    ("search_history", "SearchHistory"),
+];
+
+/// The builtin a tool name refers to (plugin and unknown names are returned as is).
+/// Dispatch and approval match on this, so each tool is listed once
+pub fn canonical_tool_name(name: &str) -> &str {
+    TOOL_ALIASES.iter().find(|(alias, _)| *alias == name).map(|(_, tool)| *tool).unwrap_or(name)
+}
+
+/// Builtins that reach the network
+const NETWORK_TOOLS: &[&str] = &["WebSearch"];
+
+pub fn is_network_tool(name: &str) -> bool {
+    NETWORK_TOOLS.contains(&canonical_tool_name(name))
+}
+
+/// Which tools the model may use, from `enabled_tools` / `disabled_tools`,
+/// `allow_network` and `allow_history_search` in config. Applies whatever the active role; an empty
+/// `enabled` list allows every tool.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolFilter {
+    pub enabled: Vec<String>,
+    pub disabled: Vec<String>,
+    /// No network tools at all (`allow_network: false`)
+    pub offline: bool,
+    /// SearchHistory may read saved sessions (`allow_history_search: true`)
+    pub history_search: bool,
+}
+
+impl ToolFilter {
+    pub fn allows(&self, name: &str) -> bool {
+        self.check(name).is_ok()
+    }
+
+    /// Error returned to the model for a tool it may not use
+    pub fn check(&self, name: &str) -> Result<(), String> {
+        if self.offline && is_network_tool(name) {
+            return Err("Error: Network access disabled (allow_network is false in config)".to_string());
+        }
+        let canonical = canonical_tool_name(name);
+        if canonical == "SearchHistory" && !self.history_search {
+            return Err("Error: SearchHistory is off (set allow_history_search: true in config to let the model read saved sessions)".to_string());
+        }
+        let listed = |list: &[String]| list.iter().any(|t| canonical_tool_name(t) == canonical);
+        if (self.enabled.is_empty() || listed(&self.enabled)) && !listed(&self.disabled) {
+            Ok(())
+        } else {
+            Err(format!("Error: tool '{}' is disabled in config", name))
+        }
+    }
+
+    pub fn apply(&self, definitions: Vec<Value>) -> Vec<Value> {
+        definitions
+            .into_iter()
+            .filter(|def| def["function"]["name"].as_str().is_some_and(|name| self.allows(name)))
+            .collect()
+    }
+}
+
 pub fn get_tool_definitions() -> Vec<Value> {
     let mut tools = vec![
         // === Bash ===
@@ -166,6 +524,10 @@ pub fn get_tool_definitions() -> Vec<Value> {
                         "description": {
                             "type": "string",
                             "description": "Brief description of what this command does (5-10 words)"
+                        },
+                        "timeout_secs": {
+                            "type": "integer",
+                            "description": "Kill the command after this many seconds (default from config, usually 120). Raise it for long builds or test runs."
                         }
                     },
                     "required": ["command"]
@@ -177,7 +539,7 @@ pub fn get_tool_definitions() -> Vec<Value> {
             "type": "function",
             "function": {
                 "name": "Read",
-                "description": "Reads a file from the filesystem. Returns content with line numbers. You MUST read a file before editing it. For large files, use offset and limit to read specific sections.",
+                "description": "Reads a file from the filesystem. Returns content with line numbers. You MUST read a file before editing it. For large files, use offset and limit to read specific sections, or byte_offset/byte_limit for a raw byte window. Jupyter notebooks (.ipynb) are shown as numbered cells with their outputs.",
                 "parameters": {
                     "type": "object",
                     "properties": {
@@ -192,6 +554,14 @@ pub fn get_tool_definitions() -> Vec<Value> {
                         "limit": {
                             "type": "integer",
                             "description": "Maximum number of lines to read. Only use for large files."
+                        },
+                        "byte_offset": {
+                            "type": "integer",
+                            "description": "Read raw bytes starting at this offset (0-indexed) instead of lines. For huge logs or binary files."
+                        },
+                        "byte_limit": {
+                            "type": "integer",
+                            "description": "Number of bytes to read in byte mode (default 4096, max 1048576)"
                         }
                     },
                     "required": ["file_path"]
@@ -203,7 +573,7 @@ pub fn get_tool_definitions() -> Vec<Value> {
             "type": "function",
             "function": {
                 "name": "Edit",
-                "description": "Performs exact string replacement in a file. You MUST Read the file first before editing. The old_string must match EXACTLY including all whitespace and indentation. The edit will FAIL if old_string is not found or is not unique in the file. To make old_string unique, include more surrounding context. Use replace_all only for renaming variables/functions across the file.",
+                "description": "Performs exact string replacement in a file. You MUST Read the file first before editing. The old_string must match EXACTLY including all whitespace and indentation. The edit will FAIL if old_string is not found or is not unique in the file. To make old_string unique, include more surrounding context. Use replace_all only for renaming variables/functions across the file. To make several changes to one file, pass them all in edits instead of calling Edit repeatedly.",
                 "parameters": {
                     "type": "object",
                     "properties": {
@@ -222,9 +592,108 @@ pub fn get_tool_definitions() -> Vec<Value> {
                         "replace_all": {
                             "type": "boolean",
                             "description": "Replace all occurrences instead of requiring uniqueness. Use for renaming variables/functions. Default: false"
+                        },
+                        "edits": {
+                            "type": "array",
+                            "description": "Several replacements applied in order in one write, instead of old_string/new_string. Each must match like a single edit; if any fails, none are applied.",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "old_string": { "type": "string" },
+                                    "new_string": { "type": "string" },
+                                    "replace_all": { "type": "boolean" }
+                                },
+                                "required": ["old_string", "new_string"]
+                            }
+                        },
+                        "force": {
+                            "type": "boolean",
+                            "description": "Apply the edit even if the file changed on disk since it was last Read. Prefer re-reading the file instead. Default: false"
+                        }
+                    },
+                    "required": ["file_path"]
+                }
+            }
+        }),
+        // === EditCell ===
+        json!({
+            "type": "function",
+            "function": {
+                "name": "EditCell",
+                "description": "Replaces the source of one cell in a Jupyter notebook (.ipynb), keeping the rest of the notebook (other cells, outputs, metadata) intact. Read the notebook first to see cell indexes.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "file_path": {
+                            "type": "string",
+                            "description": "The path to the .ipynb file"
+                        },
+                        "cell_index": {
+                            "type": "integer",
+                            "description": "0-based index of the cell, as shown by Read ([cell N])"
+                        },
+                        "new_source": {
+                            "type": "string",
+                            "description": "The complete new source for the cell"
+                        }
+                    },
+                    "required": ["file_path", "cell_index", "new_source"]
+                }
+            }
+        }),
+        // === RenameSymbol ===
+        json!({
+            "type": "function",
+            "function": {
+                "name": "RenameSymbol",
+                "description": "Renames a symbol across multiple files using whole-word matching, so substrings of longer identifiers are left alone. All files are updated together or not at all. Returns a per-file summary of replacements.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "old": {
+                            "type": "string",
+                            "description": "The current symbol name"
+                        },
+                        "new": {
+                            "type": "string",
+                            "description": "The new symbol name"
+                        },
+                        "glob": {
+                            "type": "string",
+                            "description": "Glob pattern limiting which files are changed (e.g., 'src/**/*.rs'). Defaults to all files."
+                        }
+                    },
+                    "required": ["old", "new"]
+                }
+            }
+        }),
+        // === ProjectReplace ===
+        json!({
+            "type": "function",
+            "function": {
+                "name": "ProjectReplace",
+                "description": "Replaces a literal string or regex across multiple files. The user is shown which files would change and must confirm first. All files are updated together or not at all. Returns the number of replacements per file.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "pattern": {
+                            "type": "string",
+                            "description": "The text to find (literal unless regex is true)"
+                        },
+                        "replacement": {
+                            "type": "string",
+                            "description": "The replacement text. With regex, $1 or ${name} refer to capture groups."
+                        },
+                        "regex": {
+                            "type": "boolean",
+                            "description": "Treat pattern as a regular expression (default false)"
+                        },
+                        "glob": {
+                            "type": "string",
+                            "description": "Glob pattern limiting which files are changed (e.g., 'src/**/*.rs'). Defaults to all files."
                         }
                     },
-                    "required": ["file_path", "old_string", "new_string"]
+                    "required": ["pattern", "replacement"]
                 }
             }
         }),
@@ -266,6 +735,15 @@ pub fn get_tool_definitions() -> Vec<Value> {
                         "path": {
                             "type": "string",
                             "description": "Directory to search in. Defaults to current directory."
+                        },
+                        "sort": {
+                            "type": "string",
+                            "enum": ["name", "mtime", "size"],
+                            "description": "Order of results: name (default), mtime (most recently modified first) or size (largest first)"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Return at most this many files (after sorting)"
                         }
                     },
                     "required": ["pattern"]
@@ -290,18 +768,95 @@ pub fn get_tool_definitions() -> Vec<Value> {
                             "description": "File or directory to search in. Defaults to current directory."
                         },
                         "include": {
-                            "type": "string",
-                            "description": "Only search files matching this glob pattern (e.g., '*.rs', '*.py')"
+                            "anyOf": [
+                                {"type": "string"},
+                                {"type": "array", "items": {"type": "string"}}
+                            ],
+                            "description": "Only search files matching this glob pattern, or any of a list of patterns (e.g., '*.rs' or ['*.rs', '*.toml'])"
                         },
                         "context_lines": {
                             "type": "integer",
                             "description": "Number of context lines to show before and after each match"
+                        },
+                        "numbered": {
+                            "type": "boolean",
+                            "description": "Render matches with their context as line-numbered blocks per file, formatted like Read output, so you can Edit without a separate Read. Uses context_lines (default 3)."
+                        },
+                        "group_by_file": {
+                            "type": "boolean",
+                            "description": "Print each file once as a header followed by its indented matching lines instead of repeating the path on every line. Shorter when many matches are in the same files."
+                        },
+                        "engine": {
+                            "type": "string",
+                            "enum": ["system", "builtin"],
+                            "description": "\"system\" runs grep (basic regex syntax); \"builtin\" searches in-process with Rust regex syntax. Defaults to system grep when it is installed, otherwise builtin."
                         }
                     },
                     "required": ["pattern"]
                 }
             }
         }),
+        // === GitContext ===
+        json!({
+            "type": "function",
+            "function": {
+                "name": "GitContext",
+                "description": "Shows a file's git history: recent commits touching it and, optionally, git blame for a line range. Use to understand why code looks the way it does. Requires user approval like other git commands.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "file_path": {
+                            "type": "string",
+                            "description": "The file to inspect (must be tracked in a git repository)"
+                        },
+                        "max_commits": {
+                            "type": "integer",
+                            "description": "How many recent commits to show. Default: 10"
+                        },
+                        "blame_start": {
+                            "type": "integer",
+                            "description": "First line (1-indexed) to blame. Omit to skip blame."
+                        },
+                        "blame_end": {
+                            "type": "integer",
+                            "description": "Last line to blame. Defaults to blame_start."
+                        }
+                    },
+                    "required": ["file_path"]
+                }
+            }
+        }),
+        // === Diagnostics ===
+        json!({
+            "type": "function",
+            "function": {
+                "name": "Diagnostics",
+                "description": "Runs the project's check command (configured in diagnostics.command, otherwise detected: cargo check, tsc, ruff, go vet) and returns a deduplicated list of errors and warnings as file:line:col: severity: message. Use it after editing to see what still needs fixing. Requires user approval like Bash.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {},
+                    "required": []
+                }
+            }
+        }),
+        // === EnvInfo ===
+        json!({
+            "type": "function",
+            "function": {
+                "name": "EnvInfo",
+                "description": "Reports facts about the environment without needing Bash approval: OS, architecture, working directory, detected project type and installed toolchain versions (rustc, cargo, node, python3, go, git...). Can also show specific environment variables by name, limited to well-known safe ones (PATH, HOME, LANG, CARGO_HOME, JAVA_HOME, ...); any other name is withheld.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "vars": {
+                            "type": "array",
+                            "items": {"type": "string"},
+                            "description": "Environment variable names to show. Default: a small set of safe ones (SHELL, LANG, VIRTUAL_ENV, ...)"
+                        }
+                    }
+                }
+            }
+        }),
         // === List ===
         json!({
             "type": "function",
@@ -396,6 +951,28 @@ pub fn get_tool_definitions() -> Vec<Value> {
                 }
             }
         }),
+        // === SearchHistory ===
+        json!({
+            "type": "function",
+            "function": {
+                "name": "SearchHistory",
+                "description": "Search the user's saved sessions (past conversations) for a word or phrase. Returns matching snippets with the session name. Use to recall earlier decisions or discussions.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "query": {
+                            "type": "string",
+                            "description": "Text to look for (case-insensitive)"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Maximum matches to return. Default: 20"
+                        }
+                    },
+                    "required": ["query"]
+                }
+            }
+        }),
         // === TodoWrite ===
         json!({
             "type": "function",
@@ -433,6 +1010,32 @@ pub fn get_tool_definitions() -> Vec<Value> {
                 }
             }
         }),
+        // === Summarize ===
+        json!({
+            "type": "function",
+            "function": {
+                "name": "Summarize",
+                "description": "Frees context by replacing a range of completed turns with a summary you write. Turns are numbered from 1 by user message in the current context; the turn in progress can't be summarized. Only the context sent to the model changes - the user still sees the full transcript. Keep anything you will need: file paths, decisions, open problems.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "from_turn": {
+                            "type": "integer",
+                            "description": "First turn to replace (1-indexed). Default: 1"
+                        },
+                        "to_turn": {
+                            "type": "integer",
+                            "description": "Last turn to replace (inclusive); must be before the current turn"
+                        },
+                        "summary": {
+                            "type": "string",
+                            "description": "What those turns established, replacing them in the context"
+                        }
+                    },
+                    "required": ["to_turn", "summary"]
+                }
+            }
+        }),
     ];
 
     // Add plugin tools from YAML files
@@ -448,6 +1051,12 @@ pub fn get_all_tool_names() -> Vec<String> {
         "Bash".to_string(),
         "Read".to_string(),
         "Edit".to_string(),
+        "EditCell".to_string(),
+        "RenameSymbol".to_string(),
+        "ProjectReplace".to_string(),
+        "GitContext".to_string(),
+        "Diagnostics".to_string(),
+        "EnvInfo".to_string(),
         "Write".to_string(),
         "Glob".to_string(),
         "Grep".to_string(),
@@ -456,7 +1065,9 @@ pub fn get_all_tool_names() -> Vec<String> {
         "AskUser".to_string(),
         "ConfirmPlan".to_string(),
         "WebSearch".to_string(),
+        "SearchHistory".to_string(),
         "TodoWrite".to_string(),
+        "Summarize".to_string(),
     ];
 
     // Add plugin tool names
@@ -467,7 +1078,7 @@ pub fn get_all_tool_names() -> Vec<String> {
     names
 }
 
-fn is_path_in_sandbox(path: &str, sandbox_cwd: &str) -> bool {
+pub fn is_path_in_sandbox(path: &str, sandbox_cwd: &str) -> bool {
     let path = Path::new(path);
     let sandbox = Path::new(sandbox_cwd);
 
@@ -498,58 +1109,399 @@ fn is_path_in_sandbox(path: &str, sandbox_cwd: &str) -> bool {
     canonical_path.starts_with(&canonical_sandbox)
 }
 
-pub fn execute_tool(name: &str, arguments: &str, sandbox_cwd: Option<&str>) -> String {
-    // First check if this is a plugin tool
-    if tool_plugins::is_plugin_tool(name) {
-        return tool_plugins::execute_plugin_tool(name, arguments, sandbox_cwd)
-            .unwrap_or_else(|| format!("Error: Plugin tool '{}' failed to execute", name));
-    }
+/// Default cap on Bash output returned to the model, in bytes
+pub const DEFAULT_BASH_OUTPUT_LIMIT: usize = 30_000;
+
+/// Default time a Bash command may run before it is killed
+pub const DEFAULT_BASH_TIMEOUT_SECS: u64 = 120;
+
+/// A call's own `timeout_secs` may be at most this many times the configured one
+const MAX_TIMEOUT_MULTIPLE: u64 = 10;
+
+/// How long output is still read after the command exits, for background
+/// processes it left running that keep the pipes open
+const EXITED_OUTPUT_GRACE: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Output read from a pipe so far, shared with the thread reading it
+type PipeBuffer = std::sync::Arc<std::sync::Mutex<Vec<u8>>>;
+
+/// Read `pipe` on its own thread until it closes. The buffer can be taken
+/// before that, when something else still holds the pipe open.
+fn read_pipe<R: std::io::Read + Send + 'static>(pipe: Option<R>) -> (PipeBuffer, std::thread::JoinHandle<()>) {
+    let buf = PipeBuffer::default();
+    let sink = buf.clone();
+    let reader = std::thread::spawn(move || {
+        let Some(mut pipe) = pipe else { return };
+        let mut chunk = [0u8; 8192];
+        loop {
+            match pipe.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => {
+                    if let Ok(mut buf) = sink.lock() {
+                        buf.extend_from_slice(&chunk[..n]);
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+    });
+    (buf, reader)
+}
 
-    let args: Value = serde_json::from_str(arguments).unwrap_or(json!({}));
+fn take_pipe_buffer(buf: &PipeBuffer) -> Vec<u8> {
+    buf.lock().map(|mut buf| std::mem::take(&mut *buf)).unwrap_or_default()
+}
 
-    match name {
-        "Bash" | "run_shell_command" => {
-            let command = args["command"].as_str().unwrap_or("");
+/// Kill everything the child `pid` started (it leads its own process group);
+/// the caller still kills the child itself
+fn kill_process_group(pid: u32) {
+    #[cfg(unix)]
+    {
+        let _ = Command::new("kill")
+            .args(["-KILL", "--", &format!("-{}", pid)])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status();
+    }
+    #[cfg(not(unix))]
+    let _ = pid;
+}
 
-            if command.is_empty() {
-                return "Error: command is required".to_string();
-            }
+/// Pipe both outputs, no stdin, and make the child lead its own process
+/// group so a timeout can kill whatever it started
+fn prepare_captured(cmd: &mut Command) {
+    use std::process::Stdio;
+    cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+}
 
-            let output = if let Some(cwd) = sandbox_cwd {
-                Command::new("sh")
-                    .arg("-c")
-                    .arg(format!("cd {} && {}", cwd, command))
-                    .output()
-            } else {
-                Command::new("sh").arg("-c").arg(command).output()
-            };
+/// Run `cmd` capturing stdout and stderr, killing it after `timeout`. Once it
+/// has exited, background processes it started don't hold up the result.
+/// Returns the output captured so far and whether the timeout was hit.
+fn output_with_timeout(cmd: Command, timeout: std::time::Duration) -> std::io::Result<(Vec<u8>, Vec<u8>, bool)> {
+    let (output, timed_out) = status_output_with_timeout(cmd, timeout)?;
+    Ok((output.stdout, output.stderr, timed_out))
+}
 
-            match output {
-                Ok(out) => {
-                    let stdout = String::from_utf8_lossy(&out.stdout);
-                    let stderr = String::from_utf8_lossy(&out.stderr);
+/// Like `output_with_timeout`, but also keeps the exit status
+pub fn status_output_with_timeout(mut cmd: Command, timeout: std::time::Duration) -> std::io::Result<(std::process::Output, bool)> {
+    prepare_captured(&mut cmd);
+    let mut child = cmd.spawn()?;
+    let (stdout, stdout_reader) = read_pipe(child.stdout.take());
+    let (stderr, stderr_reader) = read_pipe(child.stderr.take());
+
+    // A timeout too far off to represent is no timeout
+    let deadline = std::time::Instant::now().checked_add(timeout);
+    let mut timed_out = false;
+    let mut exited_at = None;
+    loop {
+        if exited_at.is_none() {
+            if child.try_wait()?.is_some() {
+                exited_at = Some(std::time::Instant::now());
+            } else if deadline.is_some_and(|deadline| std::time::Instant::now() >= deadline) {
+                kill_process_group(child.id());
+                let _ = child.kill();
+                timed_out = true;
+                exited_at = Some(std::time::Instant::now());
+            }
+        }
+        if let Some(exited_at) = exited_at {
+            let drained = stdout_reader.is_finished() && stderr_reader.is_finished();
+            if drained || exited_at.elapsed() >= EXITED_OUTPUT_GRACE {
+                break;
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    let status = child.wait()?;
+    let output = std::process::Output {
+        status,
+        stdout: take_pipe_buffer(&stdout),
+        stderr: take_pipe_buffer(&stderr),
+    };
+    Ok((output, timed_out))
+}
 
-                    let mut result = String::new();
-                    if !stdout.is_empty() {
-                        result.push_str(&stdout);
-                    }
-                    if !stderr.is_empty() {
-                        if !result.is_empty() {
-                            result.push('\n');
+/// Like `output_with_timeout`, but calls `on_line` with each line of stdout
+/// and stderr as it arrives. Two reader tasks send lines over a channel
+/// (tagged with the stream they came from); this loop forwards each one and
+/// keeps the bytes, until both pipes close (or the grace after the command
+/// exits runs out) or the timeout kills the command.
+async fn stream_with_timeout(
+    mut cmd: Command,
+    timeout: std::time::Duration,
+    mut on_line: impl FnMut(String),
+) -> std::io::Result<(Vec<u8>, Vec<u8>, bool)> {
+    use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+    fn forward(reader: impl AsyncBufRead + Unpin + Send + 'static, is_stderr: bool, tx: UnboundedSender<(bool, Vec<u8>)>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut reader = reader;
+            loop {
+                let mut line = Vec::new();
+                match reader.read_until(b'\n', &mut line).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        if tx.send((is_stderr, line)).is_err() {
+                            break;
                         }
-                        result.push_str(&stderr);
-                    }
-                    if result.is_empty() {
-                        "(no output)".to_string()
-                    } else {
-                        result
                     }
                 }
-                Err(e) => format!("Error executing command: {}", e),
             }
+        })
+    }
+
+    prepare_captured(&mut cmd);
+    let mut child = tokio::process::Command::from(cmd).kill_on_drop(true).spawn()?;
+    let (tx, mut rx) = unbounded_channel();
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(forward(BufReader::new(stdout), false, tx.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(forward(BufReader::new(stderr), true, tx.clone()));
+    }
+    drop(tx);
+
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    let deadline = tokio::time::sleep(timeout);
+    let grace = tokio::time::sleep(EXITED_OUTPUT_GRACE);
+    tokio::pin!(deadline, grace);
+    let mut exited = false;
+    let timed_out = loop {
+        tokio::select! {
+            line = rx.recv() => {
+                // Both readers done: the pipes closed
+                let Some((is_stderr, line)) = line else { break false };
+                on_line(String::from_utf8_lossy(&line).trim_end_matches(['\n', '\r']).to_string());
+                let captured = if is_stderr { &mut stderr } else { &mut stdout };
+                captured.extend_from_slice(&line);
+            }
+            _ = child.wait(), if !exited => {
+                // Something it left running in the background may hold the
+                // pipes open for good; read on for a short grace only
+                exited = true;
+                grace.as_mut().reset(tokio::time::Instant::now() + EXITED_OUTPUT_GRACE);
+            }
+            _ = &mut grace, if exited => break false,
+            _ = &mut deadline, if !exited => {
+                if let Some(pid) = child.id() {
+                    kill_process_group(pid);
+                }
+                let _ = child.start_kill();
+                break true;
+            }
+        }
+    };
+    // Keep what was already read, but don't wait on pipes a stray process
+    // may still hold open
+    while let Ok((is_stderr, line)) = rx.try_recv() {
+        let captured = if is_stderr { &mut stderr } else { &mut stdout };
+        captured.extend_from_slice(&line);
+    }
+    readers.iter().for_each(|reader| reader.abort());
+    child.wait().await?;
+    Ok((stdout, stderr, timed_out))
+}
+
+/// Keep the start and end of `text` within `limit` bytes, replacing the
+/// middle with a `[... N bytes truncated ...]` marker
+pub fn truncate_middle(text: &str, limit: usize) -> String {
+    // Room for the marker, whatever the number in it
+    const MARKER_RESERVE: usize = 48;
+    if text.len() <= limit {
+        return text.to_string();
+    }
+    let keep = limit.saturating_sub(MARKER_RESERVE) / 2;
+    let mut head = keep;
+    while !text.is_char_boundary(head) {
+        head -= 1;
+    }
+    let mut tail = text.len() - keep;
+    while !text.is_char_boundary(tail) {
+        tail += 1;
+    }
+    format!("{}\n[... {} bytes truncated ...]\n{}", &text[..head], tail - head, &text[tail..])
+}
+
+/// Combine a command's stdout and stderr, each capped so the whole fits in
+/// `limit` bytes (0 = no cap)
+fn combine_command_output(stdout: &str, stderr: &str, limit: usize) -> String {
+    let cap = |text: &str, share: usize| if limit == 0 { text.to_string() } else { truncate_middle(text, share) };
+    let (stdout, stderr) = if stdout.is_empty() || stderr.is_empty() {
+        (cap(stdout, limit), cap(stderr, limit))
+    } else {
+        // Both present: split the budget (less the joining newline)
+        let half = limit.saturating_sub(1) / 2;
+        (cap(stdout, half), cap(stderr, half))
+    };
+
+    let mut result = stdout;
+    if !stderr.is_empty() {
+        if !result.is_empty() {
+            result.push('\n');
+        }
+        result.push_str(&stderr);
+    }
+    if result.is_empty() {
+        "(no output)".to_string()
+    } else {
+        result
+    }
+}
+
+/// `sh -c <command>`, run in `cwd` when given. The command goes to sh untouched;
+/// splicing it into a `cd` prefix breaks on paths with spaces, quotes or `$`
+pub fn shell_command(command: &str, cwd: Option<&str>) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    if let Some(cwd) = cwd {
+        cmd.current_dir(cwd);
+    }
+    cmd
+}
+
+/// What the builtin tools take from config
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolSettings {
+    pub filter: ToolFilter,
+    /// Cap on Bash output returned to the model, in bytes (0 = no cap)
+    pub bash_output_limit: usize,
+    /// Bash timeout when the call doesn't give one
+    pub bash_timeout_secs: u64,
+    pub diagnostics: crate::diagnostics::DiagnosticsConfig,
+    pub web_search: crate::web_search::WebSearchConfig,
+}
+
+impl Default for ToolSettings {
+    fn default() -> Self {
+        ToolSettings {
+            filter: ToolFilter::default(),
+            bash_output_limit: DEFAULT_BASH_OUTPUT_LIMIT,
+            bash_timeout_secs: DEFAULT_BASH_TIMEOUT_SECS,
+            diagnostics: Default::default(),
+            web_search: Default::default(),
+        }
+    }
+}
+
+impl ToolSettings {
+    /// How long a command may run when the call doesn't say
+    pub fn command_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.bash_timeout_secs.max(1))
+    }
+}
+
+/// `execute_tool_with` under the default settings
+#[cfg(test)]
+pub fn execute_tool(name: &str, arguments: &str, sandbox_cwd: Option<&str>) -> String {
+    execute_tool_with(name, arguments, sandbox_cwd, &ToolSettings::default())
+}
+
+pub fn execute_tool_with(name: &str, arguments: &str, sandbox_cwd: Option<&str>, settings: &ToolSettings) -> String {
+    if let Err(e) = settings.filter.check(name) {
+        return e;
+    }
+
+    // First check if this is a plugin tool
+    if tool_plugins::is_plugin_tool(name) {
+        return tool_plugins::execute_plugin_tool(name, arguments, sandbox_cwd)
+            .unwrap_or_else(|| format!("Error: Plugin tool '{}' failed to execute", name));
+    }
+
+    let args: Value = serde_json::from_str(arguments).unwrap_or(json!({}));
+
+    let Some(target) = single_file_write_target(name, &args) else {
+        return run_builtin_tool(name, &args, sandbox_cwd, settings);
+    };
+    // Retries (or a model repeating itself) mustn't apply the same change twice
+    let key = (canonical_tool_name(name).to_string(), args.to_string());
+    if let Some(previous) = repeated_write(&key) {
+        return format!(
+            "{}\n(This identical {} was already applied to {} this turn and the file hasn't changed since; not applied again.)",
+            previous, key.0, target
+        );
+    }
+    let result = run_builtin_tool(name, &args, sandbox_cwd, settings);
+    if !result.starts_with("Error") {
+        let path = match sandbox_cwd {
+            Some(cwd) if Path::new(target).is_relative() => Path::new(cwd).join(target),
+            _ => Path::new(target).to_path_buf(),
+        };
+        record_applied_write(key, &path, &result);
+    }
+    result
+}
+
+/// The call's `timeout_secs` (capped at `MAX_TIMEOUT_MULTIPLE` times the
+/// configured timeout), or the configured timeout
+fn bash_timeout_secs(args: &Value, settings: &ToolSettings) -> u64 {
+    let default = settings.command_timeout().as_secs();
+    args["timeout_secs"]
+        .as_u64()
+        .filter(|&secs| secs > 0)
+        .map_or(default, |secs| secs.min(default.saturating_mul(MAX_TIMEOUT_MULTIPLE)))
+}
+
+/// The tool result for a finished (or killed) Bash command
+fn bash_result(output: std::io::Result<(Vec<u8>, Vec<u8>, bool)>, timeout_secs: u64, output_limit: usize) -> String {
+    match output {
+        Ok((stdout, stderr, timed_out)) => {
+            let output = combine_command_output(
+                &String::from_utf8_lossy(&stdout),
+                &String::from_utf8_lossy(&stderr),
+                output_limit,
+            );
+            if !timed_out {
+                output
+            } else if output == "(no output)" {
+                format!("Error: command timed out after {}s and was killed", timeout_secs)
+            } else {
+                format!("Error: command timed out after {}s and was killed. Output before that:\n{}", timeout_secs, output)
+            }
+        }
+        Err(e) => format!("Error executing command: {}", e),
+    }
+}
+
+/// Run a Bash call like `execute_tool`, but hand each output line to
+/// `on_line` while the command runs. The returned result is the same as
+/// `execute_tool`'s: the whole (capped) output, for the API.
+pub async fn execute_bash_streaming(name: &str, arguments: &str, sandbox_cwd: Option<&str>, settings: &ToolSettings, on_line: impl FnMut(String)) -> String {
+    if let Err(e) = settings.filter.check(name) {
+        return e;
+    }
+    let args: Value = serde_json::from_str(arguments).unwrap_or(json!({}));
+    let command = args["command"].as_str().unwrap_or("");
+    if command.is_empty() {
+        return "Error: command is required".to_string();
+    }
+    let timeout_secs = bash_timeout_secs(&args, settings);
+    let output = stream_with_timeout(shell_command(command, sandbox_cwd), std::time::Duration::from_secs(timeout_secs), on_line).await;
+    bash_result(output, timeout_secs, settings.bash_output_limit)
+}
+
+fn run_builtin_tool(name: &str, args: &Value, sandbox_cwd: Option<&str>, settings: &ToolSettings) -> String {
+    match canonical_tool_name(name) {
+        "Bash" => {
+            let command = args["command"].as_str().unwrap_or("");
+
+            if command.is_empty() {
+                return "Error: command is required".to_string();
+            }
+
+            let timeout_secs = bash_timeout_secs(args, settings);
+            let output = output_with_timeout(shell_command(command, sandbox_cwd), std::time::Duration::from_secs(timeout_secs));
+            bash_result(output, timeout_secs, settings.bash_output_limit)
         }
 
-        "Read" | "read_file" | "read_lines" => {
+        "Read" => {
             let file_path = args["file_path"].as_str().unwrap_or("");
             let offset = args["offset"].as_u64().map(|n| n as usize);
             let limit = args["limit"].as_u64().map(|n| n as usize);
@@ -567,11 +1519,21 @@ pub fn execute_tool(name: &str, arguments: &str, sandbox_cwd: Option<&str>) -> S
                 }
             }
 
+            let byte_offset = args["byte_offset"].as_u64();
+            let byte_limit = args["byte_limit"].as_u64();
+            if byte_offset.is_some() || byte_limit.is_some() {
+                return read_byte_range(file_path, byte_offset.unwrap_or(0), byte_limit.unwrap_or(DEFAULT_BYTE_LIMIT));
+            }
+
             match fs::read_to_string(file_path) {
                 Ok(content) => {
                     if content.len() > 10_000_000 {
                         return format!("Error: File too large ({} bytes). Use offset and limit for large files.", content.len());
                     }
+                    record_read_hash(file_path, &content);
+                    if crate::notebook::is_notebook(file_path) {
+                        return crate::notebook::render(&content).unwrap_or_else(|e| e);
+                    }
                     let lines: Vec<&str> = content.lines().collect();
                     let total_lines = lines.len();
 
@@ -622,11 +1584,9 @@ pub fn execute_tool(name: &str, arguments: &str, sandbox_cwd: Option<&str>) -> S
             }
         }
 
-        "Edit" | "edit_file" => {
+        "Edit" => {
             let file_path = args["file_path"].as_str().unwrap_or("");
-            let old_string = args["old_string"].as_str().unwrap_or("");
-            let new_string = args["new_string"].as_str().unwrap_or("");
-            let replace_all = args["replace_all"].as_bool().unwrap_or(false);
+            let force = args["force"].as_bool().unwrap_or(false);
 
             if file_path.is_empty() {
                 return "Error: file_path is required".to_string();
@@ -638,11 +1598,21 @@ pub fn execute_tool(name: &str, arguments: &str, sandbox_cwd: Option<&str>) -> S
                 }
             }
 
-            if old_string.is_empty() {
-                return "Error: old_string cannot be empty".to_string();
+            let edits = match edit_specs(args) {
+                Ok(edits) => edits,
+                Err(e) => return e,
+            };
+            let batch = args.get("edits").is_some_and(|e| !e.is_null());
+
+            if let Some(i) = edits.iter().position(|e| e.old_string.is_empty()) {
+                return if batch {
+                    format!("Error: edit {}: old_string cannot be empty", i + 1)
+                } else {
+                    "Error: old_string cannot be empty".to_string()
+                };
             }
 
-            if old_string == new_string {
+            if edits.iter().all(|e| e.old_string == e.new_string) {
                 return "✓ No changes needed - strings are identical".to_string();
             }
 
@@ -651,53 +1621,49 @@ pub fn execute_tool(name: &str, arguments: &str, sandbox_cwd: Option<&str>) -> S
                 // Read the file
                 let content = fs::read_to_string(file_path)?;
 
-                // Count occurrences
-                let count = content.matches(old_string).count();
-
-                if count == 0 {
+                if !force && changed_since_read(file_path, &content) {
                     return Err(std::io::Error::new(
-                        std::io::ErrorKind::NotFound,
+                        std::io::ErrorKind::InvalidData,
                         format!(
-                            "Error: old_string not found in {}\n\n\
-                             The text must match EXACTLY, including:\n\
-                             - All whitespace and indentation\n\
-                             - Line endings\n\
-                             - Any special characters\n\n\
-                             Tip: Copy the exact text from the Read output.",
+                            "Error: {} has changed on disk since it was last Read.\n\n\
+                             Read the file again before editing, or pass force: true to edit anyway.",
                             file_path
                         )
                     ));
                 }
 
-                if count > 1 && !replace_all {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidInput,
-                        format!(
-                            "Error: old_string appears {} times in the file.\n\n\
-                            To fix:\n\
-                            - Include more surrounding context to make old_string unique, OR\n\
-                            - Use replace_all: true to replace all occurrences",
-                            count
-                        )
-                    ));
+                // Apply every edit in memory first, so a failing one leaves the file untouched
+                let mut new_content = content;
+                let mut counts = Vec::with_capacity(edits.len());
+                for (i, edit) in edits.iter().enumerate() {
+                    let (updated, count) = apply_edit(&new_content, edit, file_path).map_err(|e| {
+                        if batch {
+                            std::io::Error::new(e.kind(), format!("Error: edit {} of {} failed, no edits were applied.\n{}", i + 1, edits.len(), e))
+                        } else {
+                            e
+                        }
+                    })?;
+                    new_content = updated;
+                    counts.push(count);
                 }
 
-                // Perform the replacement
-                let new_content = if replace_all {
-                    content.replace(old_string, new_string)
-                } else {
-                    content.replacen(old_string, new_string, 1)
-                };
-
                 // Write the file
                 fs::write(file_path, &new_content)?;
+                record_read_hash(file_path, &new_content);
 
-                Ok(count)
+                Ok(counts)
             }) {
-                Ok(count) => {
-                    let diff = generate_diff_snippet(old_string, new_string);
-                    if replace_all && count > 1 {
-                        format!("{}\n\n{}\n\n✓ Replaced {} occurrences in {}", file_path, diff, count, file_path)
+                Ok(counts) => {
+                    let diff = edits
+                        .iter()
+                        .map(|e| generate_diff_snippet(e.old_string, e.new_string))
+                        .collect::<Vec<_>>()
+                        .join("\n\n");
+                    let total: usize = counts.iter().sum();
+                    if batch {
+                        format!("{}\n\n{}\n\n✓ Applied {} edits ({} replacements) in {}", file_path, diff, edits.len(), total, file_path)
+                    } else if edits[0].replace_all && total > 1 {
+                        format!("{}\n\n{}\n\n✓ Replaced {} occurrences in {}", file_path, diff, total, file_path)
                     } else {
                         format!("{}\n\n{}\n\n✓ Successfully edited", file_path, diff)
                     }
@@ -706,7 +1672,76 @@ pub fn execute_tool(name: &str, arguments: &str, sandbox_cwd: Option<&str>) -> S
             }
         }
 
-        "Write" | "write_file" => {
+        "EditCell" => {
+            let file_path = args["file_path"].as_str().unwrap_or("");
+            let cell_index = args["cell_index"].as_u64();
+            let new_source = args["new_source"].as_str();
+
+            if file_path.is_empty() {
+                return "Error: file_path is required".to_string();
+            }
+            let (Some(cell_index), Some(new_source)) = (cell_index, new_source) else {
+                return "Error: cell_index and new_source are required".to_string();
+            };
+            if !crate::notebook::is_notebook(file_path) {
+                return format!("Error: {} is not a Jupyter notebook (.ipynb). Use Edit instead.", file_path);
+            }
+
+            if let Some(cwd) = sandbox_cwd {
+                if !is_path_in_sandbox(file_path, cwd) {
+                    return format!("Error: Cannot edit files outside of {}", cwd);
+                }
+            }
+
+            match crate::transactions::execute_file_operation(file_path, || {
+                let content = fs::read_to_string(file_path)?;
+                let updated = crate::notebook::set_cell_source(&content, cell_index as usize, new_source)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                fs::write(file_path, &updated)?;
+                record_read_hash(file_path, &updated);
+                Ok(())
+            }) {
+                Ok(_) => format!(
+                    "{}\n\n[cell {}] source replaced ({} lines)\n\n✓ Successfully edited",
+                    file_path,
+                    cell_index,
+                    new_source.lines().count()
+                ),
+                Err(e) => e.to_string(),
+            }
+        }
+
+        "RenameSymbol" => {
+            let old = args["old"].as_str().unwrap_or("");
+            let new = args["new"].as_str().unwrap_or("");
+            let scope = args["glob"].as_str().unwrap_or("**/*");
+
+            if old.is_empty() || new.is_empty() {
+                return "Error: old and new are required".to_string();
+            }
+            if old == new {
+                return "✓ No changes needed - names are identical".to_string();
+            }
+
+            rename_symbol(old, new, scope, sandbox_cwd)
+        }
+
+        "ProjectReplace" => {
+            let pattern = args["pattern"].as_str().unwrap_or("");
+            let scope = args["glob"].as_str().unwrap_or("**/*");
+
+            if pattern.is_empty() {
+                return "Error: pattern is required".to_string();
+            }
+            let Some(replacement) = args["replacement"].as_str() else {
+                return "Error: replacement is required".to_string();
+            };
+            let is_regex = args["regex"].as_bool().unwrap_or(false);
+
+            project_replace(pattern, replacement, is_regex, scope, sandbox_cwd)
+        }
+
+        "Write" => {
             let file_path = args["file_path"].as_str().unwrap_or("");
             let content = args["content"].as_str().unwrap_or("");
 
@@ -730,6 +1765,7 @@ pub fn execute_tool(name: &str, arguments: &str, sandbox_cwd: Option<&str>) -> S
                 }
 
                 fs::write(file_path, content)?;
+                record_read_hash(file_path, content);
                 Ok(())
             }) {
                 Ok(_) => format!("Successfully wrote to {}", file_path),
@@ -737,14 +1773,20 @@ pub fn execute_tool(name: &str, arguments: &str, sandbox_cwd: Option<&str>) -> S
             }
         }
 
-        "Glob" | "glob_files" => {
+        "Glob" => {
             let pattern = args["pattern"].as_str().unwrap_or("");
             let base_path = args["path"].as_str().unwrap_or(".");
+            let sort = args["sort"].as_str().unwrap_or("name");
+            let limit = args["limit"].as_u64().map(|n| n.max(1) as usize);
 
             if pattern.is_empty() {
                 return "Error: pattern is required".to_string();
             }
 
+            if !matches!(sort, "name" | "mtime" | "size") {
+                return format!("Error: unknown sort '{}' (use name, mtime or size)", sort);
+            }
+
             if let Some(cwd) = sandbox_cwd {
                 if !is_path_in_sandbox(base_path, cwd) {
                     return format!("Error: Cannot search outside of {}", cwd);
@@ -780,24 +1822,45 @@ pub fn execute_tool(name: &str, arguments: &str, sandbox_cwd: Option<&str>) -> S
                     }
 
                     results.sort();
+                    match sort {
+                        "mtime" => results.sort_by_cached_key(|p| {
+                            std::cmp::Reverse(fs::metadata(p).and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH))
+                        }),
+                        "size" => results.sort_by_cached_key(|p| std::cmp::Reverse(fs::metadata(p).map(|m| m.len()).unwrap_or(0))),
+                        _ => {}
+                    }
 
                     if results.is_empty() {
-                        "No matching files found".to_string()
-                    } else {
-                        results.join("\n")
+                        return "No matching files found".to_string();
+                    }
+                    match limit {
+                        Some(limit) if results.len() > limit => format!(
+                            "{}\n... {} more files (showing first {})",
+                            results[..limit].join("\n"),
+                            results.len() - limit,
+                            limit
+                        ),
+                        _ => results.join("\n"),
                     }
                 }
                 Err(e) => format!("Error in glob pattern: {}", e),
             }
         }
 
-        "Grep" | "grep" | "search_files" | "search_content" => {
+        "Grep" => {
             let pattern = args["pattern"].as_str()
                 .or_else(|| args["query"].as_str())
                 .unwrap_or("");
             let path = args["path"].as_str().unwrap_or(".");
-            let include = args["include"].as_str();
+            // `include` may be a single glob or a list of globs
+            let include: Vec<&str> = match &args["include"] {
+                Value::String(pattern) => vec![pattern.as_str()],
+                Value::Array(patterns) => patterns.iter().filter_map(|p| p.as_str()).collect(),
+                _ => Vec::new(),
+            };
             let context = args["context_lines"].as_u64().map(|n| n as usize);
+            let numbered = args["numbered"].as_bool().unwrap_or(false);
+            let group_by_file = args["group_by_file"].as_bool().unwrap_or(false) && !numbered;
 
             if pattern.is_empty() {
                 return "Error: pattern is required".to_string();
@@ -812,48 +1875,34 @@ pub fn execute_tool(name: &str, arguments: &str, sandbox_cwd: Option<&str>) -> S
             // Read ignore patterns from .grokignore
             let ignore_patterns = read_grokignore();
 
-            let mut grep_args = vec!["-rn", "--color=never"];
-
-            // Build exclusion args from .grokignore patterns
-            let mut exclude_strings: Vec<String> = Vec::new();
-            for pat in &ignore_patterns {
-                if pat.contains('*') || pat.contains('?') {
-                    // File pattern like *.log
-                    exclude_strings.push(format!("--exclude={}", pat));
-                } else if pat == ".*" {
-                    // Hidden files/dirs
-                    exclude_strings.push("--exclude=.*".to_string());
-                    exclude_strings.push("--exclude-dir=.*".to_string());
-                } else {
-                    // Directory pattern
-                    exclude_strings.push(format!("--exclude-dir={}", pat));
-                }
-            }
-
-            // Convert to &str for grep args
-            for excl in &exclude_strings {
-                grep_args.push(excl.as_str());
-            }
-
-            // Add context lines if specified
-            if let Some(ctx) = context {
-                grep_args.push("-C");
-                // We need to convert to string and keep it alive
-                let ctx_str = ctx.to_string();
-                grep_args.push(Box::leak(ctx_str.into_boxed_str()));
-            }
-
-            if let Some(inc) = include {
-                grep_args.push("--include");
-                grep_args.push(inc);
-            }
-
-            grep_args.push(pattern);
-            grep_args.push(path);
+            let search = GrepSearch {
+                pattern,
+                path,
+                include,
+                // Numbered mode renders its own context from the files, so only the
+                // match locations are needed
+                context: context.filter(|_| !numbered),
+                // NUL after the path separates it unambiguously for the modes that regroup by file
+                null_separated: numbered || group_by_file,
+            };
+            // Without grep on PATH (e.g. Windows), search in-process instead
+            let output = match args["engine"].as_str() {
+                Some("builtin") => search.run_builtin(&ignore_patterns),
+                Some("system") => search.run_system(&ignore_patterns),
+                Some(other) => Err(format!("Error: unknown engine '{}' (expected \"system\" or \"builtin\")", other)),
+                None if find_binary("grep").is_some() => search.run_system(&ignore_patterns),
+                None => search.run_builtin(&ignore_patterns),
+            };
 
-            match Command::new("grep").args(&grep_args).output() {
-                Ok(out) => {
-                    let stdout = String::from_utf8_lossy(&out.stdout);
+            match output {
+                Ok(stdout) => {
+                    let stdout = if numbered && !stdout.is_empty() {
+                        render_numbered_context(&stdout, context.unwrap_or(3))
+                    } else if group_by_file && !stdout.is_empty() {
+                        group_grep_by_file(&stdout, path)
+                    } else {
+                        stdout
+                    };
                     if stdout.is_empty() {
                         "No matches found".to_string()
                     } else {
@@ -873,11 +1922,11 @@ pub fn execute_tool(name: &str, arguments: &str, sandbox_cwd: Option<&str>) -> S
                         }
                     }
                 }
-                Err(e) => format!("Error running grep: {}", e),
+                Err(e) => e,
             }
         }
 
-        "List" | "list_directory" => {
+        "List" => {
             let path = args["path"].as_str().unwrap_or(".");
 
             if let Some(cwd) = sandbox_cwd {
@@ -923,7 +1972,31 @@ pub fn execute_tool(name: &str, arguments: &str, sandbox_cwd: Option<&str>) -> S
             }
         }
 
-        "FileInfo" | "file_info" => {
+        "GitContext" => {
+            let file_path = args["file_path"].as_str().unwrap_or("");
+            if file_path.is_empty() {
+                return "Error: file_path is required".to_string();
+            }
+            if let Some(cwd) = sandbox_cwd {
+                if !is_path_in_sandbox(file_path, cwd) {
+                    return format!("Error: Cannot access files outside of {}", cwd);
+                }
+            }
+            let query = GitContextQuery::from_args(args);
+            git_context(file_path, &query)
+        }
+
+        "Diagnostics" => crate::diagnostics::run(sandbox_cwd, &settings.diagnostics, settings.command_timeout()),
+
+        "EnvInfo" => {
+            let vars: Vec<String> = match args["vars"].as_array() {
+                Some(names) => names.iter().filter_map(|n| n.as_str().map(str::to_string)).collect(),
+                None => DEFAULT_ENV_VARS.iter().map(|n| n.to_string()).collect(),
+            };
+            env_info(sandbox_cwd, &vars)
+        }
+
+        "FileInfo" => {
             let path = args["path"].as_str().unwrap_or("");
 
             if path.is_empty() {
@@ -963,98 +2036,712 @@ pub fn execute_tool(name: &str, arguments: &str, sandbox_cwd: Option<&str>) -> S
             )
         }
 
-        "AskUser" | "ask_multiple_choice" => {
+        "AskUser" => {
             "Tool handled by application".to_string()
         }
 
-        "ConfirmPlan" | "confirm_plan" => {
+        "ConfirmPlan" => {
             "Tool handled by application".to_string()
         }
 
-        "WebSearch" | "web_search" => {
+        "WebSearch" => {
             // This is handled specially in process_conversation for approval
             // But if called directly, execute the search
             let query = args["query"].as_str().unwrap_or("");
             if query.is_empty() {
                 return "Error: query is required".to_string();
             }
-            execute_web_search(query)
+            execute_web_search(query, &settings.web_search)
+        }
+
+        "SearchHistory" => {
+            let query = args["query"].as_str().unwrap_or("").trim();
+            if query.is_empty() {
+                return "Error: query is required".to_string();
+            }
+            let limit = args["limit"].as_u64().unwrap_or(20).max(1) as usize;
+            let matches = crate::persistence::search_sessions(crate::persistence::SESSIONS_DIR, query, limit);
+            crate::persistence::format_session_matches(query, &matches)
         }
 
         _ => format!("Unknown tool: {}", name),
     }
 }
 
-/// Execute a web search using DuckDuckGo
-pub fn execute_web_search(query: &str) -> String {
-    let encoded_query = query.replace(' ', "+");
-    let url = format!("https://html.duckduckgo.com/html/?q={}", encoded_query);
+/// Locate an executable on PATH
+pub fn find_binary(name: &str) -> Option<std::path::PathBuf> {
+    find_binary_in(name, &std::env::var_os("PATH")?)
+}
 
-    // Use curl for simplicity (available on most systems)
-    let output = Command::new("curl")
-        .args(["-s", "-L", "-A", "Mozilla/5.0", &url])
-        .output();
+fn find_binary_in(name: &str, path_var: &std::ffi::OsStr) -> Option<std::path::PathBuf> {
+    std::env::split_paths(path_var).find_map(|dir| {
+        let candidates = [dir.join(name), dir.join(format!("{}.exe", name))];
+        candidates.into_iter().find(|candidate| is_executable(candidate))
+    })
+}
 
-    match output {
-        Ok(out) => {
-            let html = String::from_utf8_lossy(&out.stdout);
-            parse_duckduckgo_results(&html)
-        }
-        Err(e) => format!("Error performing search: {}", e),
-    }
-}
-
-/// Parse DuckDuckGo HTML results
-fn parse_duckduckgo_results(html: &str) -> String {
-    let mut results = Vec::new();
-
-    // Simple parsing - look for result links and snippets
-    // DuckDuckGo HTML has results in <a class="result__a"> tags
-    for line in html.lines() {
-        if line.contains("result__a") && line.contains("href=") {
-            // Extract title from the link text
-            if let Some(start) = line.find('>') {
-                if let Some(end) = line[start..].find("</a>") {
-                    let title = &line[start + 1..start + end];
-                    let title = title.replace("&amp;", "&")
-                        .replace("&lt;", "<")
-                        .replace("&gt;", ">")
-                        .replace("&quot;", "\"");
-                    if !title.trim().is_empty() && !title.contains('<') {
-                        results.push(format!("• {}", title.trim()));
-                    }
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Actionable error for a tool that depends on a missing program
+fn missing_binary_error(name: &str, hint: &str) -> String {
+    format!("Error: {} not found on PATH; {}", name, hint)
+}
+
+/// Environment variables EnvInfo shows when none are requested
+const DEFAULT_ENV_VARS: &[&str] = &[
+    "SHELL", "TERM", "LANG", "EDITOR", "VIRTUAL_ENV", "CONDA_DEFAULT_ENV",
+    "RUSTUP_TOOLCHAIN", "CARGO_HOME", "GOPATH", "JAVA_HOME", "NODE_ENV", "CI",
+];
+
+/// Every variable EnvInfo will show; anything else is withheld, since there
+/// is no telling what an arbitrary name holds
+const SAFE_ENV_VARS: &[&str] = &[
+    "SHELL", "TERM", "LANG", "LC_ALL", "TZ", "EDITOR", "VISUAL", "PATH", "HOME", "USER",
+    "VIRTUAL_ENV", "CONDA_DEFAULT_ENV", "PYTHONPATH", "RUSTUP_TOOLCHAIN", "RUSTUP_HOME",
+    "CARGO_HOME", "CARGO_TARGET_DIR", "GOPATH", "GOROOT", "JAVA_HOME", "NODE_ENV", "CI",
+];
+
+lazy_static::lazy_static! {
+    /// Toolchain versions, detected once per run (each probe spawns a process)
+    static ref TOOL_VERSIONS: Vec<(&'static str, String)> = [
+        ("rustc", "--version"),
+        ("cargo", "--version"),
+        ("node", "--version"),
+        ("npm", "--version"),
+        ("python3", "--version"),
+        ("go", "version"),
+        ("git", "--version"),
+    ]
+    .iter()
+    .filter_map(|(tool, flag)| {
+        let out = Command::new(tool).arg(flag).output().ok()?;
+        let text = if out.stdout.is_empty() { out.stderr } else { out.stdout };
+        let version = String::from_utf8_lossy(&text).lines().next()?.trim().to_string();
+        (out.status.success() && !version.is_empty()).then_some((*tool, version))
+    })
+    .collect();
+}
+
+/// Read-only summary of the environment for the model
+fn env_info(sandbox_cwd: Option<&str>, vars: &[String]) -> String {
+    let cwd = sandbox_cwd.map(str::to_string).unwrap_or_else(|| {
+        std::env::current_dir()
+            .map(|d| d.display().to_string())
+            .unwrap_or_else(|_| ".".to_string())
+    });
+
+    let mut result = format!(
+        "OS: {} ({})\nArch: {}\nWorking directory: {}\n",
+        std::env::consts::OS,
+        std::env::consts::FAMILY,
+        std::env::consts::ARCH,
+        cwd
+    );
+
+    let projects: Vec<String> = crate::project::detect(Path::new(&cwd))
+        .map(|kind| format!("{} ({})", kind.name, kind.marker))
+        .collect();
+    if projects.is_empty() {
+        result.push_str("Project: none detected\n");
+    } else {
+        result.push_str(&format!("Project: {}\n", projects.join(", ")));
+    }
+
+    result.push_str("\nTools:\n");
+    if TOOL_VERSIONS.is_empty() {
+        result.push_str("  (none detected)\n");
+    }
+    for (tool, version) in TOOL_VERSIONS.iter() {
+        result.push_str(&format!("  {}: {}\n", tool, version));
+    }
+
+    if !vars.is_empty() {
+        result.push_str("\nEnvironment:\n");
+        for name in vars {
+            if !SAFE_ENV_VARS.contains(&name.as_str()) {
+                result.push_str(&format!("  {} (withheld: not a known-safe variable)\n", name));
+            } else {
+                match std::env::var(name) {
+                    Ok(value) => result.push_str(&format!("  {}={}\n", name, value)),
+                    Err(_) => result.push_str(&format!("  {} (not set)\n", name)),
                 }
             }
         }
-        if line.contains("result__snippet") {
-            // Extract snippet text
-            if let Some(start) = line.find('>') {
-                if let Some(end) = line[start..].find("</") {
-                    let snippet = &line[start + 1..start + end];
-                    let snippet = snippet.replace("&amp;", "&")
-                        .replace("&lt;", "<")
-                        .replace("&gt;", ">")
-                        .replace("&quot;", "\"")
-                        .replace("<b>", "")
-                        .replace("</b>", "");
-                    if !snippet.trim().is_empty() && results.len() > 0 {
-                        results.push(format!("  {}", snippet.trim()));
-                        results.push(String::new());
+    }
+
+    result.trim_end().to_string()
+}
+
+/// Parsed GitContext arguments
+struct GitContextQuery {
+    max_commits: u64,
+    blame: Option<(u64, u64)>,
+}
+
+impl GitContextQuery {
+    fn from_args(args: &Value) -> Self {
+        let blame = args["blame_start"].as_u64().map(|start| {
+            let start = start.max(1);
+            (start, args["blame_end"].as_u64().unwrap_or(start).max(start))
+        });
+        GitContextQuery {
+            max_commits: args["max_commits"].as_u64().unwrap_or(10).clamp(1, 100),
+            blame,
+        }
+    }
+}
+
+/// The git commands GitContext runs, for approval prompts and the allowlist
+pub fn git_context_command(args_json: &str) -> String {
+    let args: Value = serde_json::from_str(args_json).unwrap_or(json!({}));
+    let file_path = args["file_path"].as_str().unwrap_or("");
+    let query = GitContextQuery::from_args(&args);
+    let mut command = format!("git log -n {} -- {}", query.max_commits, file_path);
+    if let Some((start, end)) = query.blame {
+        command.push_str(&format!(" && git blame -L {},{} -- {}", start, end, file_path));
+    }
+    command
+}
+
+fn run_git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    match Command::new("git").arg("-C").arg(dir).args(args).output() {
+        Ok(out) if out.status.success() => Ok(String::from_utf8_lossy(&out.stdout).trim_end().to_string()),
+        Ok(out) => Err(String::from_utf8_lossy(&out.stderr).trim().to_string()),
+        Err(e) => Err(format!("could not run git: {}", e)),
+    }
+}
+
+/// Recent commits (and optional blame) for a file, run from the file's own repository
+fn git_context(file_path: &str, query: &GitContextQuery) -> String {
+    if find_binary("git").is_none() {
+        return missing_binary_error("git", "install git to use GitContext");
+    }
+    let path = Path::new(file_path);
+    if !path.is_file() {
+        return format!("Error: {} is not a file", file_path);
+    }
+    let file_name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name,
+        None => return format!("Error: invalid path {}", file_path),
+    };
+    let dir = match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        Some(parent) => parent,
+        None => Path::new("."),
+    };
+
+    if let Err(e) = run_git(dir, &["rev-parse", "--show-toplevel"]) {
+        return format!("Error: {} is not in a git repository ({})", file_path, e);
+    }
+
+    let max = query.max_commits.to_string();
+    let log = match run_git(dir, &["log", "-n", &max, "--date=short", "--format=%h %ad %an: %s", "--", file_name]) {
+        Ok(log) if log.is_empty() => return format!("Error: {} has no git history (is it tracked?)", file_path),
+        Ok(log) => log,
+        Err(e) => return format!("Error running git log: {}", e),
+    };
+
+    let mut result = format!("Recent commits for {}:\n{}", file_path, log);
+
+    if let Some((start, end)) = query.blame {
+        let range = format!("{},{}", start, end);
+        match run_git(dir, &["blame", "--date=short", "-L", &range, "--", file_name]) {
+            Ok(blame) => result.push_str(&format!("\n\nBlame lines {}-{}:\n{}", start, end, blame)),
+            Err(e) => result.push_str(&format!("\n\nBlame failed: {}", e)),
+        }
+    }
+
+    result
+}
+
+/// A Grep tool search, run either by the system `grep` or in-process with the
+/// `regex` crate. Both produce `grep -rn` output (`path:line:text`, context
+/// lines as `path-line-text`, `--` between context groups, `-Z` when
+/// `null_separated`), so everything after the search is shared.
+struct GrepSearch<'a> {
+    pattern: &'a str,
+    path: &'a str,
+    include: Vec<&'a str>,
+    context: Option<usize>,
+    null_separated: bool,
+}
+
+impl GrepSearch<'_> {
+    fn run_system(&self, ignore_patterns: &[String]) -> Result<String, String> {
+        if find_binary("grep").is_none() {
+            return Err(missing_binary_error("grep", "install grep, or pass engine: \"builtin\""));
+        }
+
+        let mut grep_args: Vec<String> = vec!["-rn".to_string(), "--color=never".to_string()];
+
+        // Includes must come before any --exclude: grep only skips files
+        // matching no filter when the first filter given is an --include
+        grep_args.extend(self.include.iter().map(|inc| format!("--include={}", inc)));
+
+        // Build exclusion args from .grokignore patterns
+        for pat in ignore_patterns {
+            if pat.contains('*') || pat.contains('?') {
+                // File pattern like *.log
+                grep_args.push(format!("--exclude={}", pat));
+            } else if pat == ".*" {
+                // Hidden files/dirs
+                grep_args.push("--exclude=.*".to_string());
+                grep_args.push("--exclude-dir=.*".to_string());
+            } else {
+                // Directory pattern
+                grep_args.push(format!("--exclude-dir={}", pat));
+            }
+        }
+
+        if self.null_separated {
+            grep_args.push("-Z".to_string());
+        }
+        if let Some(ctx) = self.context {
+            grep_args.push("-C".to_string());
+            grep_args.push(ctx.to_string());
+        }
+        grep_args.push(self.pattern.to_string());
+        grep_args.push(self.path.to_string());
+
+        match Command::new("grep").args(&grep_args).output() {
+            Ok(out) => Ok(String::from_utf8_lossy(&out.stdout).into_owned()),
+            Err(e) => Err(format!("Error running grep: {}", e)),
+        }
+    }
+
+    /// Same output as `run_system`, with Rust regex syntax, files in sorted
+    /// order and non-UTF-8 (binary) files skipped
+    fn run_builtin(&self, ignore_patterns: &[String]) -> Result<String, String> {
+        let regex = regex::Regex::new(self.pattern).map_err(|e| format!("Error: invalid pattern: {}", e))?;
+        let include: Vec<glob::Pattern> = self.include.iter().filter_map(|inc| glob::Pattern::new(inc).ok()).collect();
+        let root = Path::new(self.path);
+        let (files, multi_file) = if root.is_dir() {
+            let mut files = Vec::new();
+            walk_files(root, root, &include, ignore_patterns, &mut files);
+            (files, true)
+        } else if root.is_file() {
+            (vec![root.to_path_buf()], false)
+        } else {
+            return Err(format!("Error: {} does not exist", self.path));
+        };
+
+        let mut output = String::new();
+        let mut any_group = false;
+        for file in files {
+            let Ok(content) = fs::read_to_string(&file) else { continue };
+            let lines: Vec<&str> = content.lines().collect();
+            let matches: Vec<usize> = lines.iter().enumerate().filter(|(_, l)| regex.is_match(l)).map(|(i, _)| i).collect();
+            if matches.is_empty() {
+                continue;
+            }
+
+            // Lines to print, as ranges of a match's context merged when they touch
+            let context = self.context.unwrap_or(0);
+            let mut ranges: Vec<(usize, usize)> = Vec::new();
+            for &m in &matches {
+                let (start, end) = (m.saturating_sub(context), (m + context).min(lines.len() - 1));
+                match ranges.last_mut() {
+                    Some((_, last_end)) if self.context.is_some() && start <= *last_end + 1 => *last_end = end,
+                    _ => ranges.push((start, end)),
+                }
+            }
+
+            let name = file.to_string_lossy();
+            for (start, end) in ranges {
+                if self.context.is_some() && any_group {
+                    output.push_str("--\n");
+                }
+                any_group = true;
+                for (i, line) in lines.iter().enumerate().take(end + 1).skip(start) {
+                    let marker = if matches.binary_search(&i).is_ok() { ':' } else { '-' };
+                    if multi_file {
+                        output.push_str(&name);
+                        output.push(if self.null_separated { '\0' } else { marker });
                     }
+                    output.push_str(&format!("{}{}{}\n", i + 1, marker, line));
                 }
             }
         }
+        Ok(output)
+    }
+}
+
+/// Files under `dir` (sorted, recursing into subdirectories but not following
+/// symlinks) whose name matches one of `include` (any if empty), skipping
+/// paths .grokignore excludes relative to `root`
+fn walk_files(root: &Path, dir: &Path, include: &[glob::Pattern], ignore_patterns: &[String], files: &mut Vec<std::path::PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let mut paths: Vec<std::path::PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    paths.sort();
+    for path in paths {
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        if should_ignore(&relative.to_string_lossy(), ignore_patterns) {
+            continue;
+        }
+        let Ok(meta) = fs::symlink_metadata(&path) else { continue };
+        if meta.is_dir() {
+            walk_files(root, &path, include, ignore_patterns, files);
+        } else if meta.is_file() {
+            let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            if include.is_empty() || include.iter().any(|p| p.matches(&name)) {
+                files.push(path);
+            }
+        }
+    }
+}
+
+/// Turn `grep -Z` output into one header per file with its lines indented below.
+/// A single-file search has no path in the output, so `path` names it
+fn group_grep_by_file(grep_output: &str, path: &str) -> String {
+    let mut result = String::new();
+    let mut current: Option<&str> = None;
+    let mut separator = false;
+    for line in grep_output.lines() {
+        // Context groups are split by "--"; only keep it between groups of the same file
+        if line == "--" {
+            separator = true;
+            continue;
+        }
+        let (file, rest) = line.split_once('\0').unwrap_or((path, line));
+        if current != Some(file) {
+            result.push_str(file);
+            result.push('\n');
+            current = Some(file);
+        } else if separator {
+            result.push_str("  --\n");
+        }
+        separator = false;
+        result.push_str("  ");
+        result.push_str(rest);
+        result.push('\n');
+    }
+    result
+}
 
-        if results.len() >= 15 { // Limit to ~5 results with snippets
-            break;
+/// Turn `grep -rnZ` output into per-file blocks of matches plus `context`
+/// surrounding lines, numbered the same way as Read output.
+fn render_numbered_context(grep_output: &str, context: usize) -> String {
+    // Collect match line numbers per file, preserving grep's file order
+    let mut files: Vec<(String, Vec<usize>)> = Vec::new();
+    for line in grep_output.lines() {
+        let Some((path, rest)) = line.split_once('\0') else { continue };
+        let Some(line_num) = rest.split_once(':').and_then(|(n, _)| n.parse::<usize>().ok()) else { continue };
+        match files.last_mut() {
+            Some((last, nums)) if last == path => nums.push(line_num),
+            _ => files.push((path.to_string(), vec![line_num])),
         }
     }
 
-    if results.is_empty() {
-        "No results found or unable to parse search results.".to_string()
-    } else {
-        results.join("\n")
+    let mut result = String::new();
+    for (path, matches) in files {
+        let content = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        let lines: Vec<&str> = content.lines().collect();
+        let width = lines.len().to_string().len().max(4);
+
+        // Merge overlapping context windows into ranges
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for &m in &matches {
+            let start = m.saturating_sub(context).max(1);
+            let end = (m + context).min(lines.len());
+            match ranges.last_mut() {
+                Some((_, last_end)) if start <= *last_end + 1 => *last_end = (*last_end).max(end),
+                _ => ranges.push((start, end)),
+            }
+        }
+
+        let match_list: Vec<String> = matches.iter().map(|m| m.to_string()).collect();
+        result.push_str(&format!("{} (matches on lines {})\n", path, match_list.join(", ")));
+        for (i, (start, end)) in ranges.iter().enumerate() {
+            if i > 0 {
+                result.push_str(&format!("{:>width$}\n", "...", width = width));
+            }
+            for line_num in *start..=*end {
+                result.push_str(&format!("{:>width$}\t{}\n", line_num, lines[line_num - 1], width = width));
+            }
+        }
+        result.push('\n');
+    }
+
+    result
+}
+
+/// Default and maximum window for Read's byte mode
+const DEFAULT_BYTE_LIMIT: u64 = 4096;
+const MAX_BYTE_LIMIT: u64 = 1024 * 1024;
+/// Bytes shown in the hex dump when a window isn't text
+const HEX_DUMP_BYTES: usize = 512;
+
+/// Read `limit` raw bytes from `offset`: as text if they're valid UTF-8,
+/// otherwise as a hex dump of the start of the window
+fn read_byte_range(file_path: &str, offset: u64, limit: u64) -> String {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file = match fs::File::open(file_path) {
+        Ok(f) => f,
+        Err(e) => return format!("Error reading file: {}", e),
+    };
+    let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+    if offset >= size && size > 0 {
+        return format!("Error: byte_offset {} exceeds file size ({} bytes)", offset, size);
+    }
+
+    let limit = limit.clamp(1, MAX_BYTE_LIMIT);
+    let mut buf = Vec::new();
+    let read = file
+        .seek(SeekFrom::Start(offset))
+        .and_then(|_| file.take(limit).read_to_end(&mut buf));
+    if let Err(e) = read {
+        return format!("Error reading file: {}", e);
+    }
+
+    match utf8_window(&buf) {
+        Some((start, end)) => {
+            let text = std::str::from_utf8(&buf[start..end]).unwrap_or_default();
+            format!("[bytes {}-{} of {}]\n{}", offset + start as u64, offset + end as u64, size, text)
+        }
+        None => {
+            let header = format!("[bytes {}-{} of {}]", offset, offset + buf.len() as u64, size);
+            let mut dump = format!("{} not valid UTF-8, showing hex:\n", header);
+            for (row, chunk) in buf.chunks(16).take(HEX_DUMP_BYTES / 16).enumerate() {
+                let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+                let ascii: String = chunk
+                    .iter()
+                    .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                    .collect();
+                dump.push_str(&format!("{:08x}  {:<47}  {}\n", offset as usize + row * 16, hex.join(" "), ascii));
+            }
+            if buf.len() > HEX_DUMP_BYTES {
+                dump.push_str(&format!("... {} more bytes not shown", buf.len() - HEX_DUMP_BYTES));
+            }
+            dump.trim_end().to_string()
+        }
+    }
+}
+
+/// The part of a byte window that is whole UTF-8 text, dropping a character
+/// cut in two at either edge. None if invalid bytes remain: the data is binary
+fn utf8_window(buf: &[u8]) -> Option<(usize, usize)> {
+    // At most three continuation bytes belong to a character that began before the window
+    let start = buf.iter().take(3).take_while(|&&b| b & 0xC0 == 0x80).count();
+    match std::str::from_utf8(&buf[start..]) {
+        Ok(_) => Some((start, buf.len())),
+        // error_len is None when the input just ends mid-character
+        Err(e) if e.error_len().is_none() => Some((start, start + e.valid_up_to())),
+        Err(_) => None,
+    }
+}
+
+/// A pending whole-file rewrite: (path, original, updated, replacement count)
+type FileChange = (String, String, String, usize);
+
+/// Compute the rewrite of every file matching `scope` that `re` matches, without
+/// writing anything. With `expand`, `$1`-style group references in `replacement`
+/// are expanded; otherwise it is inserted literally.
+fn plan_replacements(
+    re: &regex::Regex,
+    replacement: &str,
+    expand: bool,
+    scope: &str,
+    sandbox_cwd: Option<&str>,
+) -> Result<Vec<FileChange>, String> {
+    let paths = glob(scope).map_err(|e| format!("Error in glob pattern: {}", e))?;
+
+    let ignore_patterns = read_grokignore();
+    let mut changes: Vec<FileChange> = Vec::new();
+
+    for entry in paths.flatten() {
+        let path_str = match entry.to_str() {
+            Some(p) => p.to_string(),
+            None => continue,
+        };
+        if !entry.is_file() || should_ignore(&path_str, &ignore_patterns) {
+            continue;
+        }
+        if let Some(cwd) = sandbox_cwd {
+            if !is_path_in_sandbox(&path_str, cwd) {
+                continue;
+            }
+        }
+        // Skip binary or unreadable files
+        let content = match fs::read_to_string(&entry) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        let count = re.find_iter(&content).count();
+        if count > 0 {
+            let updated = if expand {
+                re.replace_all(&content, replacement).to_string()
+            } else {
+                re.replace_all(&content, regex::NoExpand(replacement)).to_string()
+            };
+            changes.push((path_str, content, updated, count));
+        }
     }
+
+    Ok(changes)
+}
+
+/// Write every change through the transaction manager, all-or-nothing
+fn apply_changes(changes: &[FileChange]) -> Result<(), String> {
+    apply_changes_with(changes, |path, updated| {
+        crate::transactions::execute_file_operation(path, || {
+            fs::write(path, updated)?;
+            record_read_hash(path, updated);
+            Ok(())
+        })
+    })
+}
+
+/// Write every change with `write`; if one fails, files already written are
+/// restored to their original contents before the error is returned
+fn apply_changes_with(
+    changes: &[FileChange],
+    mut write: impl FnMut(&str, &str) -> std::io::Result<()>,
+) -> Result<(), String> {
+    for (idx, (path, _, updated, _)) in changes.iter().enumerate() {
+        if let Err(e) = write(path, updated) {
+            for (done_path, original, _, _) in &changes[..idx] {
+                fs::write(done_path, original).ok();
+            }
+            return Err(format!("Error writing {}: {}", path, e));
+        }
+    }
+    Ok(())
+}
+
+/// Matches `symbol` only where neither neighbouring character is a word
+/// character. `\b` checks that when the symbol's edge character is itself a
+/// word character; next to a non-word edge (`$var`, `op!`) `\B` is the check.
+fn whole_word_regex(symbol: &str) -> Result<regex::Regex, regex::Error> {
+    let boundary = |edge: Option<char>| match edge {
+        Some(c) if c.is_alphanumeric() || c == '_' => r"\b",
+        _ => r"\B",
+    };
+    regex::Regex::new(&format!(
+        "{}{}{}",
+        boundary(symbol.chars().next()),
+        regex::escape(symbol),
+        boundary(symbol.chars().last())
+    ))
+}
+
+/// Whole-word rename of `old` to `new` across files matching `scope`.
+/// New contents are computed for every file before anything is written; if a
+/// write fails, files already written are restored so the rename is all-or-nothing.
+fn rename_symbol(old: &str, new: &str, scope: &str, sandbox_cwd: Option<&str>) -> String {
+    let word = match whole_word_regex(old) {
+        Ok(re) => re,
+        Err(e) => return format!("Error: invalid symbol: {}", e),
+    };
+
+    let changes = match plan_replacements(&word, new, false, scope, sandbox_cwd) {
+        Ok(changes) => changes,
+        Err(e) => return e,
+    };
+    if changes.is_empty() {
+        return format!("No occurrences of '{}' found in {}", old, scope);
+    }
+
+    if let Err(e) = apply_changes(&changes) {
+        return format!("{} (rename rolled back, no files changed)", e);
+    }
+
+    let total: usize = changes.iter().map(|(_, _, _, count)| count).sum();
+    let mut summary = format!("Renamed '{}' → '{}' in {} file(s):\n", old, new, changes.len());
+    for (path, _, _, count) in &changes {
+        summary.push_str(&format!("  {}: {} occurrence(s)\n", path, count));
+    }
+    summary.push_str(&format!("\n✓ Replaced {} occurrences", total));
+    summary
+}
+
+/// Matcher for ProjectReplace: `pattern` is escaped unless `is_regex`
+fn project_replace_regex(pattern: &str, is_regex: bool) -> Result<regex::Regex, String> {
+    let source = if is_regex { pattern.to_string() } else { regex::escape(pattern) };
+    regex::Regex::new(&source).map_err(|e| format!("Error: invalid regex: {}", e))
+}
+
+/// Replace `pattern` with `replacement` across files matching `scope`, all-or-nothing
+fn project_replace(
+    pattern: &str,
+    replacement: &str,
+    is_regex: bool,
+    scope: &str,
+    sandbox_cwd: Option<&str>,
+) -> String {
+    let re = match project_replace_regex(pattern, is_regex) {
+        Ok(re) => re,
+        Err(e) => return e,
+    };
+    let changes = match plan_replacements(&re, replacement, is_regex, scope, sandbox_cwd) {
+        Ok(changes) => changes,
+        Err(e) => return e,
+    };
+    if changes.is_empty() {
+        return format!("No matches for '{}' found in {}", pattern, scope);
+    }
+
+    if let Err(e) = apply_changes(&changes) {
+        return format!("{} (replace rolled back, no files changed)", e);
+    }
+
+    let total: usize = changes.iter().map(|(_, _, _, count)| count).sum();
+    let mut summary = format!("Replaced '{}' → '{}' in {} file(s):\n", pattern, replacement, changes.len());
+    for (path, _, _, count) in &changes {
+        summary.push_str(&format!("  {}: {} replacement(s)\n", path, count));
+    }
+    summary.push_str(&format!("\n✓ Replaced {} occurrences", total));
+    summary
+}
+
+/// Dry run of ProjectReplace: which files would change and how many matches each has
+fn preview_project_replace(
+    pattern: &str,
+    replacement: &str,
+    is_regex: bool,
+    scope: &str,
+    sandbox_cwd: Option<&str>,
+) -> String {
+    let re = match project_replace_regex(pattern, is_regex) {
+        Ok(re) => re,
+        Err(e) => return e,
+    };
+    let changes = match plan_replacements(&re, replacement, is_regex, scope, sandbox_cwd) {
+        Ok(changes) => changes,
+        Err(e) => return e,
+    };
+    let kind = if is_regex { "regex" } else { "literal" };
+    let mut preview = format!(
+        "Dry run - ProjectReplace {} '{}' → '{}' in files matching {}:",
+        kind, pattern, replacement, scope
+    );
+    if changes.is_empty() {
+        preview.push_str("\n  no matches");
+    }
+    for (path, original, updated, count) in &changes {
+        preview.push_str(&format!("\n  {}: {} replacement(s)", path, count));
+        // Show the first changed line of each file
+        if let Some((old_line, new_line)) = original.lines().zip(updated.lines()).find(|(a, b)| a != b) {
+            preview.push_str(&format!("\n    -  {}\n    +  {}", old_line.trim(), new_line.trim()));
+        }
+    }
+    preview
+}
+
+/// Execute a web search with the configured backend
+pub fn execute_web_search(query: &str, config: &crate::web_search::WebSearchConfig) -> String {
+    crate::web_search::search(query, config)
 }
 
 #[cfg(test)]
@@ -1079,6 +2766,135 @@ mod tests {
         assert!(result.contains("line 2"));
     }
 
+    #[test]
+    fn test_aliases_behave_like_their_tool() {
+        for (alias, tool) in TOOL_ALIASES {
+            assert_eq!(canonical_tool_name(alias), *tool);
+            assert_eq!(is_mutating_tool(alias), is_mutating_tool(tool), "{}", alias);
+            assert_eq!(is_read_only_tool(alias), is_read_only_tool(tool), "{}", alias);
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("notes.txt");
+        fs::write(&file_path, "first\nsecond\n").unwrap();
+        let args = json!({"file_path": file_path.to_str().unwrap()}).to_string();
+        assert_eq!(execute_tool("read_lines", &args, None), execute_tool("Read", &args, None));
+    }
+
+    #[test]
+    fn test_bash_in_sandbox_keeps_quotes_and_dollars() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        // A sandbox path that a `cd` prefix would have to quote
+        let sandbox = temp_dir.path().join("it's $HOME");
+        fs::create_dir(&sandbox).unwrap();
+        fs::write(sandbox.join("price.txt"), "5\n").unwrap();
+
+        let command = "x='single quoted'; echo \"$x costs \\$$(cat price.txt)\"\ncat <<'EOF'\n$PATH stays literal\nEOF";
+        let args = json!({"command": command}).to_string();
+        let result = execute_tool("Bash", &args, Some(sandbox.to_str().unwrap()));
+        assert_eq!(result, "single quoted costs $5\n$PATH stays literal\n");
+    }
+
+    #[test]
+    fn test_bash_output_is_capped_in_the_middle() {
+        // seq 1 20000 prints about 108KB
+        let result = execute_tool("Bash", &json!({"command": "seq 1 20000"}).to_string(), None);
+        assert!(result.len() <= DEFAULT_BASH_OUTPUT_LIMIT, "{} bytes", result.len());
+        assert!(result.contains(" bytes truncated ...]"));
+        assert!(result.starts_with("1\n2\n3\n"));
+        assert!(result.trim_end().ends_with("19999\n20000"));
+        // The configured limit applies, and 0 turns the cap off
+        let uncapped = ToolSettings { bash_output_limit: 0, ..ToolSettings::default() };
+        let result = execute_tool_with("Bash", &json!({"command": "seq 1 20000"}).to_string(), None, &uncapped);
+        assert!(!result.contains("truncated") && result.len() > DEFAULT_BASH_OUTPUT_LIMIT);
+
+        // Each stream gets its share, and small output is untouched
+        let big = "x".repeat(1000);
+        let combined = combine_command_output(&big, &big, 400);
+        assert!(combined.len() <= 400, "{} bytes", combined.len());
+        assert_eq!(combined.matches("bytes truncated").count(), 2);
+        assert_eq!(combine_command_output("ok\n", "", 400), "ok\n");
+        assert_eq!(combine_command_output(&big, "", 0), big);
+
+        // Cuts land on character boundaries
+        let accents = "é".repeat(100);
+        let cut = truncate_middle(&accents, 55);
+        assert!(cut.starts_with('é') && cut.ends_with('é'));
+        assert!(cut.contains("[... 196 bytes truncated ...]"), "{}", cut);
+    }
+
+    #[test]
+    fn test_bash_timeout_kills_command_and_keeps_partial_output() {
+        let start = std::time::Instant::now();
+        let result = execute_tool("Bash", &json!({"command": "sleep 5", "timeout_secs": 1}).to_string(), None);
+        assert_eq!(result, "Error: command timed out after 1s and was killed");
+        assert!(start.elapsed() < std::time::Duration::from_secs(4), "took {:?}", start.elapsed());
+
+        let result = execute_tool("Bash", &json!({"command": "echo started; sleep 5", "timeout_secs": 1}).to_string(), None);
+        assert!(result.starts_with("Error: command timed out after 1s"), "{}", result);
+        assert!(result.ends_with("started\n"), "{}", result);
+
+        // A background process holding the output open doesn't hold up the
+        // result once the shell is done, and isn't a timeout
+        let start = std::time::Instant::now();
+        let result = execute_tool("Bash", &json!({"command": "sleep 5 & echo spawned", "timeout_secs": 30}).to_string(), None);
+        assert_eq!(result, "spawned\n");
+        assert!(start.elapsed() < std::time::Duration::from_secs(4), "took {:?}", start.elapsed());
+
+        // A call can raise the timeout, but only so far
+        let settings = ToolSettings::default();
+        assert_eq!(bash_timeout_secs(&json!({}), &settings), DEFAULT_BASH_TIMEOUT_SECS);
+        assert_eq!(bash_timeout_secs(&json!({"timeout_secs": 600}), &settings), 600);
+        assert_eq!(bash_timeout_secs(&json!({"timeout_secs": u64::MAX}), &settings), DEFAULT_BASH_TIMEOUT_SECS * MAX_TIMEOUT_MULTIPLE);
+    }
+
+    #[tokio::test]
+    async fn test_bash_streaming_sends_lines_as_printed_and_returns_all_output() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let sandbox = temp_dir.path().canonicalize().unwrap();
+        let args = json!({"command": "echo first; sleep 1; echo oops >&2; sleep 0.2; pwd -P"}).to_string();
+
+        let settings = ToolSettings::default();
+        let mut lines = Vec::new();
+        let result = execute_bash_streaming("Bash", &args, Some(sandbox.to_str().unwrap()), &settings, |line| {
+            lines.push((line, std::time::Instant::now()));
+        })
+        .await;
+        let done = std::time::Instant::now();
+
+        let texts: Vec<&str> = lines.iter().map(|(line, _)| line.as_str()).collect();
+        assert_eq!(texts, ["first", "oops", sandbox.to_str().unwrap()]);
+        // "first" arrived well before the command finished
+        assert!(done - lines[0].1 >= std::time::Duration::from_millis(500));
+        // The result is what execute_tool returns: stdout, then stderr
+        assert_eq!(result, format!("first\n{}\n\noops\n", sandbox.to_str().unwrap()));
+
+        let result = execute_bash_streaming("Bash", &json!({"command": "echo started; sleep 5", "timeout_secs": 1}).to_string(), None, &settings, |_| {}).await;
+        assert!(result.starts_with("Error: command timed out after 1s"), "{}", result);
+        assert!(result.ends_with("started\n"), "{}", result);
+
+        // A background process keeping the pipes open doesn't hold it up
+        let start = std::time::Instant::now();
+        let result = execute_bash_streaming("Bash", &json!({"command": "sleep 5 & echo spawned", "timeout_secs": 30}).to_string(), None, &settings, |_| {}).await;
+        assert_eq!(result, "spawned\n");
+        assert!(start.elapsed() < std::time::Duration::from_secs(4), "took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn test_bash_runs_in_sandbox_dir() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let sandbox = temp_dir.path().canonicalize().unwrap();
+        let args = json!({"command": "pwd -P"}).to_string();
+
+        let result = execute_tool("Bash", &args, Some(sandbox.to_str().unwrap()));
+        assert_eq!(result.trim_end(), sandbox.to_str().unwrap());
+
+        // The child gets the directory itself, not a `cd` in its command line
+        let cmd = shell_command("pwd", Some(sandbox.to_str().unwrap()));
+        assert_eq!(cmd.get_current_dir(), Some(sandbox.as_path()));
+        assert_eq!(cmd.get_args().collect::<Vec<_>>(), ["-c", "pwd"]);
+    }
+
     #[test]
     fn test_read_with_offset_limit() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -1120,6 +2936,69 @@ mod tests {
         assert!(!content.contains("foo bar"));
     }
 
+    #[test]
+    fn test_retried_edit_is_applied_once() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("retry.txt");
+        fs::write(&file_path, "let total = sum;\n").unwrap();
+
+        // Not idempotent: applying it twice would give "sum + tax + tax"
+        let args = json!({
+            "file_path": file_path.to_str().unwrap(),
+            "old_string": "sum",
+            "new_string": "sum + tax"
+        }).to_string();
+
+        let turn = crate::transactions::begin_transaction();
+        let edit = |name: &str| crate::transactions::with_transaction(turn, || execute_tool(name, &args, None));
+
+        assert!(edit("Edit").contains("Successfully"));
+        // The retried turn sends the same call again
+        let retried = edit("edit_file");
+        assert!(retried.contains("already applied"), "{}", retried);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "let total = sum + tax;\n");
+
+        // Once the file has moved on, the same edit is a new change
+        fs::write(&file_path, "let total = sum;\n").unwrap();
+        execute_tool("Read", &json!({"file_path": file_path.to_str().unwrap()}).to_string(), None);
+        assert!(edit("Edit").contains("Successfully"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "let total = sum + tax;\n");
+        end_turn(turn);
+        crate::transactions::commit_transaction(turn);
+    }
+
+    #[test]
+    fn test_applied_writes_are_kept_per_turn() {
+        use crate::transactions::{begin_transaction, rollback_transaction, with_transaction};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("turns.txt");
+        fs::write(&file_path, "let total = sum;\n").unwrap();
+        let args = json!({"file_path": file_path.to_str().unwrap(), "old_string": "sum", "new_string": "sum + tax"}).to_string();
+        let (first, second) = (begin_transaction(), begin_transaction());
+
+        assert!(with_transaction(first, || execute_tool("Edit", &args, None)).contains("Successfully"));
+        assert!(with_transaction(first, || execute_tool("Edit", &args, None)).contains("already applied"));
+        // Another turn running at the same time doesn't see the first one's writes
+        assert!(with_transaction(second, || execute_tool("Edit", &args, None)).contains("Successfully"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "let total = sum + tax + tax;\n");
+        assert!(with_transaction(second, || execute_tool("Edit", &args, None)).contains("already applied"));
+
+        // Once the turn is over, repeating its write is a new change
+        end_turn(second);
+        assert!(with_transaction(second, || execute_tool("Edit", &args, None)).contains("Successfully"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "let total = sum + tax + tax + tax;\n");
+        rollback_transaction(first).unwrap();
+        rollback_transaction(second).unwrap();
+
+        // Outside any turn (the MCP server) nothing is remembered
+        fs::write(&file_path, "let total = sum;\n").unwrap();
+        execute_tool("Read", &json!({"file_path": file_path.to_str().unwrap()}).to_string(), None);
+        assert!(execute_tool("Edit", &args, None).contains("Successfully"));
+        assert!(execute_tool("Edit", &args, None).contains("Successfully"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "let total = sum + tax + tax;\n");
+    }
+
     #[test]
     fn test_edit_not_found() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -1177,6 +3056,536 @@ mod tests {
         assert_eq!(content, "bar\nbar\nbar\n");
     }
 
+    #[test]
+    fn test_edit_batch_applies_all_edits() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("batch.rs");
+        fs::write(&file_path, "fn old_name() {}\nfn main() {\n    old_name();\n    let x = 1;\n}\n").unwrap();
+
+        let args = json!({
+            "file_path": file_path.to_str().unwrap(),
+            "edits": [
+                {"old_string": "old_name", "new_string": "new_name", "replace_all": true},
+                {"old_string": "let x = 1;", "new_string": "let x = 2;"},
+                // Sees the result of the first edit
+                {"old_string": "fn new_name() {}", "new_string": "fn new_name() -> u8 { 0 }"}
+            ]
+        }).to_string();
+
+        let result = execute_tool("Edit", &args, None);
+        assert!(result.contains("Applied 3 edits (4 replacements)"), "{}", result);
+        assert!(result.contains("+  let x = 2;") && result.contains("+  fn new_name() -> u8 { 0 }"), "{}", result);
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "fn new_name() -> u8 { 0 }\nfn main() {\n    new_name();\n    let x = 2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_edit_batch_failure_applies_nothing() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("batch.txt");
+        let original = "alpha\nbeta\nbeta\n";
+        fs::write(&file_path, original).unwrap();
+
+        let edit = |edits: Value| {
+            execute_tool("Edit", &json!({"file_path": file_path.to_str().unwrap(), "edits": edits}).to_string(), None)
+        };
+
+        // The first edit would succeed on its own; the second is ambiguous
+        let result = edit(json!([
+            {"old_string": "alpha", "new_string": "gamma"},
+            {"old_string": "beta", "new_string": "delta"}
+        ]));
+        assert!(result.starts_with("Error: edit 2 of 2 failed, no edits were applied"), "{}", result);
+        assert!(result.contains("appears 2 times"), "{}", result);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), original);
+
+        let result = edit(json!([{"old_string": "alpha", "new_string": "gamma"}, {"old_string": "missing", "new_string": "x"}]));
+        assert!(result.contains("edit 2 of 2") && result.contains("not found"), "{}", result);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), original);
+
+        assert!(edit(json!([])).starts_with("Error: edits must be a non-empty array"));
+        assert_eq!(edit(json!([{"old_string": "alpha"}])), "Error: edit 1 needs old_string and new_string");
+    }
+
+    #[test]
+    fn test_read_byte_range() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("app.log");
+        fs::write(&file_path, "0123456789abcdefghij").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let args = json!({"file_path": path, "byte_offset": 5, "byte_limit": 7}).to_string();
+        assert_eq!(execute_tool("Read", &args, None), "[bytes 5-12 of 20]\n56789ab");
+
+        // The window stops at the end of the file
+        let args = json!({"file_path": path, "byte_offset": 15, "byte_limit": 100}).to_string();
+        assert_eq!(execute_tool("Read", &args, None), "[bytes 15-20 of 20]\nfghij");
+
+        let args = json!({"file_path": path, "byte_offset": 20}).to_string();
+        assert!(execute_tool("Read", &args, None).starts_with("Error: byte_offset 20 exceeds"));
+
+        // A window that cuts a character in two at either edge is still text
+        let utf8_path = temp_dir.path().join("notes.txt");
+        fs::write(&utf8_path, "héllo wörld").unwrap();
+        let args = json!({"file_path": utf8_path.to_str().unwrap(), "byte_offset": 2, "byte_limit": 7}).to_string();
+        assert_eq!(execute_tool("Read", &args, None), "[bytes 3-8 of 13]\nllo w");
+
+        // Binary data comes back as hex
+        let bin_path = temp_dir.path().join("blob.bin");
+        fs::write(&bin_path, [0x00u8, 0xff, 0x41, 0x42]).unwrap();
+        let args = json!({"file_path": bin_path.to_str().unwrap(), "byte_offset": 0}).to_string();
+        let result = execute_tool("Read", &args, None);
+        assert!(result.contains("not valid UTF-8"));
+        assert!(result.contains("00000000  00 ff 41 42"));
+        assert!(result.ends_with("..AB"));
+    }
+
+    #[test]
+    fn test_rename_symbol_across_files() {
+        // Default ignore patterns skip hidden dirs, so avoid tempdir's ".tmp" prefix
+        let temp_dir = tempfile::Builder::new().prefix("rename").tempdir().unwrap();
+        let file_a = temp_dir.path().join("a.rs");
+        let file_b = temp_dir.path().join("b.rs");
+
+        fs::write(&file_a, "let count = 1;\ncount += 1;\n").unwrap();
+        // `counter` and `recount` contain the symbol but must not change
+        fs::write(&file_b, "fn count() {}\nlet counter = 0;\nlet recount = count();\n").unwrap();
+
+        let args = json!({
+            "old": "count",
+            "new": "total",
+            "glob": format!("{}/*.rs", temp_dir.path().to_str().unwrap())
+        }).to_string();
+
+        let result = execute_tool("RenameSymbol", &args, None);
+        assert!(result.contains("in 2 file(s)"));
+        assert!(result.contains("Replaced 4 occurrences"));
+
+        assert_eq!(fs::read_to_string(&file_a).unwrap(), "let total = 1;\ntotal += 1;\n");
+        assert_eq!(
+            fs::read_to_string(&file_b).unwrap(),
+            "fn total() {}\nlet counter = 0;\nlet recount = total();\n"
+        );
+
+        // Symbols starting or ending with a non-word character
+        let file_php = temp_dir.path().join("c.php");
+        fs::write(&file_php, "$var = 1; $variable = $var; a$var;\n").unwrap();
+        let args = json!({"old": "$var", "new": "$count", "glob": file_php.to_str().unwrap()}).to_string();
+        assert!(execute_tool("RenameSymbol", &args, None).contains("Replaced 2 occurrences"));
+        assert_eq!(fs::read_to_string(&file_php).unwrap(), "$count = 1; $variable = $count; a$var;\n");
+
+        fs::write(&file_a, "op!(x); op!y; top!(z);\n").unwrap();
+        let args = json!({"old": "op!", "new": "check!", "glob": file_a.to_str().unwrap()}).to_string();
+        assert!(execute_tool("RenameSymbol", &args, None).contains("Replaced 1 occurrences"));
+        assert_eq!(fs::read_to_string(&file_a).unwrap(), "check!(x); op!y; top!(z);\n");
+    }
+
+    #[test]
+    fn test_project_replace_regex_across_files() {
+        let temp_dir = tempfile::Builder::new().prefix("replace").tempdir().unwrap();
+        let file_a = temp_dir.path().join("a.rs");
+        let file_b = temp_dir.path().join("b.rs");
+        let file_c = temp_dir.path().join("c.txt");
+
+        fs::write(&file_a, "log_v1(\"a\");\nlog_v2(\"b\");\n").unwrap();
+        fs::write(&file_b, "fn main() { log_v3(\"c\"); }\n").unwrap();
+        // Outside the glob, so untouched
+        fs::write(&file_c, "log_v1(\"z\");\n").unwrap();
+
+        let args = json!({
+            "pattern": r"log_v(\d)\(",
+            "replacement": "log(${1}, ",
+            "regex": true,
+            "glob": format!("{}/*.rs", temp_dir.path().to_str().unwrap())
+        }).to_string();
+
+        let (_, preview) = preview_file_change("ProjectReplace", &args, None);
+        assert!(preview.contains("a.rs: 2 replacement(s)"));
+        assert!(preview.contains("b.rs: 1 replacement(s)"));
+        // The preview doesn't write anything
+        assert_eq!(fs::read_to_string(&file_a).unwrap(), "log_v1(\"a\");\nlog_v2(\"b\");\n");
+        // Nor does it list files outside the sandbox
+        let elsewhere = tempfile::tempdir().unwrap();
+        let (_, sandboxed) = preview_file_change("ProjectReplace", &args, elsewhere.path().to_str());
+        assert!(sandboxed.ends_with("no matches"), "{}", sandboxed);
+
+        let result = execute_tool("ProjectReplace", &args, None);
+        assert!(result.contains("in 2 file(s)"));
+        assert!(result.contains("a.rs: 2 replacement(s)"));
+        assert!(result.contains("Replaced 3 occurrences"));
+
+        assert_eq!(fs::read_to_string(&file_a).unwrap(), "log(1, \"a\");\nlog(2, \"b\");\n");
+        assert_eq!(fs::read_to_string(&file_b).unwrap(), "fn main() { log(3, \"c\"); }\n");
+        assert_eq!(fs::read_to_string(&file_c).unwrap(), "log_v1(\"z\");\n");
+
+        // Literal mode treats regex syntax as plain text
+        let literal = json!({
+            "pattern": "(1, ",
+            "replacement": "($1, ",
+            "glob": format!("{}/a.rs", temp_dir.path().to_str().unwrap())
+        }).to_string();
+        execute_tool("ProjectReplace", &literal, None);
+        assert_eq!(fs::read_to_string(&file_a).unwrap(), "log($1, \"a\");\nlog(2, \"b\");\n");
+    }
+
+    #[test]
+    fn test_project_replace_rolls_back_on_failure() {
+        let temp_dir = tempfile::Builder::new().prefix("replace").tempdir().unwrap();
+        let file_a = temp_dir.path().join("a.txt");
+        let file_b = temp_dir.path().join("b.txt");
+        fs::write(&file_a, "old value\n").unwrap();
+        fs::write(&file_b, "old value\n").unwrap();
+
+        let re = project_replace_regex("old", false).unwrap();
+        let scope = format!("{}/*.txt", temp_dir.path().to_str().unwrap());
+        let changes = plan_replacements(&re, "new", false, &scope, None).unwrap();
+        assert_eq!(changes.len(), 2);
+
+        // The first write lands, the second fails part-way through
+        let mut writes = 0;
+        let result = apply_changes_with(&changes, |path, updated| {
+            writes += 1;
+            if writes == 2 {
+                return Err(std::io::Error::other("disk full"));
+            }
+            fs::write(path, updated)
+        });
+
+        let err = result.unwrap_err();
+        assert!(err.contains("b.txt") && err.contains("disk full"));
+        assert_eq!(fs::read_to_string(&file_a).unwrap(), "old value\n");
+        assert_eq!(fs::read_to_string(&file_b).unwrap(), "old value\n");
+    }
+
+    #[test]
+    fn test_edit_detects_external_modification() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("stale.txt");
+        let path_str = file_path.to_str().unwrap();
+        fs::write(&file_path, "alpha\nbeta\n").unwrap();
+
+        execute_tool("Read", &json!({"file_path": path_str}).to_string(), None);
+
+        // Modified outside the model's view; old_string still matches
+        fs::write(&file_path, "alpha\nbeta\ngamma\n").unwrap();
+
+        let edit = json!({"file_path": path_str, "old_string": "beta", "new_string": "BETA"});
+        let result = execute_tool("Edit", &edit.to_string(), None);
+        assert!(result.contains("changed on disk"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "alpha\nbeta\ngamma\n");
+
+        // force overrides the check
+        let forced = json!({"file_path": path_str, "old_string": "beta", "new_string": "BETA", "force": true});
+        let result = execute_tool("Edit", &forced.to_string(), None);
+        assert!(result.contains("Successfully edited"));
+
+        // Our own edit refreshes the hash, so a follow-up edit is allowed
+        let edit = json!({"file_path": path_str, "old_string": "gamma", "new_string": "GAMMA"});
+        let result = execute_tool("Edit", &edit.to_string(), None);
+        assert!(result.contains("Successfully edited"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "alpha\nBETA\nGAMMA\n");
+    }
+
+    #[test]
+    fn test_grep_numbered_context() {
+        let temp_dir = tempfile::Builder::new().prefix("grep").tempdir().unwrap();
+        let file_path = temp_dir.path().join("lib.rs");
+        let lines: Vec<String> = (1..=12).map(|i| format!("line {}", i)).collect();
+        let mut body = lines.join("\n");
+        body = body.replace("line 3\n", "fn target() {}\n").replace("line 10\n", "target();\n");
+        fs::write(&file_path, body).unwrap();
+
+        let args = json!({
+            "pattern": "target",
+            "path": temp_dir.path().to_str().unwrap(),
+            "context_lines": 1,
+            "numbered": true
+        }).to_string();
+        let result = execute_tool("Grep", &args, None);
+
+        let path_str = file_path.to_str().unwrap();
+        let expected = format!(
+            "{} (matches on lines 3, 10)\n   2\tline 2\n   3\tfn target() {{}}\n   4\tline 4\n ...\n   9\tline 9\n  10\ttarget();\n  11\tline 11",
+            path_str
+        );
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_read_and_edit_notebook_cell() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("analysis.ipynb");
+        let path_str = file_path.to_str().unwrap();
+        let notebook = json!({
+            "cells": [
+                {"cell_type": "markdown", "metadata": {}, "source": ["# Analysis"]},
+                {"cell_type": "code", "execution_count": 1, "metadata": {}, "outputs": [], "source": ["df = load()\n", "df.head()"]}
+            ],
+            "metadata": {},
+            "nbformat": 4,
+            "nbformat_minor": 5
+        });
+        fs::write(&file_path, notebook.to_string()).unwrap();
+
+        let read = execute_tool("Read", &json!({"file_path": path_str}).to_string(), None);
+        assert!(read.contains("[cell 1] code (execution_count 1)\n   1\tdf = load()\n   2\tdf.head()"));
+        assert!(!read.contains("\"cell_type\""));
+
+        let edit = json!({"file_path": path_str, "cell_index": 1, "new_source": "df = load()\ndf.describe()"});
+        let result = execute_tool("EditCell", &edit.to_string(), None);
+        assert!(result.contains("Successfully edited"), "{}", result);
+
+        let updated: Value = serde_json::from_str(&fs::read_to_string(&file_path).unwrap()).unwrap();
+        assert_eq!(updated["cells"][1]["source"], json!(["df = load()\n", "df.describe()"]));
+        assert_eq!(updated["cells"][0]["source"], json!(["# Analysis"]));
+    }
+
+    #[test]
+    fn test_git_context_returns_log() {
+        let git_available = Command::new("git").arg("--version").output().is_ok_and(|o| o.status.success());
+        if !git_available {
+            return;
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = temp_dir.path();
+        let git = |args: &[&str]| {
+            let out = Command::new("git").arg("-C").arg(repo).args(args).output().unwrap();
+            assert!(out.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&out.stderr));
+        };
+        git(&["init", "-q"]);
+        git(&["config", "user.email", "dev@example.com"]);
+        git(&["config", "user.name", "Dev"]);
+        fs::write(repo.join("lib.rs"), "fn one() {}\nfn two() {}\n").unwrap();
+        git(&["add", "lib.rs"]);
+        git(&["commit", "-q", "-m", "Add lib"]);
+        fs::write(repo.join("lib.rs"), "fn one() {}\nfn two() { todo!() }\n").unwrap();
+        git(&["commit", "-q", "-am", "Stub out two"]);
+
+        let file = repo.join("lib.rs");
+        let args = json!({"file_path": file.to_str().unwrap(), "blame_start": 2}).to_string();
+        let result = execute_tool("GitContext", &args, None);
+
+        assert!(result.contains("Dev: Stub out two"), "{}", result);
+        assert!(result.contains("Dev: Add lib"));
+        assert!(result.contains("Blame lines 2-2:"));
+        assert!(result.contains("fn two() { todo!() }"));
+
+        // Untracked files are reported rather than returning empty history
+        fs::write(repo.join("new.rs"), "").unwrap();
+        let args = json!({"file_path": repo.join("new.rs").to_str().unwrap()}).to_string();
+        assert!(execute_tool("GitContext", &args, None).contains("no git history"));
+    }
+
+    #[test]
+    fn test_env_info_reports_platform_and_hides_secrets() {
+        std::env::set_var("NODE_ENV", "test");
+        std::env::set_var("GROK_ENVINFO_PLAIN", "harmless-looking");
+        std::env::set_var("GROK_ENVINFO_API_KEY", "supersecret");
+        std::env::set_var("GROK_ENVINFO_DATABASE_URL", "postgres://user:hunter2@db");
+
+        let args = json!({"vars": ["NODE_ENV", "GROK_ENVINFO_PLAIN", "GROK_ENVINFO_API_KEY", "GROK_ENVINFO_DATABASE_URL"]}).to_string();
+        let result = execute_tool("EnvInfo", &args, None);
+
+        assert!(result.contains(&format!("OS: {}", std::env::consts::OS)));
+        assert!(result.contains(&format!("Arch: {}", std::env::consts::ARCH)));
+        assert!(result.contains("NODE_ENV=test"));
+        // Only allowlisted names are shown, whatever they look like
+        assert!(result.contains("GROK_ENVINFO_PLAIN (withheld"));
+        assert!(result.contains("GROK_ENVINFO_API_KEY (withheld"));
+        assert!(!result.contains("harmless-looking"));
+        assert!(!result.contains("supersecret"));
+        assert!(!result.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_find_binary_in_path() {
+        let bin_dir = tempfile::tempdir().unwrap();
+        let tool = bin_dir.path().join("mytool");
+        fs::write(&tool, "#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+            // Present but not executable doesn't count
+            fs::write(bin_dir.path().join("notexec"), "").unwrap();
+            assert!(find_binary_in("notexec", bin_dir.path().as_os_str()).is_none());
+        }
+
+        let empty_dir = tempfile::tempdir().unwrap();
+        let path_var = std::env::join_paths([empty_dir.path(), bin_dir.path()]).unwrap();
+        assert_eq!(find_binary_in("mytool", &path_var), Some(tool));
+        assert!(find_binary_in("curl-but-not-really", &path_var).is_none());
+
+        assert_eq!(
+            missing_binary_error("curl", "install it"),
+            "Error: curl not found on PATH; install it"
+        );
+    }
+
+    #[test]
+    fn test_grep_include_array() {
+        let temp_dir = tempfile::Builder::new().prefix("grep").tempdir().unwrap();
+        fs::write(temp_dir.path().join("main.rs"), "needle in rust\n").unwrap();
+        fs::write(temp_dir.path().join("Cargo.toml"), "needle = 1\n").unwrap();
+        fs::write(temp_dir.path().join("notes.md"), "needle in docs\n").unwrap();
+
+        let args = json!({
+            "pattern": "needle",
+            "path": temp_dir.path().to_str().unwrap(),
+            "include": ["*.rs", "*.toml"]
+        }).to_string();
+        let result = execute_tool("Grep", &args, None);
+
+        assert!(result.contains("main.rs:1:needle in rust"));
+        assert!(result.contains("Cargo.toml:1:needle = 1"));
+        assert!(!result.contains("notes.md"));
+
+        // A plain string still works
+        let args = json!({"pattern": "needle", "path": temp_dir.path().to_str().unwrap(), "include": "*.md"}).to_string();
+        let result = execute_tool("Grep", &args, None);
+        assert!(result.contains("notes.md") && !result.contains("main.rs"));
+    }
+
+    #[test]
+    fn test_grep_group_by_file() {
+        let temp_dir = tempfile::Builder::new().prefix("grep").tempdir().unwrap();
+        let many = temp_dir.path().join("many.rs");
+        fs::write(&many, "needle one
+hay
+needle two
+needle three
+").unwrap();
+        fs::write(temp_dir.path().join("one.rs"), "hay
+needle four
+").unwrap();
+
+        let args = json!({"pattern": "needle", "path": temp_dir.path().to_str().unwrap(), "group_by_file": true}).to_string();
+        let result = execute_tool("Grep", &args, None);
+        let lines: Vec<&str> = result.lines().collect();
+        let headers: Vec<&str> = lines.iter().copied().filter(|l| !l.starts_with("  ")).collect();
+        assert_eq!(headers.len(), 2, "{}", result);
+        assert!(headers.iter().any(|h| h.ends_with("many.rs")));
+        assert!(headers.iter().any(|h| h.ends_with("one.rs")));
+        assert_eq!(lines.len(), 6);
+
+        let many_at = lines.iter().position(|l| l.ends_with("many.rs")).unwrap();
+        assert_eq!(&lines[many_at + 1..many_at + 4], ["  1:needle one", "  3:needle two", "  4:needle three"]);
+
+        // Searching one file still gets its header
+        let args = json!({"pattern": "needle", "path": many.to_str().unwrap(), "group_by_file": true}).to_string();
+        let result = execute_tool("Grep", &args, None);
+        assert!(result.starts_with(&format!("{}\n  1:needle one", many.to_str().unwrap())), "{}", result);
+
+        // Flat stays the default
+        let args = json!({"pattern": "needle", "path": many.to_str().unwrap()}).to_string();
+        assert!(execute_tool("Grep", &args, None).starts_with("1:needle one"));
+    }
+
+    #[test]
+    fn test_builtin_grep_engine() {
+        let temp_dir = tempfile::Builder::new().prefix("grep").tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("node_modules/dep")).unwrap();
+        let main_rs = root.join("src/main.rs");
+        fs::write(&main_rs, "fn main() {\n    let needle = 1;\n    a();\n    b();\n    c();\n    d();\n    needle_two();\n}\n").unwrap();
+        fs::write(root.join("notes.md"), "a needle in docs\n").unwrap();
+        fs::write(root.join("node_modules/dep/index.rs"), "needle\n").unwrap();
+        let root_str = root.to_str().unwrap();
+        let grep = |extra: Value| {
+            let mut args = json!({"pattern": "needle", "path": root_str, "engine": "builtin"});
+            args.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            execute_tool("Grep", &args.to_string(), None)
+        };
+
+        // path:line:text, .grokignore respected, include filters by file name
+        let main_path = main_rs.to_str().unwrap();
+        let notes_path = root.join("notes.md");
+        assert_eq!(
+            grep(json!({})),
+            format!("{}:1:a needle in docs\n{}:2:    let needle = 1;\n{}:7:    needle_two();", notes_path.display(), main_path, main_path)
+        );
+        assert!(!grep(json!({"include": "*.rs"})).contains("notes.md"));
+
+        // Context lines use '-', and "--" separates groups that don't touch
+        let result = grep(json!({"include": ["*.rs"], "context_lines": 1}));
+        assert_eq!(
+            result.lines().collect::<Vec<_>>(),
+            [
+                format!("{}-1-fn main() {{", main_path),
+                format!("{}:2:    let needle = 1;", main_path),
+                format!("{}-3-    a();", main_path),
+                "--".to_string(),
+                format!("{}-6-    d();", main_path),
+                format!("{}:7:    needle_two();", main_path),
+                format!("{}-8-}}", main_path),
+            ]
+        );
+
+        // A single file has no path prefix; Rust regex syntax; numbered mode still works
+        let args = json!({"pattern": r"needle\w*\(", "path": main_path, "engine": "builtin"}).to_string();
+        assert_eq!(execute_tool("Grep", &args, None), "7:    needle_two();");
+        let numbered = grep(json!({"include": "*.rs", "numbered": true, "context_lines": 0}));
+        assert!(numbered.contains("main.rs (matches on lines 2, 7)"), "{}", numbered);
+
+        // Same matches as system grep where it's available
+        if find_binary("grep").is_some() {
+            let sorted = |out: String| {
+                let mut lines: Vec<String> = out.lines().map(String::from).collect();
+                lines.sort();
+                lines
+            };
+            assert_eq!(sorted(grep(json!({"engine": "system"}))), sorted(grep(json!({}))));
+            let single = |engine: &str| {
+                execute_tool("Grep", &json!({"pattern": "needle", "path": main_path, "context_lines": 2, "engine": engine}).to_string(), None)
+            };
+            assert_eq!(single("system"), single("builtin"));
+        }
+
+        // Output is capped at 100 lines like the system engine
+        fs::write(root.join("many.txt"), "needle\n".repeat(150)).unwrap();
+        assert!(grep(json!({"include": "*.txt"})).ends_with("... 50 more lines (showing first 100)"));
+
+        assert!(grep(json!({"pattern": "("})).starts_with("Error: invalid pattern"));
+        assert!(grep(json!({"engine": "ripgrep"})).starts_with("Error: unknown engine"));
+    }
+
+    #[test]
+    fn test_glob_sort_and_limit() {
+        // Not a dot-prefixed temp dir, which .grokignore would hide
+        let dir = tempfile::Builder::new().prefix("glob").tempdir().unwrap();
+        let root = dir.path();
+        let now = std::time::SystemTime::now();
+        for (name, age_secs, size) in [("a.txt", 300, 30), ("b.txt", 10, 10), ("c.txt", 3600, 20)] {
+            let path = root.join(name);
+            fs::write(&path, "x".repeat(size)).unwrap();
+            let file = fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(now - std::time::Duration::from_secs(age_secs)).unwrap();
+        }
+        let glob = |extra: Value| {
+            let mut args = json!({"pattern": "*.txt", "path": root.to_str().unwrap()});
+            args.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            execute_tool("Glob", &args.to_string(), None)
+        };
+        let names = |out: String| -> Vec<String> {
+            out.lines()
+                .map(|l| Path::new(l).file_name().map_or(l.to_string(), |n| n.to_string_lossy().to_string()))
+                .collect()
+        };
+
+        assert_eq!(names(glob(json!({}))), ["a.txt", "b.txt", "c.txt"]);
+        assert_eq!(names(glob(json!({"sort": "mtime"}))), ["b.txt", "a.txt", "c.txt"]);
+        assert_eq!(names(glob(json!({"sort": "size"}))), ["a.txt", "c.txt", "b.txt"]);
+
+        let limited = glob(json!({"sort": "mtime", "limit": 2}));
+        assert_eq!(names(limited), ["b.txt", "a.txt", "... 1 more files (showing first 2)"]);
+        assert!(!glob(json!({"limit": 3})).contains("more files"));
+        assert!(glob(json!({"sort": "newest"})).starts_with("Error: unknown sort"));
+    }
+
     #[test]
     fn test_write_creates_directories() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -1223,6 +3632,16 @@ mod tests {
         assert!(result.contains("Type: directory"));
     }
 
+    #[test]
+    fn test_unsupported_ignore_syntax() {
+        assert!(unsupported_ignore_syntax("/foo").unwrap().contains("anchored"));
+        assert!(unsupported_ignore_syntax("!keep.log").is_some());
+        assert!(unsupported_ignore_syntax("src/**/gen").is_some());
+        assert!(unsupported_ignore_syntax("target").is_none());
+        assert!(unsupported_ignore_syntax("*.log").is_none());
+        assert!(unsupported_ignore_syntax(".*").is_none());
+    }
+
     #[test]
     fn test_file_info_nonexistent() {
         let args = json!({
@@ -1232,4 +3651,55 @@ mod tests {
         let result = execute_tool("FileInfo", &args, None);
         assert!(result.contains("Error getting metadata"));
     }
+
+    #[test]
+    fn test_network_tools_refused_offline() {
+        let config = crate::config::Config { allow_network: false, ..Default::default() };
+        let filter = config.tool_filter();
+        for name in ["WebSearch", "web_search"] {
+            let error = filter.check(name).unwrap_err();
+            assert!(error.contains("Network access disabled"), "{}", error);
+        }
+        assert!(filter.check("Read").is_ok());
+        assert!(filter.apply(get_tool_definitions()).iter().all(|def| def["function"]["name"] != "WebSearch"));
+
+        assert!(crate::config::Config::default().tool_filter().check("WebSearch").is_ok());
+    }
+
+    #[test]
+    fn test_search_history_is_opt_in() {
+        let filter = crate::config::Config::default().tool_filter();
+        for name in ["SearchHistory", "search_history"] {
+            let error = filter.check(name).unwrap_err();
+            assert!(error.contains("allow_history_search"), "{}", error);
+        }
+
+        let config = crate::config::Config { allow_history_search: true, ..Default::default() };
+        assert!(config.tool_filter().check("SearchHistory").is_ok());
+        let defs = ToolFilter { history_search: true, ..Default::default() }.apply(vec![json!({"function": {"name": "SearchHistory"}})]);
+        assert_eq!(defs.len(), 1);
+    }
+
+    #[test]
+    fn test_disabled_tool_is_hidden_and_rejected() {
+        let filter = ToolFilter { disabled: vec!["Bash".to_string(), "web_search".to_string()], ..Default::default() };
+        let names: Vec<String> = filter
+            .apply(get_tool_definitions())
+            .iter()
+            .map(|def| def["function"]["name"].as_str().unwrap().to_string())
+            .collect();
+        assert!(!names.contains(&"Bash".to_string()));
+        assert!(!names.contains(&"WebSearch".to_string()));
+        assert!(names.contains(&"Read".to_string()));
+
+        // Aliases can't get around the list
+        assert_eq!(filter.check("run_shell_command").unwrap_err(), "Error: tool 'run_shell_command' is disabled in config");
+        assert!(filter.check("Read").is_ok());
+
+        // An allow list offers nothing else
+        let only = ToolFilter { enabled: vec!["Read".to_string(), "Grep".to_string()], ..Default::default() };
+        assert_eq!(only.apply(get_tool_definitions()).len(), 2);
+        assert!(only.check("search_content").is_ok());
+        assert!(only.check("Write").is_err());
+    }
 }
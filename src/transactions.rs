@@ -65,6 +65,9 @@ impl FileSnapshot {
 pub struct Transaction {
     pub snapshots: HashMap<String, FileSnapshot>,
     pub modified_files: Vec<String>,
+    /// Content of each modified file at commit (None if it was removed), so
+    /// undo can tell whether anything changed it since
+    pub committed: HashMap<String, Option<String>>,
 }
 
 impl Transaction {
@@ -72,6 +75,7 @@ impl Transaction {
         Transaction {
             snapshots: HashMap::new(),
             modified_files: Vec::new(),
+            committed: HashMap::new(),
         }
     }
 
@@ -98,18 +102,71 @@ impl Transaction {
         }
         Ok(())
     }
+
+    /// Remember what the modified files hold now that the transaction is done
+    fn record_committed(&mut self) {
+        self.committed = self
+            .modified_files
+            .iter()
+            .map(|path| (path.clone(), fs::read_to_string(path).ok()))
+            .collect();
+    }
+
+    /// Modified files whose content is no longer what the commit left behind
+    fn changed_since_commit(&self) -> Vec<String> {
+        self.modified_files
+            .iter()
+            .filter(|path| self.committed.get(*path).is_some_and(|content| *content != fs::read_to_string(path).ok()))
+            .cloned()
+            .collect()
+    }
+
+    /// Restore the files this transaction modified, returning their paths
+    pub fn revert_modified(&self) -> std::io::Result<Vec<String>> {
+        for path in &self.modified_files {
+            if let Some(snapshot) = self.snapshots.get(path) {
+                snapshot.restore()?;
+            }
+        }
+        Ok(self.modified_files.clone())
+    }
+}
+
+/// Identifies one open transaction (one user turn)
+pub type TransactionId = u64;
+
+tokio::task_local! {
+    /// The transaction file operations on this task are recorded in
+    static CURRENT_TRANSACTION: TransactionId;
+}
+
+/// Run `operation` with its file changes recorded in transaction `id`
+pub fn with_transaction<R>(id: TransactionId, operation: impl FnOnce() -> R) -> R {
+    CURRENT_TRANSACTION.sync_scope(id, operation)
+}
+
+/// The transaction of the enclosing `with_transaction` scope, if any
+pub fn current_transaction() -> Option<TransactionId> {
+    CURRENT_TRANSACTION.try_with(|id| *id).ok()
 }
 
 /// Global transaction manager
 pub struct TransactionManager {
-    current_transaction: Option<Transaction>,
+    /// Open transactions; a turn cancelled while a new one starts can still
+    /// roll back its own without touching the new one's
+    open: HashMap<TransactionId, Transaction>,
+    next_id: TransactionId,
+    /// The most recent committed transaction that changed files, kept for /undo
+    last_committed: Option<Transaction>,
     sandbox_cwd: Option<String>,
 }
 
 impl TransactionManager {
     pub fn new() -> Self {
         TransactionManager {
-            current_transaction: None,
+            open: HashMap::new(),
+            next_id: 0,
+            last_committed: None,
             sandbox_cwd: None,
         }
     }
@@ -119,22 +176,58 @@ impl TransactionManager {
     }
 
     /// Start a new transaction
-    pub fn begin_transaction(&mut self) {
-        self.current_transaction = Some(Transaction::new());
+    pub fn begin_transaction(&mut self) -> TransactionId {
+        self.next_id += 1;
+        self.open.insert(self.next_id, Transaction::new());
+        self.next_id
     }
 
-    /// End the current transaction (commit - just clear it)
-    pub fn commit_transaction(&mut self) {
-        self.current_transaction = None;
+    /// End transaction `id`, keeping its snapshots for `undo_last_commit` if it
+    /// changed anything. Ending one that is already over does nothing.
+    pub fn commit_transaction(&mut self, id: TransactionId) {
+        if let Some(mut transaction) = self.open.remove(&id) {
+            if !transaction.modified_files.is_empty() {
+                transaction.record_committed();
+                self.last_committed = Some(transaction);
+            }
+        }
     }
 
-    /// Rollback the current transaction and restore all files
-    pub fn rollback_transaction(&mut self) -> std::io::Result<()> {
-        if let Some(ref transaction) = self.current_transaction {
-            transaction.rollback()?;
+    /// End transaction `id`: commit it, or roll it back if the turn was
+    /// cancelled so no half-applied edits are left behind
+    pub fn finish_transaction(&mut self, id: TransactionId, cancelled: bool) -> std::io::Result<()> {
+        if cancelled {
+            self.rollback_transaction(id)
+        } else {
+            self.commit_transaction(id);
+            Ok(())
+        }
+    }
+
+    /// Restore the files changed by the most recent committed transaction,
+    /// returning their paths (empty when there is nothing to undo). Refuses,
+    /// keeping the transaction, if any of them changed since the commit.
+    pub fn undo_last_commit(&mut self) -> std::io::Result<Vec<String>> {
+        let Some(transaction) = self.last_committed.take() else {
+            return Ok(Vec::new());
+        };
+        let changed = transaction.changed_since_commit();
+        if !changed.is_empty() {
+            self.last_committed = Some(transaction);
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("changed since that turn, not overwriting: {}", changed.join(", ")),
+            ));
+        }
+        transaction.revert_modified()
+    }
+
+    /// Rollback transaction `id` and restore all its files
+    pub fn rollback_transaction(&mut self, id: TransactionId) -> std::io::Result<()> {
+        match self.open.remove(&id) {
+            Some(transaction) => transaction.rollback(),
+            None => Ok(()),
         }
-        self.current_transaction = None;
-        Ok(())
     }
 
     /// Check if a path is allowed (sandbox mode)
@@ -147,7 +240,7 @@ impl TransactionManager {
     }
 
     /// Prepare a file for modification (take snapshot if not already done)
-    pub fn prepare_file(&mut self, path: &str) -> std::io::Result<()> {
+    pub fn prepare_file(&mut self, id: Option<TransactionId>, path: &str) -> std::io::Result<()> {
         if !self.is_path_allowed(path) {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::PermissionDenied,
@@ -155,45 +248,54 @@ impl TransactionManager {
             ));
         }
 
-        if let Some(ref mut transaction) = self.current_transaction {
+        if let Some(transaction) = id.and_then(|id| self.open.get_mut(&id)) {
             transaction.snapshot_file(path)?;
         }
         Ok(())
     }
 
     /// Mark a file as modified
-    pub fn mark_file_modified(&mut self, path: &str) {
-        if let Some(ref mut transaction) = self.current_transaction {
+    pub fn mark_file_modified(&mut self, id: Option<TransactionId>, path: &str) {
+        if let Some(transaction) = id.and_then(|id| self.open.get_mut(&id)) {
             transaction.mark_modified(path);
         }
     }
 
-    /// Execute a file operation within the transaction context
-    pub fn execute_file_operation<F, R>(&mut self, path: &str, operation: F) -> std::io::Result<R>
+    /// Execute a file operation within transaction `id` (untracked if None)
+    pub fn execute_file_operation<F, R>(&mut self, id: Option<TransactionId>, path: &str, operation: F) -> std::io::Result<R>
     where
         F: FnOnce() -> std::io::Result<R>,
     {
-        self.prepare_file(path)?;
+        self.prepare_file(id, path)?;
         let result = operation()?;
-        self.mark_file_modified(path);
+        self.mark_file_modified(id, path);
         Ok(result)
     }
 
-    /// Check if we're currently in a transaction
-    pub fn in_transaction(&self) -> bool {
-        self.current_transaction.is_some()
+    /// Files changed by transaction `id` that haven't been committed yet
+    pub fn modified_files(&self, id: TransactionId) -> Vec<String> {
+        self.open
+            .get(&id)
+            .map(|t| t.modified_files.clone())
+            .unwrap_or_default()
+    }
+
+    /// Check if transaction `id` is still open
+    pub fn in_transaction(&self, id: TransactionId) -> bool {
+        self.open.contains_key(&id)
     }
 
     /// Get transaction status for debugging
     pub fn get_transaction_status(&self) -> String {
-        if let Some(ref transaction) = self.current_transaction {
+        if self.open.is_empty() {
+            "No active transaction".to_string()
+        } else {
             format!(
-                "Transaction active: {} files tracked, {} modified",
-                transaction.snapshots.len(),
-                transaction.modified_files.len()
+                "{} transaction(s) active: {} files tracked, {} modified",
+                self.open.len(),
+                self.open.values().map(|t| t.snapshots.len()).sum::<usize>(),
+                self.open.values().map(|t| t.modified_files.len()).sum::<usize>()
             )
-        } else {
-            "No active transaction".to_string()
         }
     }
 }
@@ -212,40 +314,62 @@ pub fn init_transaction_manager(sandbox_cwd: Option<String>) {
 }
 
 /// Begin a new transaction
-pub fn begin_transaction() {
+pub fn begin_transaction() -> TransactionId {
+    TRANSACTION_MANAGER.lock().map(|mut m| m.begin_transaction()).unwrap_or_default()
+}
+
+/// Commit transaction `id`
+pub fn commit_transaction(id: TransactionId) {
+    if let Ok(mut manager) = TRANSACTION_MANAGER.lock() {
+        manager.commit_transaction(id);
+    }
+}
+
+/// Commit transaction `id`, or roll it back when the turn was cancelled
+pub fn finish_transaction(id: TransactionId, cancelled: bool) -> std::io::Result<()> {
     if let Ok(mut manager) = TRANSACTION_MANAGER.lock() {
-        manager.begin_transaction();
+        manager.finish_transaction(id, cancelled)
+    } else {
+        Err(std::io::Error::new(std::io::ErrorKind::Other, "Could not access transaction manager"))
     }
 }
 
-/// Commit the current transaction
-pub fn commit_transaction() {
+/// Undo the file changes of the most recent committed transaction
+pub fn undo_last_commit() -> std::io::Result<Vec<String>> {
     if let Ok(mut manager) = TRANSACTION_MANAGER.lock() {
-        manager.commit_transaction();
+        manager.undo_last_commit()
+    } else {
+        Err(std::io::Error::new(std::io::ErrorKind::Other, "Could not access transaction manager"))
     }
 }
 
-/// Rollback the current transaction
-pub fn rollback_transaction() -> std::io::Result<()> {
+/// Rollback transaction `id`
+pub fn rollback_transaction(id: TransactionId) -> std::io::Result<()> {
     if let Ok(mut manager) = TRANSACTION_MANAGER.lock() {
-        manager.rollback_transaction()
+        manager.rollback_transaction(id)
     } else {
         Err(std::io::Error::new(std::io::ErrorKind::Other, "Could not access transaction manager"))
     }
 }
 
-/// Execute a file operation within transaction context
+/// Execute a file operation within the transaction of the calling
+/// `with_transaction` scope, if any
 pub fn execute_file_operation<F, R>(path: &str, operation: F) -> std::io::Result<R>
 where
     F: FnOnce() -> std::io::Result<R>,
 {
     if let Ok(mut manager) = TRANSACTION_MANAGER.lock() {
-        manager.execute_file_operation(path, operation)
+        manager.execute_file_operation(current_transaction(), path, operation)
     } else {
         Err(std::io::Error::new(std::io::ErrorKind::Other, "Could not access transaction manager"))
     }
 }
 
+/// Files changed by transaction `id` that haven't been committed yet
+pub fn modified_files(id: TransactionId) -> Vec<String> {
+    TRANSACTION_MANAGER.lock().map(|m| m.modified_files(id)).unwrap_or_default()
+}
+
 /// Get transaction status
 pub fn get_transaction_status() -> String {
     if let Ok(manager) = TRANSACTION_MANAGER.lock() {
@@ -253,4 +377,81 @@ pub fn get_transaction_status() -> String {
     } else {
         "Transaction manager unavailable".to_string()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_restores_files_of_last_committed_transaction() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let edited = temp_dir.path().join("main.rs");
+        let created = temp_dir.path().join("new.rs");
+        let (edited, created) = (edited.to_str().unwrap(), created.to_str().unwrap());
+        fs::write(edited, "fn main() {}\n").unwrap();
+
+        let mut manager = TransactionManager::new();
+        let id = manager.begin_transaction();
+        manager.execute_file_operation(Some(id), edited, || fs::write(edited, "fn main() { changed(); }\n")).unwrap();
+        manager.execute_file_operation(Some(id), created, || fs::write(created, "pub fn changed() {}\n")).unwrap();
+        manager.commit_transaction(id);
+
+        // A later turn that changes nothing doesn't replace what /undo reverts
+        let id = manager.begin_transaction();
+        manager.commit_transaction(id);
+
+        assert_eq!(manager.undo_last_commit().unwrap(), [edited, created]);
+        assert_eq!(fs::read_to_string(edited).unwrap(), "fn main() {}\n");
+        assert!(!Path::new(created).exists());
+
+        // Undo only goes back one transaction
+        assert!(manager.undo_last_commit().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cancelled_turn_rolls_back_its_writes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        let path = path.to_str().unwrap();
+        fs::write(path, "debug = false\n").unwrap();
+
+        let mut manager = TransactionManager::new();
+        let cancelled = manager.begin_transaction();
+        manager.execute_file_operation(Some(cancelled), path, || fs::write(path, "debug = true\n")).unwrap();
+
+        // A turn started before the cancelled one has wound down is left alone
+        let next = manager.begin_transaction();
+        manager.finish_transaction(cancelled, true).unwrap();
+
+        assert_eq!(fs::read_to_string(path).unwrap(), "debug = false\n");
+        assert!(!manager.in_transaction(cancelled));
+        assert!(manager.in_transaction(next));
+        // Nothing was committed, so there is nothing to undo either
+        assert!(manager.undo_last_commit().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_undo_keeps_edits_made_after_the_commit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("lib.rs");
+        let path = path.to_str().unwrap();
+        fs::write(path, "v1\n").unwrap();
+
+        let mut manager = TransactionManager::new();
+        let id = manager.begin_transaction();
+        manager.execute_file_operation(Some(id), path, || fs::write(path, "v2\n")).unwrap();
+        manager.commit_transaction(id);
+
+        // The user edits the file by hand afterwards
+        fs::write(path, "v3\n").unwrap();
+        let err = manager.undo_last_commit().unwrap_err();
+        assert!(err.to_string().contains(path), "{}", err);
+        assert_eq!(fs::read_to_string(path).unwrap(), "v3\n");
+
+        // Put back as the turn left it, the undo goes through
+        fs::write(path, "v2\n").unwrap();
+        assert_eq!(manager.undo_last_commit().unwrap(), [path]);
+        assert_eq!(fs::read_to_string(path).unwrap(), "v1\n");
+    }
+}
@@ -6,7 +6,20 @@ use ratatui::{
     Frame,
 };
 use crate::app::{App, AppMode, total_context_tokens, TodoStatus};
-use crate::markdown::{render_markdown_lines, wrap_text};
+use crate::markdown::{render_markdown_lines, render_raw_lines, wrap_text};
+use crate::images::{self, InlineImage, IMAGE_ROWS};
+
+/// Frames of the loading spinner, one per tick
+pub const SPINNER_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// How long each spinner frame is shown
+pub const SPINNER_FRAME_MS: u128 = 80;
+
+/// Spinner frame for the time since the task started, so the animation runs
+/// at the same speed however often the screen is drawn
+pub fn spinner_frame(elapsed: std::time::Duration) -> &'static str {
+    SPINNER_FRAMES[(elapsed.as_millis() / SPINNER_FRAME_MS) as usize % SPINNER_FRAMES.len()]
+}
 
 pub fn ui(f: &mut Frame, app: &mut App) {
     // Modern layout with padding and better spacing
@@ -35,19 +48,8 @@ pub fn ui(f: &mut Frame, app: &mut App) {
     let status_area = chunks[1];
     let input_area = chunks[2];
 
-    // Split messages area if todos exist (with better proportions)
-    let (messages_area, todos_area) = if !app.todos.is_empty() {
-        let split = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([
-                Constraint::Min(50),      // Messages (more space)
-                Constraint::Length(40),   // Todo panel (wider)
-            ])
-            .split(messages_area);
-        (split[0], Some(split[1]))
-    } else {
-        (messages_area, None)
-    };
+    let (messages_area, todos_area) = split_todos_area(messages_area, app);
+    let (messages_area, todos_area, thinking_area) = split_thinking_area(messages_area, todos_area, app);
 
     // === Messages Area ===
     let mut list_items = Vec::new();
@@ -91,6 +93,9 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         list_items.push(ListItem::new(Line::from("")));
     }
 
+    // Item index of the first row reserved for each drawable image
+    let mut image_slots: Vec<(usize, String)> = Vec::new();
+
     // Process messages
     let mut i = 0;
     while i < app.messages.len() {
@@ -127,6 +132,7 @@ pub fn ui(f: &mut Frame, app: &mut App) {
                         Span::raw(*line),
                     ])));
                 }
+                push_image_refs(content, &mut list_items, &mut image_slots);
             }
             i += 1;
             continue;
@@ -206,6 +212,14 @@ pub fn ui(f: &mut Frame, app: &mut App) {
                                         list_items.push(ListItem::new(Line::from(spans)));
                                     }
                                 }
+                            } else if let Some(output) = app.live_tool_output.get(&tc.id) {
+                                // Still running: the latest lines of its output
+                                for line in live_output_tail(output, LIVE_OUTPUT_ROWS) {
+                                    list_items.push(ListItem::new(Line::from(vec![
+                                        Span::styled("   ┆ ", Style::default().fg(Color::DarkGray)),
+                                        Span::styled(truncate(&sanitize_terminal_text(line), max_width - 6), Style::default().fg(Color::Gray)),
+                                    ])));
+                                }
                             }
                         }
                     }
@@ -229,7 +243,12 @@ pub fn ui(f: &mut Frame, app: &mut App) {
                             None
                         };
 
-                        for line in render_markdown_lines(content, max_width - 1) {
+                        let lines = if app.raw_markdown {
+                            render_raw_lines(content, max_width - 1)
+                        } else {
+                            render_markdown_lines(content, max_width - 1)
+                        };
+                        for line in lines {
                             let mut spans = vec![Span::styled("🤖 ", Style::default().fg(Color::Green))];
                             if let Some(color) = megamind_color {
                                 // Apply megamind color to the line
@@ -241,6 +260,7 @@ pub fn ui(f: &mut Frame, app: &mut App) {
                             }
                             list_items.push(ListItem::new(Line::from(spans)));
                         }
+                        push_image_refs(content, &mut list_items, &mut image_slots);
                     }
                 }
             }
@@ -266,7 +286,7 @@ pub fn ui(f: &mut Frame, app: &mut App) {
             ))));
         }
         list_items.push(ListItem::new(Line::from(Span::styled(
-            " [y/n/feedback]",
+            " [y/n/feedback - end a line with \\ or Shift+Enter for more lines]",
             Style::default().fg(Color::DarkGray)
         ))));
     }
@@ -288,6 +308,21 @@ pub fn ui(f: &mut Frame, app: &mut App) {
 
     f.render_stateful_widget(messages_list, messages_area, &mut app.list_state);
 
+    // Images whose reserved rows are fully on screen get drawn after the frame
+    let inner = Block::default().borders(Borders::ALL).inner(messages_area);
+    let offset = app.list_state.offset();
+    let indent = if app.list_state.selected().is_some() { 5 } else { 3 };
+    app.inline_images = image_slots.into_iter().filter_map(|(idx, path)| {
+        let row = u16::try_from(idx.checked_sub(offset)?).ok()?;
+        (row.saturating_add(IMAGE_ROWS) <= inner.height).then(|| InlineImage {
+            path,
+            x: inner.x + indent,
+            y: inner.y + row,
+            cols: inner.width.saturating_sub(indent + 1).min(60),
+            rows: IMAGE_ROWS,
+        })
+    }).collect();
+
     // === Todo Panel ===
     if let Some(todo_area) = todos_area {
         let todo_items: Vec<ListItem> = app.todos.iter().map(|todo| {
@@ -304,11 +339,7 @@ pub fn ui(f: &mut Frame, app: &mut App) {
 
             // Truncate long text
             let max_text_len = todo_area.width.saturating_sub(6) as usize;
-            let display_text = if text.len() > max_text_len && max_text_len > 3 {
-                format!("{}…", &text[..max_text_len - 1])
-            } else {
-                text.clone()
-            };
+            let display_text = if max_text_len > 3 { truncate(text, max_text_len) } else { text.clone() };
 
             ListItem::new(Line::from(vec![
                 Span::styled(format!(" {} ", icon), style),
@@ -333,23 +364,31 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         f.render_widget(todo_list, todo_area);
     }
 
+    // === Thinking Panel ===
+    if let Some(thinking_area) = thinking_area {
+        let width = thinking_area.width.saturating_sub(2).max(1) as usize;
+        let rows = thinking_area.height.saturating_sub(2) as usize;
+        let lines = thinking_lines(&app.thinking, width, rows);
+
+        let thinking = Paragraph::new(lines.into_iter().map(|l| Line::from(Span::styled(
+            l,
+            Style::default().fg(Color::Magenta).add_modifier(Modifier::ITALIC)
+        ))).collect::<Vec<_>>())
+            .block(Block::default()
+                .title(Span::styled(
+                    " 💭 Thinking ",
+                    Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)
+                ))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(Color::DarkGray)));
+
+        f.render_widget(thinking, thinking_area);
+    }
+
     // === Status Bar ===
     let cwd = std::env::current_dir()
-        .map(|p| {
-            let path = p.to_string_lossy();
-            // Shorten home directory
-            if let Ok(home) = std::env::var("HOME") {
-                if path.starts_with(&home) {
-                    return format!("~{}", &path[home.len()..]);
-                }
-            }
-            // Truncate long paths
-            if path.len() > 30 {
-                format!("...{}", &path[path.len()-27..])
-            } else {
-                path.to_string()
-            }
-        })
+        .map(|p| short_cwd(&p.to_string_lossy(), std::env::var("HOME").ok().as_deref()))
         .unwrap_or_else(|_| ".".to_string());
 
     // Use API-reported tokens if available, otherwise estimate
@@ -387,11 +426,9 @@ pub fn ui(f: &mut Frame, app: &mut App) {
     };
 
     let status_spans = if app.is_loading {
-        let spinner = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
-        let s = spinner[app.spinner_index % spinner.len()];
-        let elapsed = app.task_start
-            .map(|start| format_duration(start.elapsed()))
-            .unwrap_or_default();
+        let running_for = app.task_start.map(|start| start.elapsed());
+        let s = spinner_frame(running_for.unwrap_or_default());
+        let elapsed = running_for.map(format_duration).unwrap_or_default();
         let mut spans = vec![
             Span::styled(format!(" {} ", cwd), Style::default().fg(Color::DarkGray)),
             Span::styled("│", Style::default().fg(Color::DarkGray)),
@@ -414,14 +451,15 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         spans.push(Span::styled("│", Style::default().fg(Color::DarkGray)));
         spans.push(Span::styled(format!(" {} ", s), Style::default().fg(Color::Yellow)));
 
-        // Show thinking preview if available, otherwise status message
-        if let Some(ref thinking) = app.thinking_preview {
+        // With the Thinking panel collapsed, preview the reasoning here instead
+        if !app.show_thinking && !app.thinking.is_empty() {
             // Clean up thinking text: remove newlines, take last part
-            let clean = thinking.replace('\n', " ").replace("  ", " ");
-            let preview = if clean.len() > 60 {
-                format!("{}...", &clean.chars().take(60).collect::<String>())
+            let clean = app.thinking.replace('\n', " ").replace("  ", " ");
+            let chars: Vec<char> = clean.trim().chars().collect();
+            let preview = if chars.len() > 60 {
+                format!("...{}", chars[chars.len() - 60..].iter().collect::<String>())
             } else {
-                clean
+                chars.iter().collect()
             };
             spans.push(Span::styled("💭 ", Style::default().fg(Color::Magenta)));
             spans.push(Span::styled(preview, Style::default().fg(Color::Magenta).add_modifier(Modifier::ITALIC)));
@@ -626,15 +664,31 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         f.render_widget(Clear, area);
 
         let block = Block::default()
-            .title(Span::styled(
-                format!(" {} ", state.question),
-                Style::default().add_modifier(Modifier::BOLD)
-            ))
             .borders(Borders::ALL)
             .border_type(BorderType::Rounded)
             .border_style(Style::default().fg(Color::Cyan));
 
         f.render_widget(block.clone(), area);
+        let inner = block.inner(area);
+
+        // Question wraps above the options, taking at most half the modal
+        let question_lines: Vec<Line> = state.question.lines()
+            .flat_map(|line| wrap_text(line, inner.width.saturating_sub(1).max(1) as usize))
+            .map(|line| Line::from(Span::styled(format!(" {}", line), Style::default().add_modifier(Modifier::BOLD))))
+            .collect();
+        let question_height = (question_lines.len() as u16).clamp(1, (inner.height / 2).max(1));
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(question_height),
+                Constraint::Length(1),
+                Constraint::Min(1),
+                Constraint::Length(1),
+            ])
+            .split(inner);
+
+        f.render_widget(Paragraph::new(question_lines), chunks[0]);
 
         let items: Vec<ListItem> = state.options.iter().enumerate().map(|(i, opt)| {
             let marker = if state.selected[i] { "☑" } else { "☐" };
@@ -644,12 +698,58 @@ pub fn ui(f: &mut Frame, app: &mut App) {
             ]))
         }).collect();
 
+        let rows = chunks[2].height as usize;
+        let total = state.options.len();
+        let selected = state.list_state.selected().unwrap_or(0);
+        let offset = planning_scroll_offset(selected, state.list_state.offset(), rows, total);
+        *state.list_state.offset_mut() = offset;
+
         let list = List::new(items)
             .block(Block::default().borders(Borders::NONE))
             .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow))
             .highlight_symbol("▸ ");
 
-        f.render_stateful_widget(list, block.inner(area), &mut state.list_state);
+        f.render_stateful_widget(list, chunks[2], &mut state.list_state);
+
+        // Show where we are when the options don't all fit
+        if total > rows {
+            let position = format!(" {}-{} of {} (↑↓ to scroll)", offset + 1, (offset + rows).min(total), total);
+            f.render_widget(
+                Paragraph::new(Span::styled(position, Style::default().fg(Color::DarkGray))),
+                chunks[3],
+            );
+        }
+    }
+
+    // === Restored Draft Modal ===
+    if let AppMode::DraftChoice(state) = &mut app.mode {
+        let area = centered_rect(60, 30, f.area());
+        f.render_widget(Clear, area);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Cyan));
+
+        f.render_widget(block.clone(), area);
+        let inner = block.inner(area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(2), Constraint::Length(1), Constraint::Min(1)])
+            .split(inner);
+
+        let question = vec![
+            Line::from(Span::styled(" Restore the draft you didn't send last time?", Style::default().add_modifier(Modifier::BOLD))),
+            Line::from(Span::styled(format!(" {}", state.preview), Style::default().fg(Color::DarkGray))),
+        ];
+        f.render_widget(Paragraph::new(question), chunks[0]);
+
+        let items: Vec<ListItem> = crate::app::DRAFT_OPTIONS.iter().map(|opt| ListItem::new(format!(" {}", opt))).collect();
+        let list = List::new(items)
+            .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow))
+            .highlight_symbol("▸ ");
+        f.render_stateful_widget(list, chunks[2], &mut state.list_state);
     }
 
     // === Error View Modal ===
@@ -725,6 +825,7 @@ pub fn ui(f: &mut Frame, app: &mut App) {
             // Get the current value for this setting
             let is_enabled = match setting.key {
                 "rate_limiter_enabled" => app.config.settings.rate_limiter_enabled,
+                "mouse_capture_enabled" => app.config.settings.mouse_capture_enabled,
                 _ => false,
             };
 
@@ -788,6 +889,93 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         ]));
         f.render_widget(footer, footer_area);
     }
+
+    // Everything above is drawn in the dark palette; other themes remap it
+    if !app.theme.is_native() {
+        for cell in f.buffer_mut().content.iter_mut() {
+            let style = app.theme.apply(cell.style());
+            cell.set_style(style);
+        }
+    }
+
+    // NO_COLOR / --no-color: drop every color drawn above, keeping modifiers
+    if !app.color_enabled {
+        for cell in f.buffer_mut().content.iter_mut() {
+            let style = monochrome(cell.style());
+            cell.set_style(style);
+        }
+    }
+}
+
+/// Split the messages area when the Tasks panel is visible (there are todos
+/// and Ctrl+L hasn't hidden it); otherwise messages get the full width
+fn split_todos_area(area: Rect, app: &App) -> (Rect, Option<Rect>) {
+    if !app.show_todos || app.todos.is_empty() {
+        return (area, None);
+    }
+    let split = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Min(50),      // Messages (more space)
+            Constraint::Length(40),   // Todo panel (wider)
+        ])
+        .split(area);
+    (split[0], Some(split[1]))
+}
+
+/// Place the Thinking panel, shown only while a turn is streaming reasoning:
+/// below the Tasks panel if that is open, otherwise at the right of the messages
+fn split_thinking_area(messages: Rect, todos: Option<Rect>, app: &App) -> (Rect, Option<Rect>, Option<Rect>) {
+    if !app.show_thinking || !app.is_loading || app.thinking.is_empty() {
+        return (messages, todos, None);
+    }
+    if let Some(todos) = todos {
+        let split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(todos);
+        return (messages, Some(split[0]), Some(split[1]));
+    }
+    let split = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(50), Constraint::Length(40)])
+        .split(messages);
+    (split[0], None, Some(split[1]))
+}
+
+/// The last `rows` wrapped lines of the reasoning, so the panel follows the stream
+fn thinking_lines(thinking: &str, width: usize, rows: usize) -> Vec<String> {
+    let lines: Vec<String> = thinking
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .flat_map(|l| wrap_text(l, width))
+        .collect();
+    lines[lines.len().saturating_sub(rows)..].to_vec()
+}
+
+/// Whether to draw colors, given environment lookups: off when NO_COLOR is set
+/// to anything non-empty (https://no-color.org) or on a dumb terminal
+pub fn colors_enabled_with(get: impl Fn(&str) -> Option<String>) -> bool {
+    let no_color = get("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let dumb = get("TERM").is_some_and(|t| t == "dumb");
+    !no_color && !dumb
+}
+
+/// Strip colors from a style, keeping modifiers. Backgrounds (used for
+/// selection highlights) become reverse video so they stay visible.
+pub fn monochrome(style: Style) -> Style {
+    let had_background = style.bg.is_some_and(|bg| bg != Color::Reset);
+    let style = Style {
+        fg: style.fg.map(|_| Color::Reset),
+        bg: style.bg.map(|_| Color::Reset),
+        underline_color: style.underline_color.map(|_| Color::Reset),
+        ..style
+    };
+    if had_background {
+        style.add_modifier(Modifier::REVERSED)
+    } else {
+        style
+    }
 }
 
 /// Format tool call for display
@@ -808,6 +996,21 @@ fn format_tool_call(name: &str, args: &str, max_len: usize) -> (&'static str, St
             let path = parsed.get("file_path").and_then(|v| v.as_str()).unwrap_or("?");
             ("📝", truncate(path, max_len))
         }
+        "EditCell" | "edit_cell" => {
+            let path = parsed.get("file_path").and_then(|v| v.as_str()).unwrap_or("?");
+            let cell = parsed.get("cell_index").and_then(|v| v.as_u64()).unwrap_or(0);
+            ("✏️", truncate(&format!("{} [cell {}]", path, cell), max_len))
+        }
+        "RenameSymbol" | "rename_symbol" => {
+            let old = parsed.get("old").and_then(|v| v.as_str()).unwrap_or("?");
+            let new = parsed.get("new").and_then(|v| v.as_str()).unwrap_or("?");
+            ("✏️", truncate(&format!("{} → {}", old, new), max_len))
+        }
+        "ProjectReplace" | "project_replace" => {
+            let pattern = parsed.get("pattern").and_then(|v| v.as_str()).unwrap_or("?");
+            let replacement = parsed.get("replacement").and_then(|v| v.as_str()).unwrap_or("?");
+            ("✏️", truncate(&format!("{} → {}", pattern, replacement), max_len))
+        }
         "Glob" | "glob" => {
             let pattern = parsed.get("pattern").and_then(|v| v.as_str()).unwrap_or("*");
             ("🔍", truncate(pattern, max_len))
@@ -816,6 +1019,17 @@ fn format_tool_call(name: &str, args: &str, max_len: usize) -> (&'static str, St
             let pattern = parsed.get("pattern").and_then(|v| v.as_str()).unwrap_or("?");
             ("🔎", truncate(&format!("/{}/", pattern), max_len))
         }
+        "EnvInfo" | "env_info" => ("🧭", truncate("environment", max_len)),
+        "Diagnostics" | "diagnostics" => ("🩺", truncate("diagnostics", max_len)),
+        "Summarize" | "summarize" => {
+            let from = parsed.get("from_turn").and_then(|v| v.as_u64()).unwrap_or(1);
+            let to = parsed.get("to_turn").and_then(|v| v.as_u64()).unwrap_or(0);
+            ("🗜", truncate(&format!("turns {}-{}", from, to), max_len))
+        }
+        "GitContext" | "git_context" => {
+            let path = parsed.get("file_path").and_then(|v| v.as_str()).unwrap_or("?");
+            ("🕘", truncate(path, max_len))
+        }
         "Bash" | "bash" | "shell" => {
             let cmd = parsed.get("command").and_then(|v| v.as_str()).unwrap_or("?");
             ("💻", truncate(cmd, max_len))
@@ -828,12 +1042,70 @@ fn format_tool_call(name: &str, args: &str, max_len: usize) -> (&'static str, St
             let query = parsed.get("query").and_then(|v| v.as_str()).unwrap_or("?");
             ("🌐", truncate(query, max_len))
         }
+        "SearchHistory" | "search_history" => {
+            let query = parsed.get("query").and_then(|v| v.as_str()).unwrap_or("?");
+            ("🗂", truncate(query, max_len))
+        }
         _ => ("⚙️", truncate(args, max_len.min(30)))
     }
 }
 
+/// Tool output made safe to draw: ANSI escape sequences (colors, cursor
+/// movement, titles), other control characters and zero-width characters are
+/// dropped, tabs become spaces, and a line rewritten with `\r` (progress bars)
+/// keeps only what was written last
+pub fn sanitize_terminal_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut line_start = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters and intermediates up to a final byte in @..~
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC (and DCS/APC/PM/SOS strings): up to BEL or ESC \
+                Some(']' | 'P' | '_' | '^' | 'X') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                // Two-character escapes (charset selection etc.)
+                _ => {}
+            },
+            '\n' => {
+                out.push('\n');
+                line_start = out.len();
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\r' => out.truncate(line_start),
+            '\t' => out.push_str("    "),
+            '\u{200b}' | '\u{200c}' | '\u{200d}' | '\u{2060}' | '\u{feff}' => {}
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Rows of a running tool call's output shown under it
+const LIVE_OUTPUT_ROWS: usize = 6;
+
+/// The last `rows` lines of streamed output
+fn live_output_tail(lines: &[String], rows: usize) -> &[String] {
+    &lines[lines.len().saturating_sub(rows)..]
+}
+
 /// Format tool result for display with diff coloring
 fn format_tool_result(content: &str, max_width: usize) -> Vec<Line<'static>> {
+    let content = &sanitize_terminal_text(content);
     let lines: Vec<&str> = content.lines().collect();
 
     if lines.is_empty() {
@@ -937,6 +1209,7 @@ fn format_tool_result(content: &str, max_width: usize) -> Vec<Line<'static>> {
 
 /// Format tool result for display - FULL version (expanded)
 fn format_tool_result_full(content: &str, max_width: usize) -> Vec<Line<'static>> {
+    let content = &sanitize_terminal_text(content);
     let lines: Vec<&str> = content.lines().collect();
 
     if lines.is_empty() {
@@ -974,13 +1247,63 @@ fn format_tool_result_full(content: &str, max_width: usize) -> Vec<Line<'static>
     result
 }
 
-fn truncate(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
+/// `[image: path]` lines for local images referenced in `content`, followed
+/// by blank rows the image is drawn into when the terminal supports it
+fn push_image_refs(content: &str, list_items: &mut Vec<ListItem>, image_slots: &mut Vec<(usize, String)>) {
+    let protocol = images::protocol();
+    for path in images::image_refs(content) {
+        list_items.push(ListItem::new(Line::from(Span::styled(
+            format!("   🖼 {}", images::placeholder(&path)),
+            Style::default().fg(Color::DarkGray)
+        ))));
+        if images::can_render(protocol, &path) {
+            image_slots.push((list_items.len(), path));
+            for _ in 0..IMAGE_ROWS {
+                list_items.push(ListItem::new(Line::from("")));
+            }
+        }
+    }
+}
+
+/// First visible option so that `selected` stays on screen, moving the
+/// current `offset` as little as possible
+fn planning_scroll_offset(selected: usize, offset: usize, rows: usize, total: usize) -> usize {
+    if rows == 0 || total <= rows {
+        return 0;
+    }
+    let offset = if selected < offset {
+        selected
+    } else if selected >= offset + rows {
+        selected + 1 - rows
+    } else {
+        offset
+    };
+    offset.min(total - rows)
+}
+
+/// The working directory for the status bar: under `home` it starts with "~",
+/// otherwise anything past 30 characters keeps only its last 27
+fn short_cwd(path: &str, home: Option<&str>) -> String {
+    if let Some(rest) = home.and_then(|home| path.strip_prefix(home)) {
+        return format!("~{}", rest);
+    }
+    let chars: Vec<char> = path.chars().collect();
+    if chars.len() > 30 {
+        format!("...{}", chars[chars.len() - 27..].iter().collect::<String>())
+    } else {
+        path.to_string()
+    }
+}
+
+/// Shorten `s` to at most `max_len` characters, ending in "…" when cut.
+/// Counts chars, not bytes, so multibyte paths and commands are never split mid-character
+pub fn truncate(s: &str, max_len: usize) -> String {
+    if s.chars().count() <= max_len {
         s.to_string()
     } else if max_len > 3 {
-        format!("{}…", &s[..max_len - 1])
+        format!("{}…", s.chars().take(max_len - 1).collect::<String>())
     } else {
-        s[..max_len].to_string()
+        s.chars().take(max_len).collect()
     }
 }
 
@@ -1003,3 +1326,217 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         ])
         .split(popup_layout[1])[1]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spinner_frame_follows_the_clock() {
+        let at = |ms: u64| spinner_frame(std::time::Duration::from_millis(ms));
+        assert_eq!(at(0), SPINNER_FRAMES[0]);
+        assert_eq!(at(79), SPINNER_FRAMES[0]);
+        assert_eq!(at(80), SPINNER_FRAMES[1]);
+        assert_eq!(at(80 * 9 + 40), SPINNER_FRAMES[9]);
+        // Wraps around after the last frame
+        assert_eq!(at(80 * 10), SPINNER_FRAMES[0]);
+        assert_eq!(at(80 * 23), SPINNER_FRAMES[3]);
+        // Long tasks keep animating
+        assert_eq!(at(3_600_000 + 160), SPINNER_FRAMES[2]);
+    }
+
+    #[test]
+    fn test_tool_output_renders_without_escape_sequences() {
+        let output = "\x1b[1;32m   Compiling\x1b[0m demo v0.1.0\n\x1b]0;cargo\x07\tDone\u{200b}\n 40%\r100%\r\n\x1b[31merror\x1b[0m: oops\x08";
+        assert_eq!(sanitize_terminal_text(output), "   Compiling demo v0.1.0\n    Done\n100%\nerror: oops");
+
+        for lines in [format_tool_result(output, 80), format_tool_result_full(output, 80)] {
+            let text: String = lines.iter().flat_map(|l| l.spans.iter()).map(|s| s.content.as_ref()).collect();
+            assert!(text.contains("Compiling demo v0.1.0"));
+            assert!(text.chars().all(|c| !c.is_control()), "{:?}", text);
+        }
+        // A colored error is still recognised as one
+        let error = format_tool_result("\x1b[31mError:\x1b[0m not found", 80);
+        assert_eq!(error[0].spans[0].content, "✗ Error: not found");
+    }
+
+    #[test]
+    fn test_monochrome_strips_colors_keeps_modifiers() {
+        let style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD | Modifier::ITALIC);
+        let plain = monochrome(style);
+        assert_eq!(plain.fg, Some(Color::Reset));
+        assert!(plain.add_modifier.contains(Modifier::BOLD | Modifier::ITALIC));
+        assert!(!plain.add_modifier.contains(Modifier::REVERSED));
+
+        // Highlight backgrounds turn into reverse video
+        let highlight = monochrome(Style::default().bg(Color::Rgb(50, 50, 70)).fg(Color::White));
+        assert_eq!((highlight.fg, highlight.bg), (Some(Color::Reset), Some(Color::Reset)));
+        assert!(highlight.add_modifier.contains(Modifier::REVERSED));
+
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+        };
+        assert!(colors_enabled_with(env(&[("TERM", "xterm-256color")])));
+        assert!(!colors_enabled_with(env(&[("NO_COLOR", "1")])));
+        assert!(colors_enabled_with(env(&[("NO_COLOR", "")])));
+        assert!(!colors_enabled_with(env(&[("TERM", "dumb")])));
+    }
+
+    #[test]
+    fn test_rerendering_stored_messages_is_stable() {
+        use ratatui::{backend::TestBackend, Terminal};
+
+        let client = crate::app::tests::test_client("grok-3");
+        let msg = crate::app::tests::msg;
+        let messages = vec![
+            msg("system", "You are Grok"),
+            msg("user", "show me a loop"),
+            msg("assistant", "## Loop\n\nUse **for**:\n\n```rust\nfor i in 0..3 {\n    println!(\"{}\", i);\n}\n```\n\n- one\n- two"),
+        ];
+        let mut app = App::new(client, messages.clone(), &crate::config::Config::default(), false, false);
+
+        let render = |app: &mut App| {
+            let mut terminal = Terminal::new(TestBackend::new(80, 40)).unwrap();
+            terminal.draw(|f| ui(f, app)).unwrap();
+            terminal.backend().buffer().clone()
+        };
+        let first = render(&mut app);
+        let second = render(&mut app);
+
+        assert_eq!(first, second);
+        // Rendering never rewrites the stored conversation
+        assert_eq!(app.messages.len(), messages.len());
+        assert_eq!(app.messages[2].content, messages[2].content);
+        let text: String = first.content().iter().map(|c| c.symbol()).collect();
+        assert!(text.contains("println!"));
+    }
+
+    #[test]
+    fn test_planning_scroll_keeps_selection_visible() {
+        // Everything fits: never scroll
+        assert_eq!(planning_scroll_offset(3, 0, 10, 5), 0);
+
+        // 20 options, 5 rows: moving down past the last row scrolls by one
+        assert_eq!(planning_scroll_offset(4, 0, 5, 20), 0);
+        assert_eq!(planning_scroll_offset(5, 0, 5, 20), 1);
+        assert_eq!(planning_scroll_offset(19, 0, 5, 20), 15);
+
+        // Moving within the window keeps the offset; moving above it scrolls up
+        assert_eq!(planning_scroll_offset(12, 10, 5, 20), 10);
+        assert_eq!(planning_scroll_offset(7, 10, 5, 20), 7);
+
+        // A stale offset (e.g. the modal grew) is clamped so no rows are wasted
+        assert_eq!(planning_scroll_offset(19, 18, 5, 20), 15);
+    }
+
+    #[test]
+    fn test_truncate_multibyte() {
+        assert_eq!(truncate("héllo", 10), "héllo");
+        assert_eq!(truncate("héllo wörld", 6), "héllo…");
+        assert_eq!(truncate("日本語のファイル名.rs", 5), "日本語の…");
+        assert_eq!(truncate("ééé", 2), "éé");
+        assert_eq!(truncate("🦀🦀🦀🦀🦀", 4), "🦀🦀🦀…");
+    }
+
+    #[test]
+    fn test_short_cwd_multibyte() {
+        assert_eq!(short_cwd("/home/ü/projekt", Some("/home/ü")), "~/projekt");
+        assert_eq!(short_cwd("/srv/app", Some("/home/ü")), "/srv/app");
+        let path = "/srv/プロジェクト/ドキュメント/説明書き/ソースコード/モジュール";
+        let short = short_cwd(path, None);
+        assert_eq!(short.chars().count(), 30);
+        assert!(short.starts_with("...") && path.ends_with(&short[3..]), "{}", short);
+    }
+
+    #[test]
+    fn test_format_tool_call_multibyte_args() {
+        let path = "src/ドキュメント/説明書き_ü.rs";
+        let args = serde_json::json!({
+            "file_path": path, "path": path, "command": "echo 'héllo wörld' > 日本.txt",
+            "pattern": "naïve→café", "replacement": "über", "old": "größe", "new": "tamaño",
+            "query": "très élevé ünïcödé", "cell_index": 2,
+        })
+        .to_string();
+        let names = [
+            "Read", "Edit", "Write", "EditCell", "RenameSymbol", "ProjectReplace", "Glob", "Grep",
+            "EnvInfo", "Diagnostics", "GitContext", "Bash", "List", "WebSearch", "SearchHistory", "UnknownTool",
+        ];
+        for name in names {
+            // Every cut point, so a multibyte character straddles one of them
+            for max_len in 0..40 {
+                let (_, desc) = format_tool_call(name, &args, max_len);
+                assert!(desc.chars().count() <= max_len.max(1), "{} at {}: {}", name, max_len, desc);
+            }
+        }
+        assert_eq!(format_tool_call("Read", &args, 12).1, "src/ドキュメント/…");
+        assert_eq!(format_tool_call("Bash", &args, 10).1, "echo 'hél…");
+    }
+
+    #[test]
+    fn test_todo_panel_truncates_multibyte_text() {
+        use ratatui::{backend::TestBackend, Terminal};
+
+        let mut app = crate::app::tests::test_app();
+        app.todos = vec![crate::app::TodoItem {
+            content: "Übersetze die Benutzeroberfläche ins Japanische: 日本語のテキストを確認する".repeat(3),
+            status: crate::app::TodoStatus::Pending,
+            active_form: "Übersetze".to_string(),
+        }];
+        for width in [20, 33, 47, 80] {
+            let mut terminal = Terminal::new(TestBackend::new(width, 30)).unwrap();
+            terminal.draw(|f| ui(f, &mut app)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_toggle_todo_panel_reclaims_width() {
+        let mut app = crate::app::tests::test_app();
+        let area = Rect::new(0, 0, 120, 30);
+
+        // No todos: no panel either way
+        assert_eq!(split_todos_area(area, &app), (area, None));
+
+        app.todos = vec![crate::app::TodoItem {
+            content: "Write tests".to_string(),
+            status: crate::app::TodoStatus::Pending,
+            active_form: "Writing tests".to_string(),
+        }];
+        let (messages, panel) = split_todos_area(area, &app);
+        assert_eq!((messages.width, panel.map(|p| p.width)), (80, Some(40)));
+
+        app.toggle_todos();
+        assert!(!app.show_todos);
+        assert_eq!(split_todos_area(area, &app), (area, None));
+
+        app.toggle_todos();
+        assert_eq!(split_todos_area(area, &app).0.width, 80);
+    }
+
+    #[test]
+    fn test_thinking_tokens_fill_panel_and_clear_on_finish() {
+        let mut app = crate::app::tests::test_app();
+        let area = Rect::new(0, 0, 120, 30);
+        let messages_before = app.messages.len();
+
+        app.is_loading = true;
+        app.task_start = Some(std::time::Instant::now());
+        for token in ["Check the ", "config first.\n", "Then run the tests."] {
+            app.push_thinking(token);
+        }
+        assert_eq!(app.thinking, "Check the config first.\nThen run the tests.");
+        assert_eq!(app.messages.len(), messages_before, "reasoning stays out of the chat");
+
+        let (messages, todos, panel) = split_thinking_area(area, None, &app);
+        assert_eq!((messages.width, todos, panel.map(|p| p.width)), (80, None, Some(40)));
+        assert_eq!(thinking_lines(&app.thinking, 12, 2), vec!["Then run the", "tests."]);
+
+        app.toggle_thinking();
+        assert_eq!(split_thinking_area(area, None, &app), (area, None, None));
+        app.toggle_thinking();
+
+        app.finish_task();
+        assert!(app.thinking.is_empty());
+        assert!(app.last_task_duration.is_some());
+        assert_eq!(split_thinking_area(area, None, &app), (area, None, None));
+    }
+}
@@ -0,0 +1,77 @@
+//! Extended version information for `--version` and `/version`
+//!
+//! Includes the resolved config path, active model, and build details so
+//! bug reports carry enough context to reproduce an issue.
+
+use crate::config::{get_config_path, Config};
+
+/// Inputs for the version report (collected separately so it can be tested)
+pub struct VersionInfo {
+    pub version: &'static str,
+    pub model: String,
+    pub config_path: String,
+    pub config_exists: bool,
+    pub build_profile: &'static str,
+    pub target: String,
+    pub plugin_count: usize,
+    pub sandbox_enabled: bool,
+}
+
+impl VersionInfo {
+    /// Collect version info for the running binary and loaded config
+    pub fn collect(config: &Config) -> Self {
+        let config_path = get_config_path();
+        VersionInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            model: config.model.clone(),
+            config_exists: config_path.exists(),
+            config_path: config_path.to_string_lossy().to_string(),
+            build_profile: if cfg!(debug_assertions) { "debug" } else { "release" },
+            target: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+            plugin_count: crate::tool_plugins::get_plugin_tool_descriptions().len(),
+            sandbox_enabled: config.sandbox_enabled,
+        }
+    }
+
+    /// Render the multi-line report
+    pub fn render(&self) -> String {
+        format!(
+            "grok-cli {}\n  Model:    {}\n  Config:   {}{}\n  Build:    {} ({})\n  Plugins:  {} loaded\n  Sandbox:  {}",
+            self.version,
+            self.model,
+            self.config_path,
+            if self.config_exists { "" } else { " (not created, using defaults)" },
+            self.build_profile,
+            self.target,
+            self.plugin_count,
+            if self.sandbox_enabled { "ON" } else { "OFF" },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_version_info() {
+        let info = VersionInfo {
+            version: "0.1.0",
+            model: "grok-3".to_string(),
+            config_path: "/home/user/.config/grok-cli/config.json".to_string(),
+            config_exists: false,
+            build_profile: "release",
+            target: "linux-x86_64".to_string(),
+            plugin_count: 5,
+            sandbox_enabled: true,
+        };
+
+        let report = info.render();
+        assert!(report.starts_with("grok-cli 0.1.0"));
+        assert!(report.contains("Model:    grok-3"));
+        assert!(report.contains("/home/user/.config/grok-cli/config.json (not created, using defaults)"));
+        assert!(report.contains("release (linux-x86_64)"));
+        assert!(report.contains("Plugins:  5 loaded"));
+        assert!(report.contains("Sandbox:  ON"));
+    }
+}
@@ -0,0 +1,554 @@
+//! Backends for the WebSearch tool
+//!
+//! DuckDuckGo's HTML page needs no setup but is scraped and rate-limited.
+//! A self-hosted SearxNG instance, or the Brave / Google Custom Search APIs
+//! (which need a key), return JSON and are more reliable. Any other JSON API
+//! can be used through the `json` backend by naming its fields in config.
+
+use serde::{Deserialize, Serialize};
+use reqwest::blocking::{Client, RequestBuilder};
+use serde_json::Value;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchBackend {
+    #[default]
+    DuckDuckGo,
+    SearxNG,
+    Brave,
+    Google,
+    /// Any JSON search API, described by `web_search.json`
+    Json,
+}
+
+impl SearchBackend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SearchBackend::DuckDuckGo => "DuckDuckGo",
+            SearchBackend::SearxNG => "SearxNG",
+            SearchBackend::Brave => "Brave",
+            SearchBackend::Google => "Google",
+            SearchBackend::Json => "JSON API",
+        }
+    }
+}
+
+/// Which search backend WebSearch uses, and its settings
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct WebSearchConfig {
+    pub backend: SearchBackend,
+    /// Base URL of the SearxNG instance (e.g. "http://localhost:8888"), or the
+    /// search endpoint of the `json` backend
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// API key for Brave or Google (sent as a bearer token by the `json` backend)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Google Custom Search engine ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cx: Option<String>,
+    /// Maximum number of results returned to the model
+    pub max_results: usize,
+    /// Seconds to wait for the backend before giving up
+    pub timeout_secs: u64,
+    /// Where the `json` backend finds its results
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json: Option<JsonApiFields>,
+}
+
+/// Layout of a generic JSON search API's response
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct JsonApiFields {
+    /// Query string parameter that carries the search terms
+    pub query_param: String,
+    /// JSON pointer to the array of results (e.g. "/data/hits")
+    pub results: String,
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+impl Default for JsonApiFields {
+    fn default() -> Self {
+        JsonApiFields {
+            query_param: "q".to_string(),
+            results: "/results".to_string(),
+            title: "title".to_string(),
+            url: "url".to_string(),
+            snippet: "snippet".to_string(),
+        }
+    }
+}
+
+impl Default for WebSearchConfig {
+    fn default() -> Self {
+        WebSearchConfig {
+            backend: SearchBackend::default(),
+            url: None,
+            api_key: None,
+            cx: None,
+            max_results: 5,
+            timeout_secs: 20,
+            json: None,
+        }
+    }
+}
+
+/// One search hit, whatever the backend
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+/// Search with the backend in `config`, formatted for the model
+pub fn search(query: &str, config: &WebSearchConfig) -> String {
+    let config = config.clone();
+    let max_results = config.max_results.max(1);
+    let query = query.to_string();
+
+    // reqwest's blocking client can't run on a tokio worker, so give it its own thread
+    let results = std::thread::spawn(move || search_with(&config, &query))
+        .join()
+        .unwrap_or_else(|_| Err("search thread panicked".to_string()));
+
+    match results {
+        Ok(results) if results.is_empty() => "No results found or unable to parse search results.".to_string(),
+        Ok(results) => format_results(&results[..results.len().min(max_results)]),
+        Err(e) => format!("Error performing search: {}", e),
+    }
+}
+
+/// A search backend: how to ask it, and how to read its answer
+pub trait SearchProvider {
+    fn name(&self) -> &'static str;
+    fn request(&self, client: &Client, query: &str) -> Result<RequestBuilder, String>;
+    fn parse(&self, body: &str) -> Result<Vec<SearchResult>, String>;
+}
+
+/// DuckDuckGo's HTML results page (no key, scraped)
+pub struct DuckDuckGo;
+
+impl SearchProvider for DuckDuckGo {
+    fn name(&self) -> &'static str {
+        SearchBackend::DuckDuckGo.name()
+    }
+
+    fn request(&self, client: &Client, query: &str) -> Result<RequestBuilder, String> {
+        Ok(client.get("https://html.duckduckgo.com/html/").query(&[("q", query)]))
+    }
+
+    fn parse(&self, body: &str) -> Result<Vec<SearchResult>, String> {
+        Ok(parse_duckduckgo(body))
+    }
+}
+
+/// A SearxNG instance with the JSON format enabled
+pub struct SearxNG {
+    pub base_url: String,
+}
+
+impl SearchProvider for SearxNG {
+    fn name(&self) -> &'static str {
+        SearchBackend::SearxNG.name()
+    }
+
+    fn request(&self, client: &Client, query: &str) -> Result<RequestBuilder, String> {
+        Ok(client
+            .get(format!("{}/search", self.base_url.trim_end_matches('/')))
+            .query(&[("q", query), ("format", "json")]))
+    }
+
+    fn parse(&self, body: &str) -> Result<Vec<SearchResult>, String> {
+        parse_json_results(body, "/results", "title", "url", "content")
+    }
+}
+
+pub struct Brave {
+    pub api_key: String,
+}
+
+impl SearchProvider for Brave {
+    fn name(&self) -> &'static str {
+        SearchBackend::Brave.name()
+    }
+
+    fn request(&self, client: &Client, query: &str) -> Result<RequestBuilder, String> {
+        Ok(client
+            .get("https://api.search.brave.com/res/v1/web/search")
+            .header("X-Subscription-Token", &self.api_key)
+            .query(&[("q", query)]))
+    }
+
+    fn parse(&self, body: &str) -> Result<Vec<SearchResult>, String> {
+        parse_json_results(body, "/web/results", "title", "url", "description")
+    }
+}
+
+/// Google Custom Search
+pub struct Google {
+    pub api_key: String,
+    pub cx: String,
+}
+
+impl SearchProvider for Google {
+    fn name(&self) -> &'static str {
+        SearchBackend::Google.name()
+    }
+
+    fn request(&self, client: &Client, query: &str) -> Result<RequestBuilder, String> {
+        Ok(client
+            .get("https://www.googleapis.com/customsearch/v1")
+            .query(&[("key", self.api_key.as_str()), ("cx", self.cx.as_str()), ("q", query)]))
+    }
+
+    fn parse(&self, body: &str) -> Result<Vec<SearchResult>, String> {
+        parse_json_results(body, "/items", "title", "link", "snippet")
+    }
+}
+
+/// Any JSON search API, with its layout taken from config
+pub struct JsonApi {
+    pub url: String,
+    pub api_key: Option<String>,
+    pub fields: JsonApiFields,
+}
+
+impl SearchProvider for JsonApi {
+    fn name(&self) -> &'static str {
+        SearchBackend::Json.name()
+    }
+
+    fn request(&self, client: &Client, query: &str) -> Result<RequestBuilder, String> {
+        let request = client.get(&self.url).query(&[(self.fields.query_param.as_str(), query)]);
+        Ok(match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        })
+    }
+
+    fn parse(&self, body: &str) -> Result<Vec<SearchResult>, String> {
+        let f = &self.fields;
+        parse_json_results(body, &f.results, &f.title, &f.url, &f.snippet)
+    }
+}
+
+/// The provider for the configured backend, or what its config is missing
+pub fn provider(config: &WebSearchConfig) -> Result<Box<dyn SearchProvider>, String> {
+    let api_key = || {
+        config.api_key.clone().filter(|k| !k.is_empty())
+            .ok_or_else(|| format!("web_search.api_key is required for the {} backend", config.backend.name()))
+    };
+    let url = || {
+        config.url.clone()
+            .ok_or_else(|| format!("web_search.url is required for the {} backend", config.backend.name()))
+    };
+
+    let provider: Box<dyn SearchProvider> = match config.backend {
+        SearchBackend::DuckDuckGo => Box::new(DuckDuckGo),
+        SearchBackend::SearxNG => Box::new(SearxNG { base_url: url()? }),
+        SearchBackend::Brave => Box::new(Brave { api_key: api_key()? }),
+        SearchBackend::Google => {
+            let cx = config.cx.clone().ok_or("web_search.cx is required for the Google backend")?;
+            Box::new(Google { api_key: api_key()?, cx })
+        }
+        SearchBackend::Json => Box::new(JsonApi {
+            url: url()?,
+            api_key: config.api_key.clone().filter(|k| !k.is_empty()),
+            fields: config.json.clone().unwrap_or_default(),
+        }),
+    };
+    Ok(provider)
+}
+
+fn search_with(config: &WebSearchConfig, query: &str) -> Result<Vec<SearchResult>, String> {
+    let provider = provider(config)?;
+    let timeout = config.timeout_secs.max(1);
+    let client = Client::builder()
+        .user_agent("Mozilla/5.0")
+        .timeout(std::time::Duration::from_secs(timeout))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let describe = |e: reqwest::Error| {
+        let host = e.url().and_then(|u| u.host_str()).unwrap_or("the server").to_string();
+        if e.is_timeout() {
+            format!("{} ({}) did not answer within {}s (web_search.timeout_secs)", provider.name(), host, timeout)
+        } else if e.is_connect() {
+            format!("could not connect to {} ({}); check your network connection", provider.name(), host)
+        } else {
+            format!("{} request failed: {}", provider.name(), e)
+        }
+    };
+
+    let response = provider.request(&client, query)?.send().map_err(describe)?;
+    if !response.status().is_success() {
+        return Err(format!("{} returned HTTP {}", provider.name(), response.status()));
+    }
+    let body = response.text().map_err(describe)?;
+    provider.parse(&body)
+}
+
+fn format_results(results: &[SearchResult]) -> String {
+    results
+        .iter()
+        .map(|r| {
+            let mut entry = format!("• {}", r.title);
+            if !r.url.is_empty() {
+                entry.push_str(&format!("\n  {}", r.url));
+            }
+            if !r.snippet.is_empty() {
+                entry.push_str(&format!("\n  {}", r.snippet));
+            }
+            entry
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .trim()
+        .to_string()
+}
+
+/// Results from a JSON API: an array at `pointer` of objects with a title
+/// field, a URL field and a snippet field
+fn parse_json_results(body: &str, pointer: &str, title_field: &str, url_field: &str, snippet_field: &str) -> Result<Vec<SearchResult>, String> {
+    let json: Value = serde_json::from_str(body).map_err(|e| format!("invalid JSON response: {}", e))?;
+    let results = json.pointer(pointer).and_then(|r| r.as_array()).cloned().unwrap_or_default();
+    Ok(results
+        .iter()
+        .filter_map(|r| {
+            let title = strip_tags(r[title_field].as_str()?);
+            Some(SearchResult {
+                title,
+                url: r[url_field].as_str().unwrap_or("").to_string(),
+                snippet: strip_tags(r[snippet_field].as_str().unwrap_or("")),
+            })
+        })
+        .collect())
+}
+
+/// Scrape DuckDuckGo's HTML results page
+fn parse_duckduckgo(html: &str) -> Vec<SearchResult> {
+    let mut results: Vec<SearchResult> = Vec::new();
+
+    // Results are <a class="result__a" href=...>title</a> followed by a result__snippet
+    for line in html.lines() {
+        if line.contains("result__a") && line.contains("href=") {
+            let title = line
+                .find('>')
+                .and_then(|start| line[start..].find("</a>").map(|end| strip_tags(&line[start + 1..start + end])));
+            if let Some(title) = title.filter(|t| !t.is_empty()) {
+                results.push(SearchResult { title, url: duckduckgo_target(line), snippet: String::new() });
+            }
+        } else if line.contains("result__snippet") {
+            if let Some(last) = results.last_mut().filter(|r| r.snippet.is_empty()) {
+                last.snippet = strip_tags(line);
+            }
+        }
+    }
+
+    results
+}
+
+/// The real destination of a DuckDuckGo redirect link (`//duckduckgo.com/l/?uddg=...`)
+fn duckduckgo_target(line: &str) -> String {
+    let Some(href) = line.split("href=\"").nth(1).and_then(|rest| rest.split('"').next()) else {
+        return String::new();
+    };
+    let href = href.replace("&amp;", "&");
+    let absolute = if href.starts_with("//") { format!("https:{}", href) } else { href.clone() };
+    match reqwest::Url::parse(&absolute) {
+        Ok(url) => url
+            .query_pairs()
+            .find(|(key, _)| key == "uddg")
+            .map(|(_, target)| target.into_owned())
+            .unwrap_or(absolute),
+        Err(_) => href,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_searxng_results() {
+        let body = r#"{
+            "query": "rust async",
+            "results": [
+                {"title": "Async in <b>Rust</b>", "url": "https://rust-lang.github.io/async-book/", "content": "The async book &amp; more", "engine": "ddg"},
+                {"title": "Tokio", "url": "https://tokio.rs", "engine": "google"},
+                {"url": "https://no-title.example"}
+            ]
+        }"#;
+        let results = SearxNG { base_url: String::new() }.parse(body).unwrap();
+
+        assert_eq!(results, vec![
+            SearchResult {
+                title: "Async in Rust".to_string(),
+                url: "https://rust-lang.github.io/async-book/".to_string(),
+                snippet: "The async book & more".to_string(),
+            },
+            SearchResult { title: "Tokio".to_string(), url: "https://tokio.rs".to_string(), snippet: String::new() },
+        ]);
+
+        assert!(SearxNG { base_url: String::new() }.parse(r#"{"results": []}"#).unwrap().is_empty());
+        assert!(SearxNG { base_url: String::new() }.parse("<html>").is_err());
+    }
+
+    #[test]
+    fn test_parse_duckduckgo_results() {
+        let html = r#"<a rel="nofollow" class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fdoc.rust-lang.org%2F&amp;rut=abc">The <b>Rust</b> docs</a>
+<a class="result__snippet" href="x">Official <b>documentation</b></a>"#;
+        let results = DuckDuckGo.parse(html).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "The Rust docs");
+        assert_eq!(results[0].url, "https://doc.rust-lang.org/");
+        assert_eq!(results[0].snippet, "Official documentation");
+    }
+
+    #[test]
+    fn test_parse_brave_and_google_results() {
+        let brave = r#"{"type": "search", "web": {"results": [
+            {"title": "Rust", "url": "https://www.rust-lang.org/", "description": "A language empowering <strong>everyone</strong>"}
+        ]}}"#;
+        let results = Brave { api_key: "k".to_string() }.parse(brave).unwrap();
+        assert_eq!(results, vec![SearchResult {
+            title: "Rust".to_string(),
+            url: "https://www.rust-lang.org/".to_string(),
+            snippet: "A language empowering everyone".to_string(),
+        }]);
+
+        let google = r#"{"kind": "customsearch#search", "items": [
+            {"title": "crates.io", "link": "https://crates.io/", "snippet": "The Rust community's crate registry"}
+        ]}"#;
+        let results = Google { api_key: "k".to_string(), cx: "c".to_string() }.parse(google).unwrap();
+        assert_eq!(results[0].url, "https://crates.io/");
+        assert_eq!(results[0].snippet, "The Rust community's crate registry");
+    }
+
+    #[test]
+    fn test_parse_generic_json_api_results() {
+        let config: WebSearchConfig = serde_json::from_str(r#"{
+            "backend": "json",
+            "url": "https://search.example/api",
+            "json": {"results": "/data/hits", "title": "name", "url": "link"}
+        }"#).unwrap();
+        let fields = config.json.clone().unwrap();
+        assert_eq!(fields.query_param, "q");
+        assert_eq!(fields.snippet, "snippet");
+
+        let body = r#"{"data": {"hits": [
+            {"name": "Result one", "link": "https://one.example", "snippet": "first"},
+            {"name": "Result two", "link": "https://two.example"}
+        ]}}"#;
+        let results = provider(&config).unwrap().parse(body).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0], SearchResult {
+            title: "Result one".to_string(),
+            url: "https://one.example".to_string(),
+            snippet: "first".to_string(),
+        });
+        assert_eq!(results[1].snippet, "");
+
+        // The endpoint is required
+        let missing_url = WebSearchConfig { backend: SearchBackend::Json, ..WebSearchConfig::default() };
+        assert!(provider(&missing_url).err().unwrap().contains("web_search.url"));
+    }
+
+    /// Serve one canned HTTP response on a local port, returning its base URL
+    /// and the request line the client sent
+    fn mock_server(status: &'static str, body: &'static str) -> (String, std::thread::JoinHandle<String>) {
+        use std::io::{BufRead, BufReader, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            // Skip the headers
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            write!(stream, "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body).unwrap();
+            request_line
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_search_against_mock_server() {
+        let (url, server) = mock_server("200 OK", r#"{"results": [{"title": "Tokio", "url": "https://tokio.rs", "content": "An async runtime"}]}"#);
+        let config = WebSearchConfig { backend: SearchBackend::SearxNG, url: Some(url), ..WebSearchConfig::default() };
+        let results = search_with(&config, "rust async").unwrap();
+        assert_eq!(results, vec![SearchResult {
+            title: "Tokio".to_string(),
+            url: "https://tokio.rs".to_string(),
+            snippet: "An async runtime".to_string(),
+        }]);
+        let request_line = server.join().unwrap();
+        assert!(request_line.starts_with("GET /search?q=rust+async&format=json "), "{}", request_line);
+
+        let (url, server) = mock_server("503 Service Unavailable", "");
+        let config = WebSearchConfig { backend: SearchBackend::Json, url: Some(url), ..WebSearchConfig::default() };
+        assert_eq!(search_with(&config, "x").unwrap_err(), "JSON API returned HTTP 503 Service Unavailable");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_search_network_errors_are_descriptive() {
+        // Nothing listening on the port
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let config = WebSearchConfig {
+            backend: SearchBackend::SearxNG,
+            url: Some(format!("http://127.0.0.1:{}", port)),
+            ..WebSearchConfig::default()
+        };
+        let error = search_with(&config, "x").unwrap_err();
+        assert!(error.starts_with("could not connect to SearxNG (127.0.0.1)"), "{}", error);
+
+        // Accepts the connection but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = WebSearchConfig {
+            backend: SearchBackend::SearxNG,
+            url: Some(format!("http://{}", listener.local_addr().unwrap())),
+            timeout_secs: 1,
+            ..WebSearchConfig::default()
+        };
+        let error = search_with(&config, "x").unwrap_err();
+        assert!(error.contains("did not answer within 1s"), "{}", error);
+        drop(listener);
+    }
+
+    #[test]
+    fn test_backend_config() {
+        let config: WebSearchConfig = serde_json::from_str(r#"{"backend": "searxng", "url": "http://localhost:8888"}"#).unwrap();
+        assert_eq!(config.backend, SearchBackend::SearxNG);
+        assert_eq!(config.max_results, 5);
+        assert_eq!(WebSearchConfig::default().backend, SearchBackend::DuckDuckGo);
+        assert_eq!(provider(&WebSearchConfig::default()).unwrap().name(), "DuckDuckGo");
+        assert!(provider(&WebSearchConfig { backend: SearchBackend::Brave, ..WebSearchConfig::default() }).is_err());
+    }
+}